@@ -72,7 +72,10 @@ fn main() {
     println!("cargo:rerun-if-changed=./src");
     println!("cargo:rerun-if-changed=./build.rs");
 
-    let version = "v0.1.x";
+    // The native release has to match the extern declarations, the
+    // signatures and struct layouts changed in place since v0.1.x, an older
+    // library would still link and be called with the wrong abi.
+    let version = "v0.2.x";
     let output_dir = env::var("OUT_DIR").unwrap();
     let temp = env::var("TEMP").unwrap();
 
@@ -178,7 +178,9 @@ impl Observer for ObserverImpl {
     // This event is triggered when the peer creates a data channel.
     fn on_data_channel(&self, channel: RTCDataChannel) {
         // Register a data sink for this data channel.
-        channel.register_sink(0, Sinker::new(ChannelSinkImpl {}));
+        channel
+            .add_sink(Sinker::new(ChannelSinkImpl {}))
+            .detach();
 
         // Next, we will continue to use this container to prevent automatic
         // release. This is a bad implementation, and it will not be
@@ -189,17 +191,21 @@ impl Observer for ObserverImpl {
 
     // This event is triggered when the peer creates a video track or audio
     // track.
-    fn on_track(&self, mut track: MediaStreamTrack) {
+    fn on_track(&self, mut event: RTCTrackEvent) {
         let audio_track = self.audio_track.clone();
 
         // Register sinks for audio and video tracks.
-        match &mut track {
+        match &mut event.track {
             MediaStreamTrack::Video(track) => {
-                track.register_sink(0, Sinker::new(VideoPlayer::new(track.label().to_string())));
+                track
+                    .add_sink(Sinker::new(VideoPlayer::new(track.label().to_string())))
+                    .detach();
             }
             MediaStreamTrack::Audio(track) => {
                 if let MediaStreamTrack::Audio(at) = audio_track {
-                    track.register_sink(0, Sinker::new(AudioPlayer::new(at.clone())));
+                    track
+                        .add_sink(Sinker::new(AudioPlayer::new(at.clone())))
+                        .detach();
                 }
             }
         }
@@ -208,7 +214,7 @@ impl Observer for ObserverImpl {
         // release. This is a bad implementation, and it will not be
         // implemented in a normal process, but for a simpler implementation
         // example, this bad method will be used here.
-        let _ = ManuallyDrop::new(track);
+        let _ = ManuallyDrop::new(event);
     }
 }
 
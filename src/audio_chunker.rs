@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use crate::{pacer::Pacer, AudioFrame, AudioTrack};
+
+/// Webrtc processes audio in units of 10 milliseconds.
+pub(crate) const FRAME_DURATION: Duration = Duration::from_millis(10);
+
+/// Splits interleaved pcm buffers of arbitrary size into 10ms audio frames.
+///
+/// Audio from files, speech engines or capture callbacks arrives in whatever
+/// size the producer likes, but webrtc wants exactly 10ms of pcm per
+/// `AudioTrack::add_frame`. The chunker keeps the remainder between calls
+/// and stamps every frame with a running timestamp in milliseconds.
+///
+/// At rates that are not a multiple of 100, such as 22050Hz, 10ms is not a
+/// whole number of samples. The frames then alternate between the rounded
+/// down and the rounded up size, so that 100 frames are exactly one second.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let samples = vec![0i16; 4096];
+/// let track = AudioTrack::new("audio")?;
+/// let mut chunker = AudioFrameChunker::new(44100, 2);
+/// chunker.set_pacing(true);
+///
+/// // Any buffer size is fine, the chunker pushes 10ms frames to the track
+/// // and waits between frames so that the track is not overrun.
+/// chunker.write(&track, &samples);
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioFrameChunker {
+    sample_rate: usize,
+    channels: u8,
+    // The number of frames taken out in the current second, it decides the
+    // size of the next frame.
+    frames: usize,
+    timestamp: usize,
+    buf: Vec<i16>,
+    pacer: Option<Pacer>,
+}
+
+impl AudioFrameChunker {
+    /// Create a chunker for interleaved pcm at the given sample rate and
+    /// channel count.
+    pub fn new(sample_rate: usize, channels: u8) -> Self {
+        assert!(channels > 0);
+        assert!(sample_rate >= 100);
+
+        Self {
+            buf: Vec::with_capacity(sample_rate.div_ceil(100) * channels as usize * 2),
+            pacer: None,
+            timestamp: 0,
+            frames: 0,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// When pacing is enabled, `write` waits between frames so that the
+    /// output runs at real time speed no matter how fast the input arrives.
+    pub fn set_pacing(&mut self, enable: bool) {
+        self.pacer = if enable {
+            Some(Pacer::new(FRAME_DURATION))
+        } else {
+            None
+        };
+    }
+
+    /// Set the timestamp of the next frame, in milliseconds.
+    pub fn set_timestamp(&mut self, timestamp: usize) {
+        self.timestamp = timestamp;
+    }
+
+    /// The sample rate of the produced frames.
+    pub fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    /// The channel count of the produced frames.
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// The number of samples per channel in one 10ms frame, rounded down.
+    pub fn frame_size(&self) -> usize {
+        self.sample_rate / 100
+    }
+
+    /// The number of samples per channel held back until the next call.
+    pub fn pending(&self) -> usize {
+        self.buf.len() / self.channels as usize
+    }
+
+    /// Append interleaved pcm and take out all complete 10ms frames.
+    ///
+    /// This never waits, pacing only applies to `write`.
+    pub fn push(&mut self, buf: &[i16]) -> Vec<AudioFrame> {
+        self.buf.extend_from_slice(buf);
+
+        let channels = self.channels as usize;
+        let mut frames = Vec::with_capacity(self.buf.len() / (self.frame_size() * channels));
+        let mut offset = 0;
+        loop {
+            let size = self.next_frame_size() * channels;
+            if self.buf.len() - offset < size {
+                break;
+            }
+
+            let chunk = self.buf[offset..offset + size].to_vec();
+            frames.push(self.make_frame(chunk));
+            offset += size;
+        }
+
+        // Only the remainder is moved, once per call.
+        self.buf.drain(..offset);
+        frames
+    }
+
+    /// Append interleaved pcm and push all complete 10ms frames into the
+    /// audio track, waiting between frames if pacing is enabled.
+    pub fn write(&mut self, track: &AudioTrack, buf: &[i16]) {
+        for frame in self.push(buf) {
+            if let Some(pacer) = self.pacer.as_mut() {
+                pacer.wait();
+            }
+
+            track.add_frame(&frame);
+        }
+    }
+
+    /// Take out the remainder as a final frame, padded with silence to the
+    /// full 10ms.
+    pub fn flush(&mut self) -> Option<AudioFrame> {
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        let mut chunk = std::mem::take(&mut self.buf);
+        chunk.resize(self.next_frame_size() * self.channels as usize, 0);
+        Some(self.make_frame(chunk))
+    }
+
+    /// Drop the remainder and restart the timestamp and the pacing clock.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.timestamp = 0;
+        self.frames = 0;
+        if self.pacer.is_some() {
+            self.pacer = Some(Pacer::new(FRAME_DURATION));
+        }
+    }
+
+    /// The samples per channel of the next frame, the fraction of a sample
+    /// left over by the previous frames is carried into this one.
+    fn next_frame_size(&self) -> usize {
+        (self.frames + 1) * self.sample_rate / 100 - self.frames * self.sample_rate / 100
+    }
+
+    fn make_frame(&mut self, chunk: Vec<i16>) -> AudioFrame {
+        let frame = AudioFrame::from_pcm(self.sample_rate, self.channels, self.timestamp, chunk);
+        self.timestamp += FRAME_DURATION.as_millis() as usize;
+        self.frames = (self.frames + 1) % 100;
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        let mut chunker = AudioFrameChunker::new(48000, 2);
+        let samples = (0..4000).map(|i| i as i16).collect::<Vec<_>>();
+
+        // 3 frames of 960 samples, odd sized buffers.
+        let mut frames = Vec::new();
+        for buf in samples[..2900].chunks(7) {
+            frames.extend(chunker.push(buf));
+        }
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(chunker.pending(), 10);
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(frame.frames(), 480);
+            assert_eq!(frame.timestamp(), index as i64 * 10);
+            assert_eq!(frame.as_ref(), &samples[index * 960..(index + 1) * 960]);
+        }
+
+        let frame = chunker.flush().unwrap();
+        assert_eq!(&frame.as_ref()[..20], &samples[2880..2900]);
+        assert!(frame.as_ref()[20..].iter().all(|sample| *sample == 0));
+        assert_eq!(frame.timestamp(), 30);
+        assert!(chunker.flush().is_none());
+    }
+
+    #[test]
+    fn fractional_rate() {
+        for sample_rate in [22050, 11025, 44100, 8000] {
+            let mut chunker = AudioFrameChunker::new(sample_rate, 1);
+            let frames = chunker.push(&vec![1; sample_rate * 3]);
+            assert_eq!(frames.len(), 300);
+            assert_eq!(chunker.pending(), 0);
+
+            // Every frame is within one sample of 10ms.
+            for frame in &frames {
+                assert!(
+                    frame.frames() == sample_rate / 100
+                        || frame.frames() == sample_rate.div_ceil(100)
+                );
+            }
+
+            let total = frames.iter().map(|frame| frame.frames()).sum::<usize>();
+            assert_eq!(total, sample_rate * 3);
+            assert_eq!(frames[299].timestamp(), 2990);
+        }
+    }
+
+    #[test]
+    fn large_push() {
+        // A whole minute at once stays linear.
+        let mut chunker = AudioFrameChunker::new(48000, 2);
+        let frames = chunker.push(&vec![0; 48000 * 2 * 60]);
+        assert_eq!(frames.len(), 6000);
+    }
+}
@@ -0,0 +1,561 @@
+use std::ffi::{c_char, c_int, c_void};
+
+use crate::{
+    cstr::{free_cstring, to_c_str, StringError},
+    media_engine::{codec_result, RawCodecParameter, RawCodecParameters},
+    CodecError,
+};
+
+/// An audio format as advertised in the sdp, the rtpmap encoding name,
+/// clock rate and channels plus the fmtp parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub name: String,
+    pub clock_rate: u32,
+    pub channels: u8,
+    pub parameters: Vec<(String, String)>,
+    /// The nominal bitrate in bps, used by the native bitrate allocation
+    /// and not part of the sdp.
+    pub bitrate: u32,
+}
+
+impl AudioFormat {
+    pub fn new(name: &str, clock_rate: u32, channels: u8, bitrate: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters: Vec::new(),
+            clock_rate,
+            channels,
+            bitrate,
+        }
+    }
+
+    /// Add an fmtp parameter.
+    pub fn with_parameter(mut self, key: &str, value: &str) -> Self {
+        self.parameters.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawAudioFormat {
+    name: *const c_char,
+    clock_rate: c_int,
+    channels: c_int,
+    bitrate: c_int,
+    parameters: *const RawCodecParameter,
+    parameters_size: c_int,
+}
+
+/// The raw formats of a factory, owning the strings.
+struct RawAudioFormats {
+    formats: Vec<RawAudioFormat>,
+    #[allow(dead_code)]
+    parameters: Vec<RawCodecParameters>,
+}
+
+impl RawAudioFormats {
+    fn new(formats: &[AudioFormat]) -> Result<Self, StringError> {
+        let parameters = formats
+            .iter()
+            .map(|format| RawCodecParameters::new(&format.parameters))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Drop frees the names converted before a failure.
+        let mut raw = Self {
+            formats: Vec::with_capacity(formats.len()),
+            parameters,
+        };
+
+        for (format, parameters) in formats.iter().zip(&raw.parameters) {
+            raw.formats.push(RawAudioFormat {
+                name: to_c_str(&format.name)?,
+                clock_rate: format.clock_rate as c_int,
+                channels: format.channels as c_int,
+                bitrate: format.bitrate as c_int,
+                parameters: parameters.as_ptr(),
+                parameters_size: parameters.len(),
+            });
+        }
+
+        Ok(raw)
+    }
+}
+
+impl Drop for RawAudioFormats {
+    fn drop(&mut self) {
+        for format in &self.formats {
+            free_cstring(format.name);
+        }
+    }
+}
+
+/// An audio encoder implemented in rust.
+#[allow(unused)]
+pub trait AudioEncoder: Send {
+    /// The sample rate of the pcm the encoder takes.
+    fn sample_rate(&self) -> u32;
+    fn channels(&self) -> u8;
+    /// Encode 10ms of interleaved pcm, returning `None` while the encoder
+    /// collects more audio for the next packet.
+    fn encode(&mut self, rtp_timestamp: u32, pcm: &[i16]) -> Result<Option<Vec<u8>>, CodecError>;
+    /// The target bitrate in bps from the congestion controller.
+    fn set_target_bitrate(&mut self, bitrate: u32) {}
+}
+
+/// An audio decoder implemented in rust.
+pub trait AudioDecoder: Send {
+    /// The sample rate of the decoded pcm.
+    fn sample_rate(&self) -> u32;
+    fn channels(&self) -> u8;
+    /// Decode a payload into interleaved pcm.
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, CodecError>;
+}
+
+/// Creates the audio encoders of a peer connection.
+pub trait AudioEncoderFactory: Send + Sync {
+    /// The formats offered in the sdp, in order of preference. Only read
+    /// once, when the media engine is created.
+    fn supported_formats(&self) -> Vec<AudioFormat>;
+    /// Create an encoder for one of the supported formats.
+    fn create_encoder(&self, format: &AudioFormat) -> Option<Box<dyn AudioEncoder>>;
+}
+
+/// Creates the audio decoders of a peer connection.
+pub trait AudioDecoderFactory: Send + Sync {
+    /// The formats accepted in the sdp, in order of preference. Only read
+    /// once, when the media engine is created.
+    fn supported_formats(&self) -> Vec<AudioFormat>;
+    /// Create a decoder for one of the supported formats.
+    fn create_decoder(&self, format: &AudioFormat) -> Option<Box<dyn AudioDecoder>>;
+}
+
+pub(crate) type AudioEncoderContext = Box<dyn AudioEncoder>;
+pub(crate) type AudioDecoderContext = Box<dyn AudioDecoder>;
+type EncodedPayloadCallback = extern "C" fn(*const c_void, *const u8, usize);
+type DecodedPcmCallback = extern "C" fn(*const c_void, *const i16, usize);
+
+/// The encoder factory vtable, native picks a format by its index in the
+/// formats array.
+#[repr(C)]
+#[rustfmt::skip]
+pub(crate) struct RawAudioEncoderFactory {
+    ctx: *const AudioEncoderFactoryRef,
+    formats: *const RawAudioFormat,
+    formats_size: c_int,
+    create: extern "C" fn(&AudioEncoderFactoryRef, c_int) -> *mut AudioEncoderContext,
+    sample_rate: extern "C" fn(&AudioEncoderContext) -> c_int,
+    channels: extern "C" fn(&AudioEncoderContext) -> c_int,
+    encode: extern "C" fn(&mut AudioEncoderContext, u32, *const i16, usize, EncodedPayloadCallback, *const c_void) -> c_int,
+    set_target_bitrate: extern "C" fn(&mut AudioEncoderContext, c_int),
+    release: extern "C" fn(*mut AudioEncoderContext),
+}
+
+/// Owns a rust encoder factory and its vtable.
+pub(crate) struct AudioEncoderFactoryRef {
+    raw: RawAudioEncoderFactory,
+    factory: Box<dyn AudioEncoderFactory>,
+    formats: Vec<AudioFormat>,
+    #[allow(dead_code)]
+    raw_formats: RawAudioFormats,
+}
+
+impl AudioEncoderFactoryRef {
+    pub(crate) fn new(factory: Box<dyn AudioEncoderFactory>) -> Result<Box<Self>, StringError> {
+        let formats = factory.supported_formats();
+        let raw_formats = RawAudioFormats::new(&formats)?;
+        let mut this = Box::new(Self {
+            raw: RawAudioEncoderFactory {
+                ctx: std::ptr::null(),
+                formats: raw_formats.formats.as_ptr(),
+                formats_size: raw_formats.formats.len() as c_int,
+                create: create_audio_encoder,
+                sample_rate: get_audio_encoder_sample_rate,
+                channels: get_audio_encoder_channels,
+                encode: encode_audio_frame,
+                set_target_bitrate: set_audio_encoder_bitrate,
+                release: release_audio_encoder,
+            },
+            raw_formats,
+            formats,
+            factory,
+        });
+
+        this.raw.ctx = &*this;
+        Ok(this)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawAudioEncoderFactory {
+        &self.raw
+    }
+}
+
+#[no_mangle]
+extern "C" fn create_audio_encoder(
+    ctx: &AudioEncoderFactoryRef,
+    index: c_int,
+) -> *mut AudioEncoderContext {
+    ctx.formats
+        .get(index as usize)
+        .and_then(|format| ctx.factory.create_encoder(format))
+        .map(|encoder| Box::into_raw(Box::new(encoder)))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[no_mangle]
+extern "C" fn get_audio_encoder_sample_rate(encoder: &AudioEncoderContext) -> c_int {
+    encoder.sample_rate() as c_int
+}
+
+#[no_mangle]
+extern "C" fn get_audio_encoder_channels(encoder: &AudioEncoderContext) -> c_int {
+    encoder.channels() as c_int
+}
+
+/// The pcm is borrowed for the call, the payload is only valid during the
+/// callback, which is not called if the encoder has no output yet.
+#[no_mangle]
+extern "C" fn encode_audio_frame(
+    encoder: &mut AudioEncoderContext,
+    rtp_timestamp: u32,
+    data: *const i16,
+    size: usize,
+    callback: EncodedPayloadCallback,
+    callback_ctx: *const c_void,
+) -> c_int {
+    let pcm = if data.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, size) }
+    };
+    match encoder.encode(rtp_timestamp, pcm) {
+        Ok(Some(payload)) => {
+            callback(callback_ctx, payload.as_ptr(), payload.len());
+            0
+        }
+        ret => codec_result(ret),
+    }
+}
+
+#[no_mangle]
+extern "C" fn set_audio_encoder_bitrate(encoder: &mut AudioEncoderContext, bitrate: c_int) {
+    encoder.set_target_bitrate(bitrate.max(0) as u32)
+}
+
+#[no_mangle]
+extern "C" fn release_audio_encoder(encoder: *mut AudioEncoderContext) {
+    if !encoder.is_null() {
+        drop(unsafe { Box::from_raw(encoder) });
+    }
+}
+
+/// The decoder factory vtable, native picks a format by its index in the
+/// formats array.
+#[repr(C)]
+#[rustfmt::skip]
+pub(crate) struct RawAudioDecoderFactory {
+    ctx: *const AudioDecoderFactoryRef,
+    formats: *const RawAudioFormat,
+    formats_size: c_int,
+    create: extern "C" fn(&AudioDecoderFactoryRef, c_int) -> *mut AudioDecoderContext,
+    sample_rate: extern "C" fn(&AudioDecoderContext) -> c_int,
+    channels: extern "C" fn(&AudioDecoderContext) -> c_int,
+    decode: extern "C" fn(&mut AudioDecoderContext, *const u8, usize, DecodedPcmCallback, *const c_void) -> c_int,
+    release: extern "C" fn(*mut AudioDecoderContext),
+}
+
+/// Owns a rust decoder factory and its vtable.
+pub(crate) struct AudioDecoderFactoryRef {
+    raw: RawAudioDecoderFactory,
+    factory: Box<dyn AudioDecoderFactory>,
+    formats: Vec<AudioFormat>,
+    #[allow(dead_code)]
+    raw_formats: RawAudioFormats,
+}
+
+impl AudioDecoderFactoryRef {
+    pub(crate) fn new(factory: Box<dyn AudioDecoderFactory>) -> Result<Box<Self>, StringError> {
+        let formats = factory.supported_formats();
+        let raw_formats = RawAudioFormats::new(&formats)?;
+        let mut this = Box::new(Self {
+            raw: RawAudioDecoderFactory {
+                ctx: std::ptr::null(),
+                formats: raw_formats.formats.as_ptr(),
+                formats_size: raw_formats.formats.len() as c_int,
+                create: create_audio_decoder,
+                sample_rate: get_audio_decoder_sample_rate,
+                channels: get_audio_decoder_channels,
+                decode: decode_audio_payload,
+                release: release_audio_decoder,
+            },
+            raw_formats,
+            formats,
+            factory,
+        });
+
+        this.raw.ctx = &*this;
+        Ok(this)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawAudioDecoderFactory {
+        &self.raw
+    }
+}
+
+#[no_mangle]
+extern "C" fn create_audio_decoder(
+    ctx: &AudioDecoderFactoryRef,
+    index: c_int,
+) -> *mut AudioDecoderContext {
+    ctx.formats
+        .get(index as usize)
+        .and_then(|format| ctx.factory.create_decoder(format))
+        .map(|decoder| Box::into_raw(Box::new(decoder)))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[no_mangle]
+extern "C" fn get_audio_decoder_sample_rate(decoder: &AudioDecoderContext) -> c_int {
+    decoder.sample_rate() as c_int
+}
+
+#[no_mangle]
+extern "C" fn get_audio_decoder_channels(decoder: &AudioDecoderContext) -> c_int {
+    decoder.channels() as c_int
+}
+
+/// The payload is borrowed for the call, the pcm is only valid during the
+/// callback.
+#[no_mangle]
+extern "C" fn decode_audio_payload(
+    decoder: &mut AudioDecoderContext,
+    data: *const u8,
+    size: usize,
+    callback: DecodedPcmCallback,
+    callback_ctx: *const c_void,
+) -> c_int {
+    let payload = if data.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, size) }
+    };
+
+    match decoder.decode(payload) {
+        Ok(pcm) => {
+            callback(callback_ctx, pcm.as_ptr(), pcm.len());
+            0
+        }
+        Err(e) => e.code(),
+    }
+}
+
+#[no_mangle]
+extern "C" fn release_audio_decoder(decoder: *mut AudioDecoderContext) {
+    if !decoder.is_null() {
+        drop(unsafe { Box::from_raw(decoder) });
+    }
+}
+
+/// Uncompressed 16 bit linear pcm, the L16 payload format of RFC 3551.
+///
+/// Advertised as `L16/<rate>/<channels>` in the sdp. Every 10ms of audio
+/// is sent as one packet, which is 1920 bytes for 48kHz stereo.
+#[derive(Clone, Copy, Debug)]
+pub struct L16AudioCodec {
+    sample_rate: u32,
+    channels: u8,
+}
+
+impl L16AudioCodec {
+    pub fn new(sample_rate: u32, channels: u8) -> Self {
+        assert!(channels > 0);
+        assert_eq!(sample_rate % 100, 0);
+
+        Self {
+            sample_rate,
+            channels,
+        }
+    }
+
+    fn format(&self) -> AudioFormat {
+        AudioFormat::new(
+            "L16",
+            self.sample_rate,
+            self.channels,
+            self.sample_rate * self.channels as u32 * 16,
+        )
+    }
+
+    fn accepts(&self, format: &AudioFormat) -> bool {
+        format.name.eq_ignore_ascii_case("L16")
+            && format.clock_rate == self.sample_rate
+            && format.channels == self.channels
+    }
+}
+
+impl Default for L16AudioCodec {
+    fn default() -> Self {
+        Self::new(48000, 2)
+    }
+}
+
+impl AudioEncoderFactory for L16AudioCodec {
+    fn supported_formats(&self) -> Vec<AudioFormat> {
+        vec![self.format()]
+    }
+
+    fn create_encoder(&self, format: &AudioFormat) -> Option<Box<dyn AudioEncoder>> {
+        self.accepts(format)
+            .then(|| Box::new(*self) as Box<dyn AudioEncoder>)
+    }
+}
+
+impl AudioDecoderFactory for L16AudioCodec {
+    fn supported_formats(&self) -> Vec<AudioFormat> {
+        vec![self.format()]
+    }
+
+    fn create_decoder(&self, format: &AudioFormat) -> Option<Box<dyn AudioDecoder>> {
+        self.accepts(format)
+            .then(|| Box::new(*self) as Box<dyn AudioDecoder>)
+    }
+}
+
+impl AudioEncoder for L16AudioCodec {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn encode(&mut self, _: u32, pcm: &[i16]) -> Result<Option<Vec<u8>>, CodecError> {
+        Ok(Some(
+            pcm.iter().flat_map(|sample| sample.to_be_bytes()).collect(),
+        ))
+    }
+}
+
+impl AudioDecoder for L16AudioCodec {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, CodecError> {
+        // A payload always carries whole samples of every channel.
+        match payload.len() % (2 * self.channels as usize) {
+            0 => Ok(payload
+                .chunks_exact(2)
+                .map(|sample| i16::from_be_bytes([sample[0], sample[1]]))
+                .collect()),
+            _ => Err(CodecError::InvalidParameter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec_test::{negotiate, rtpmaps},
+        cstr::c_str_to_str,
+        MediaEngine, MediaStreamTrack,
+    };
+
+    #[test]
+    fn l16_round_trip() {
+        let codec = L16AudioCodec::default();
+        let format = AudioFormat::new("L16", 48000, 2, 0);
+        let mut encoder = codec.create_encoder(&format).unwrap();
+        let mut decoder = codec.create_decoder(&format).unwrap();
+
+        let mut pcm = (0..960).map(|i| (i * 67) as i16).collect::<Vec<_>>();
+        pcm[..4].copy_from_slice(&[i16::MIN, i16::MAX, -1, 0x0102]);
+
+        let payload = encoder.encode(0, &pcm).unwrap().unwrap();
+        assert_eq!(payload.len(), 1920);
+        assert_eq!(
+            payload[..8],
+            [0x80, 0x00, 0x7f, 0xff, 0xff, 0xff, 0x01, 0x02]
+        );
+        assert_eq!(decoder.decode(&payload).unwrap(), pcm);
+    }
+
+    #[test]
+    fn l16_invalid_payload() {
+        let mut decoder = L16AudioCodec::new(16000, 2);
+        assert!(decoder.decode(&[]).unwrap().is_empty());
+        assert!(matches!(
+            decoder.decode(&[0; 3]),
+            Err(CodecError::InvalidParameter)
+        ));
+
+        // One sample is missing its second channel.
+        assert!(matches!(
+            decoder.decode(&[0; 6]),
+            Err(CodecError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn l16_formats() {
+        let codec = L16AudioCodec::new(16000, 1);
+        assert_eq!(
+            AudioEncoderFactory::supported_formats(&codec),
+            [AudioFormat::new("L16", 16000, 1, 256_000)]
+        );
+
+        assert!(codec
+            .create_decoder(&AudioFormat::new("l16", 16000, 1, 0))
+            .is_some());
+        for format in [
+            AudioFormat::new("L16", 48000, 1, 0),
+            AudioFormat::new("L16", 16000, 2, 0),
+            AudioFormat::new("PCMU", 16000, 1, 0),
+        ] {
+            assert!(codec.create_encoder(&format).is_none());
+            assert!(codec.create_decoder(&format).is_none());
+        }
+    }
+
+    #[test]
+    fn l16_rtpmap() {
+        // Native builds the rtpmap `L16/16000/1` from the raw format.
+        let codec = L16AudioCodec::new(16000, 1);
+        let formats = AudioDecoderFactory::supported_formats(&codec);
+        let raw = RawAudioFormats::new(&formats).unwrap();
+        assert_eq!(raw.formats.len(), 1);
+
+        let format = &raw.formats[0];
+        assert_eq!(c_str_to_str(format.name).unwrap(), "L16");
+        assert_eq!((format.clock_rate, format.channels), (16000, 1));
+        assert_eq!(format.parameters_size, 0);
+
+        let invalid = [AudioFormat::new("L16\0", 16000, 1, 0)];
+        assert!(matches!(
+            RawAudioFormats::new(&invalid),
+            Err(StringError::NulError)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs the native library"]
+    fn l16_negotiation() {
+        let engine = || {
+            MediaEngine::default()
+                .with_audio_encoder_factory(L16AudioCodec::default())
+                .with_audio_decoder_factory(L16AudioCodec::default())
+        };
+
+        let track = MediaStreamTrack::create_audio_track("audio").unwrap();
+        let (offer, answer) = negotiate(engine, track);
+        for sdp in [offer, answer] {
+            assert!(rtpmaps(&sdp, "audio").contains(&"L16/48000/2".to_string()));
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    ffi::{c_int, c_void},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    audio_resampler::FormatConverter,
+    frame_stream::{FramePolicy, FrameStream},
+    pacer::Pacer,
+    sink::SinkRegistry,
+    trace::rtc_span,
+    AudioFrame, AudioSource, SinkHandle, Sinker,
+};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_audio_transport_record(
+        transport: *const crate::audio_device::RawAudioTransport,
+        frame: *const crate::audio_frame::RawAudioFrame,
+    ) -> c_int;
+
+    pub(crate) fn rtc_audio_transport_playout(
+        transport: *const crate::audio_device::RawAudioTransport,
+        sample_rate: c_int,
+        channels: c_int,
+        frames: usize,
+        buf: *mut i16,
+    ) -> c_int;
+}
+
+pub(crate) type RawAudioTransport = c_void;
+
+/// The native side of an audio device, recorded audio is delivered to it
+/// and the mixed playout audio is pulled from it.
+///
+/// The transport becomes inert once native unregisters it, recording is
+/// then ignored and playout returns `None`.
+#[derive(Clone)]
+pub struct AudioTransport {
+    raw: Arc<RwLock<Option<usize>>>,
+}
+
+impl AudioTransport {
+    /// Deliver recorded audio, as a microphone would.
+    pub fn record(&self, frame: &AudioFrame) -> bool {
+        match *self.raw.read().unwrap() {
+            Some(raw) => unsafe {
+                rtc_audio_transport_record(raw as *const RawAudioTransport, frame.get_raw()) == 0
+            },
+            None => false,
+        }
+    }
+
+    /// Pull 10ms of the mixed audio of all remote tracks, as a speaker
+    /// would play it. The timestamp of the frame is in milliseconds.
+    pub fn playout(
+        &self,
+        sample_rate: usize,
+        channels: u8,
+        timestamp: usize,
+    ) -> Option<AudioFrame> {
+        let raw = (*self.raw.read().unwrap())? as *const RawAudioTransport;
+        let frames = sample_rate / 100;
+        let mut buf = vec![0i16; frames * channels as usize];
+        let ret = unsafe {
+            rtc_audio_transport_playout(
+                raw,
+                sample_rate as c_int,
+                channels as c_int,
+                frames,
+                buf.as_mut_ptr(),
+            )
+        };
+
+        (ret == 0).then(|| AudioFrame::from_pcm(sample_rate, channels, timestamp, buf))
+    }
+}
+
+/// An audio device implemented in rust, replacing the sound card access
+/// of the native library.
+///
+/// Native calls the device from its worker thread, the device is expected
+/// to record and play on its own threads through the transport.
+#[allow(unused)]
+pub trait AudioDeviceModule: Send + Sync {
+    /// Called once with the transport the device talks to.
+    fn init(&self, transport: AudioTransport) {}
+    fn start_playout(&self) {}
+    fn stop_playout(&self) {}
+    fn start_recording(&self) {}
+    fn stop_recording(&self) {}
+    /// The device is no longer used, stop any threads.
+    fn terminate(&self) {}
+}
+
+#[repr(C)]
+#[rustfmt::skip]
+pub(crate) struct RawAudioDeviceModule {
+    ctx: *const AudioDeviceModuleRef,
+    register_transport: extern "C" fn(&AudioDeviceModuleRef, *const RawAudioTransport),
+    start_playout: extern "C" fn(&AudioDeviceModuleRef),
+    stop_playout: extern "C" fn(&AudioDeviceModuleRef),
+    start_recording: extern "C" fn(&AudioDeviceModuleRef),
+    stop_recording: extern "C" fn(&AudioDeviceModuleRef),
+}
+
+/// Owns a rust audio device and its vtable.
+pub(crate) struct AudioDeviceModuleRef {
+    raw: RawAudioDeviceModule,
+    device: Box<dyn AudioDeviceModule>,
+    transport: Arc<RwLock<Option<usize>>>,
+    initialized: AtomicBool,
+}
+
+impl AudioDeviceModuleRef {
+    pub(crate) fn new(device: Box<dyn AudioDeviceModule>) -> Box<Self> {
+        let mut this = Box::new(Self {
+            raw: RawAudioDeviceModule {
+                ctx: std::ptr::null(),
+                register_transport: register_audio_transport,
+                start_playout: start_audio_playout,
+                stop_playout: stop_audio_playout,
+                start_recording: start_audio_recording,
+                stop_recording: stop_audio_recording,
+            },
+            transport: Arc::new(RwLock::new(None)),
+            initialized: AtomicBool::new(false),
+            device,
+        });
+
+        this.raw.ctx = &*this;
+        this
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawAudioDeviceModule {
+        &self.raw
+    }
+}
+
+impl Drop for AudioDeviceModuleRef {
+    fn drop(&mut self) {
+        let _ = self.transport.write().unwrap().take();
+        self.device.terminate();
+    }
+}
+
+/// A null transport unregisters the current one.
+#[no_mangle]
+extern "C" fn register_audio_transport(
+    ctx: &AudioDeviceModuleRef,
+    transport: *const RawAudioTransport,
+) {
+    *ctx.transport.write().unwrap() = (!transport.is_null()).then_some(transport as usize);
+    if !transport.is_null() && !ctx.initialized.swap(true, Ordering::Relaxed) {
+        ctx.device.init(AudioTransport {
+            raw: ctx.transport.clone(),
+        });
+    }
+}
+
+#[no_mangle]
+extern "C" fn start_audio_playout(ctx: &AudioDeviceModuleRef) {
+    ctx.device.start_playout()
+}
+
+#[no_mangle]
+extern "C" fn stop_audio_playout(ctx: &AudioDeviceModuleRef) {
+    ctx.device.stop_playout()
+}
+
+#[no_mangle]
+extern "C" fn start_audio_recording(ctx: &AudioDeviceModuleRef) {
+    ctx.device.start_recording()
+}
+
+#[no_mangle]
+extern "C" fn stop_audio_recording(ctx: &AudioDeviceModuleRef) {
+    ctx.device.stop_recording()
+}
+
+struct VirtualAudioDeviceInner {
+    sample_rate: usize,
+    channels: u8,
+    playing: AtomicBool,
+    recording: AtomicBool,
+    closed: AtomicBool,
+    source: Mutex<Option<Box<dyn AudioSource>>>,
+    sinks: SinkRegistry<Arc<AudioFrame>>,
+    thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+// The playout sinks are only called from the device thread.
+unsafe impl Send for VirtualAudioDeviceInner {}
+unsafe impl Sync for VirtualAudioDeviceInner {}
+
+impl VirtualAudioDeviceInner {
+    /// Read 10ms of recording from the source, silence if there is no
+    /// source or it has ended.
+    fn read_recording(&self, converter: &mut FormatConverter, timestamp: usize) -> AudioFrame {
+        let silence = || {
+            let size = self.sample_rate / 100 * self.channels as usize;
+            AudioFrame::from_pcm(self.sample_rate, self.channels, timestamp, vec![0; size])
+        };
+
+        let mut source = self.source.lock().unwrap();
+        let Some(input) = source.as_mut() else {
+            return silence();
+        };
+
+        let (rate, channels) = (input.sample_rate(), input.channels());
+        let mut buf = vec![0i16; rate / 100 * channels as usize];
+        let size = input.read(&mut buf);
+        if size == 0 {
+            let _ = source.take();
+            return silence();
+        }
+
+        // A short read is padded with silence, the clock keeps running.
+        let frame = AudioFrame::from_pcm(rate, channels, timestamp, buf);
+        match converter.convert(&frame) {
+            Some(pcm) => AudioFrame::from_pcm(self.sample_rate, self.channels, timestamp, pcm),
+            None => silence(),
+        }
+    }
+
+    fn run(&self, transport: AudioTransport) {
+        let mut converter = FormatConverter::new(self.sample_rate, self.channels);
+        let mut pacer = Pacer::new(Duration::from_millis(10));
+        let mut timestamp = 0;
+
+        while !self.closed.load(Ordering::Relaxed) {
+            pacer.wait();
+            if self.recording.load(Ordering::Relaxed) {
+                transport.record(&self.read_recording(&mut converter, timestamp));
+            }
+
+            if self.playing.load(Ordering::Relaxed) {
+                if let Some(frame) = transport.playout(self.sample_rate, self.channels, timestamp) {
+                    self.sinks.on_data(Arc::new(frame));
+                }
+            }
+
+            timestamp += 10;
+        }
+    }
+}
+
+/// An audio device for machines without a sound card.
+///
+/// A virtual clock records and plays 10ms of audio at a time. Recording
+/// is silence unless a source is set, the mixed playout audio, what a
+/// speaker would have played, is handed to the playout sinks.
+///
+/// ```no_run
+/// # use futures::StreamExt;
+/// # use librtc::*;
+/// # async fn run() {
+/// let device = VirtualAudioDevice::new(48000, 2);
+/// let mut playout = device.playout(100, FramePolicy::DropOldest);
+/// let engine = MediaEngine::default().with_audio_device_module(device.clone());
+///
+/// while let Some(frame) = playout.next().await {
+///     // mixed audio of all remote tracks
+/// }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct VirtualAudioDevice {
+    inner: Arc<VirtualAudioDeviceInner>,
+}
+
+impl VirtualAudioDevice {
+    /// Create a device with the given playout and recording format.
+    pub fn new(sample_rate: usize, channels: u8) -> Self {
+        assert!(channels > 0);
+        assert_eq!(sample_rate % 100, 0);
+
+        Self {
+            inner: Arc::new(VirtualAudioDeviceInner {
+                playing: AtomicBool::new(false),
+                recording: AtomicBool::new(false),
+                closed: AtomicBool::new(false),
+                source: Mutex::new(None),
+                sinks: SinkRegistry::new(rtc_span!(DEBUG, "sinks", kind = "audio_device")),
+                thread: Mutex::new(None),
+                sample_rate,
+                channels,
+            }),
+        }
+    }
+
+    /// Record from the source instead of silence, the audio is converted
+    /// to the device format.
+    pub fn set_recording_source<S: AudioSource + 'static>(&self, source: S) {
+        let _ = self.inner.source.lock().unwrap().insert(Box::new(source));
+    }
+
+    /// Receive the mixed playout audio.
+    pub fn register_playout_sink(&self, sink: Sinker<Arc<AudioFrame>>) -> SinkHandle {
+        let key = self.inner.sinks.next_key();
+        self.inner.sinks.insert(key, sink, || ());
+
+        let inner = Arc::downgrade(&self.inner);
+        SinkHandle::new(move || {
+            if let Some(inner) = Weak::upgrade(&inner) {
+                inner.sinks.remove(key, || ());
+            }
+        })
+    }
+
+    /// The mixed playout audio as a stream of frames.
+    pub fn playout(&self, capacity: usize, policy: FramePolicy) -> FrameStream<Arc<AudioFrame>> {
+        let (queue, mut stream) = FrameStream::channel(capacity, policy);
+        stream.attach(self.register_playout_sink(Sinker::new(queue)));
+        stream
+    }
+}
+
+impl AudioDeviceModule for VirtualAudioDevice {
+    fn init(&self, transport: AudioTransport) {
+        let inner = self.inner.clone();
+        let handle = thread::spawn(move || inner.run(transport));
+        let _ = self.inner.thread.lock().unwrap().insert(handle);
+    }
+
+    fn start_playout(&self) {
+        self.inner.playing.store(true, Ordering::Relaxed);
+    }
+
+    fn stop_playout(&self) {
+        self.inner.playing.store(false, Ordering::Relaxed);
+    }
+
+    fn start_recording(&self) {
+        self.inner.recording.store(true, Ordering::Relaxed);
+    }
+
+    fn stop_recording(&self) {
+        self.inner.recording.store(false, Ordering::Relaxed);
+    }
+
+    fn terminate(&self) {
+        self.inner.closed.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.inner.thread.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+}
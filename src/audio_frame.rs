@@ -22,6 +22,10 @@ pub struct RawAudioFrame {
 #[derive(Debug)]
 pub struct AudioFrame {
     raw: *const RawAudioFrame,
+    // Frames created from an owned pcm buffer keep it here, the raw frame
+    // only borrows the pointer.
+    #[allow(dead_code)]
+    buf: Option<Vec<i16>>,
 }
 
 unsafe impl Send for AudioFrame {}
@@ -31,7 +35,7 @@ impl AudioFrame {
     /// crate AudiFrame from raw type.
     pub(crate) fn from_raw(raw: *const RawAudioFrame) -> Arc<Self> {
         assert!(!raw.is_null());
-        Arc::new(Self { raw })
+        Arc::new(Self { raw, buf: None })
     }
 
     pub(crate) fn get_raw(&self) -> *const RawAudioFrame {
@@ -56,8 +60,50 @@ impl AudioFrame {
                 remote: false,
                 frames,
             })),
+            buf: None,
         }
     }
+
+    /// Create a pcm frame that owns its sample buffer.
+    ///
+    /// The samples are interleaved, and the number of frames is derived from
+    /// the buffer length and the channel count. The timestamp is in
+    /// milliseconds.
+    pub fn from_pcm(sample_rate: usize, channels: u8, timestamp: usize, buf: Vec<i16>) -> Self {
+        assert!(channels > 0);
+        Self {
+            raw: Box::into_raw(Box::new(RawAudioFrame {
+                frames: buf.len() / channels as usize,
+                sample_rate: sample_rate as c_int,
+                channels: channels as usize,
+                timestamp: timestamp as i64,
+                buf: buf.as_ptr(),
+                size: buf.len(),
+                remote: false,
+            })),
+            buf: Some(buf),
+        }
+    }
+
+    /// get audio frame sample rate
+    pub fn sample_rate(&self) -> usize {
+        unsafe { &*self.raw }.sample_rate as usize
+    }
+
+    /// get audio frame channel count
+    pub fn channels(&self) -> u8 {
+        unsafe { &*self.raw }.channels as u8
+    }
+
+    /// get the number of samples per channel
+    pub fn frames(&self) -> usize {
+        unsafe { &*self.raw }.frames
+    }
+
+    /// get audio frame timestamp
+    pub fn timestamp(&self) -> i64 {
+        unsafe { &*self.raw }.timestamp
+    }
 }
 
 impl AsRef<[i16]> for AudioFrame {
@@ -0,0 +1,674 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+};
+
+use crate::{
+    audio_chunker::FRAME_DURATION, audio_resampler::FormatConverter, pacer::Pacer, AudioFrame,
+    AudioTrack, SinkExt, Sinker,
+};
+
+/// The limiter keeps the mix below -1dBFS.
+const LIMITER_THRESHOLD: f32 = 0.89 * i16::MAX as f32;
+
+/// Fraction of the distance back to unity gain recovered per frame.
+const LIMITER_RELEASE: f32 = 0.05;
+
+/// Audio mixer configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioMixerOptions {
+    /// The sample rate of the mixed output.
+    pub sample_rate: usize,
+    /// The channel count of the mixed output.
+    pub channels: u8,
+    /// Number of 10ms frames an input buffers before it joins the mix, and
+    /// again after this many frames in a row arrived too late.
+    pub jitter_frames: usize,
+    /// Upper bound of buffered frames per input, the oldest frames are
+    /// dropped when an input runs ahead of the mixer clock.
+    pub max_buffered_frames: usize,
+}
+
+impl Default for AudioMixerOptions {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48000,
+            channels: 1,
+            jitter_frames: 4,
+            max_buffered_frames: 20,
+        }
+    }
+}
+
+struct InputState {
+    gain: f32,
+    muted: bool,
+    converter: FormatConverter,
+    // Jitter buffer, ordered by timestamp and then by arrival.
+    pending: BTreeMap<(i64, u64), Vec<i16>>,
+    sequence: u64,
+    // Added to the timestamps of the input to place them on the mixer
+    // timeline, `None` until the input has buffered enough to join.
+    offset: Option<i64>,
+    // Converted samples, starting at `fifo_start` on the mixer timeline.
+    fifo: VecDeque<i16>,
+    fifo_start: i64,
+    // The end of the last window taken out of the input.
+    played: i64,
+    // Frames in a row that arrived after their time had been played.
+    late: usize,
+}
+
+impl InputState {
+    /// The position of a timestamp in milliseconds on the mixer timeline,
+    /// in samples per channel.
+    fn position(&self, timestamp: i64, options: &AudioMixerOptions) -> i64 {
+        timestamp * options.sample_rate as i64 / 1000 + self.offset.unwrap_or(0)
+    }
+
+    fn fifo_end(&self, channels: usize) -> i64 {
+        self.fifo_start + (self.fifo.len() / channels) as i64
+    }
+
+    /// The timeline position of the first buffered frame, if the input has
+    /// buffered enough to join the mix.
+    fn ready(&self, options: &AudioMixerOptions) -> Option<i64> {
+        if self.offset.is_some() || self.pending.len() < options.jitter_frames {
+            return None;
+        }
+
+        let ((timestamp, _), _) = self.pending.first_key_value()?;
+        Some(self.position(*timestamp, options))
+    }
+
+    /// Take the samples of the window of the mixer timeline starting at
+    /// `start`, returns `None` while the input is buffering.
+    fn take(&mut self, start: i64, options: &AudioMixerOptions) -> Option<Vec<i16>> {
+        let channels = options.channels as usize;
+        let frame_len = (options.sample_rate / 100) as i64;
+        let end = start + frame_len;
+
+        if self.offset.is_none() {
+            let first = self.ready(options)?;
+
+            // An input on the clock of the mix keeps its timestamps, an
+            // input with an unrelated clock starts playing now.
+            let tolerance = options.max_buffered_frames as i64 * frame_len;
+            let _ = self.offset.insert(if (first - start).abs() > tolerance {
+                start - first
+            } else {
+                0
+            });
+
+            self.fifo.clear();
+            self.fifo_start = start;
+        }
+
+        // Fill the fifo to the end of the window. Gaps in the timestamps
+        // become silence, jitter below half a frame is ignored so that the
+        // resampler does not cause clicks.
+        while self.fifo_end(channels) < end {
+            let Some(((timestamp, _), samples)) = self.pending.pop_first() else {
+                break;
+            };
+
+            let position = self.position(timestamp, options);
+            let fifo_end = self.fifo_end(channels);
+            if self.fifo.is_empty() && position > fifo_end {
+                self.fifo_start = position;
+            } else if position - fifo_end > frame_len / 2 {
+                let gap = (position - fifo_end) as usize * channels;
+                self.fifo.extend(std::iter::repeat_n(0, gap));
+            }
+
+            let overlap = fifo_end - position;
+            let skip = if overlap > frame_len / 2 {
+                (overlap as usize * channels).min(samples.len())
+            } else {
+                0
+            };
+
+            self.fifo.extend(&samples[skip..]);
+        }
+
+        // Samples before the window are too late.
+        let fifo_len = (self.fifo.len() / channels) as i64;
+        let late = (start - self.fifo_start).clamp(0, fifo_len);
+        self.fifo.drain(..late as usize * channels);
+        self.fifo_start += late;
+
+        // Silence until the first sample of the input, and after the last.
+        let lead = (self.fifo_start - start).clamp(0, frame_len) as usize * channels;
+        let mut samples = vec![0; lead];
+        let count = (frame_len as usize * channels - lead).min(self.fifo.len());
+        samples.extend(self.fifo.drain(..count));
+        samples.resize(frame_len as usize * channels, 0);
+        self.fifo_start += (count / channels) as i64;
+
+        self.played = end;
+        Some(samples)
+    }
+}
+
+struct MixerInputShared {
+    id: u32,
+    options: AudioMixerOptions,
+    state: Mutex<InputState>,
+}
+
+/// One input of the audio mixer.
+///
+/// The input is an audio track sink, register it to the track whose audio
+/// should be mixed. Frames of any sample rate and channel layout are
+/// accepted and converted to the mixer format.
+#[derive(Clone)]
+pub struct AudioMixerInput {
+    shared: Arc<MixerInputShared>,
+}
+
+impl AudioMixerInput {
+    /// The id of the input, used to exclude it from an output.
+    pub fn id(&self) -> u32 {
+        self.shared.id
+    }
+
+    /// Set the linear gain of the input, 1.0 keeps the level unchanged.
+    pub fn set_gain(&self, gain: f32) {
+        self.shared.state.lock().unwrap().gain = gain.max(0.0);
+    }
+
+    /// Muted inputs keep buffering but are left out of the mix.
+    pub fn set_muted(&self, muted: bool) {
+        self.shared.state.lock().unwrap().muted = muted;
+    }
+}
+
+impl SinkExt for AudioMixerInput {
+    type Item = Arc<AudioFrame>;
+
+    fn on_data(&self, frame: Arc<AudioFrame>) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        // Frames older than what has already been played are too late.
+        let timestamp = frame.timestamp();
+        let options = &self.shared.options;
+        if state.offset.is_some() {
+            let frame_len = (options.sample_rate / 100) as i64;
+            if state.position(timestamp, options) + frame_len <= state.played {
+                // The clock of the input jumped or drifted away, join the
+                // mix again as a new input.
+                state.late += 1;
+                if state.late >= options.jitter_frames.max(1) {
+                    state.offset = None;
+                    state.pending.clear();
+                    state.fifo.clear();
+                    state.late = 0;
+                }
+
+                return;
+            }
+
+            state.late = 0;
+        }
+
+        if let Some(samples) = state.converter.convert(&frame) {
+            let sequence = state.sequence;
+            state.sequence += 1;
+            state.pending.insert((timestamp, sequence), samples);
+
+            while state.pending.len() > options.max_buffered_frames {
+                state.pending.pop_first();
+            }
+        }
+    }
+}
+
+/// Soft limiter, reduces the gain when the mix would clip and recovers
+/// slowly afterwards.
+struct Limiter {
+    gain: f32,
+}
+
+impl Limiter {
+    fn process(&mut self, buf: &[f32]) -> Vec<i16> {
+        let peak = buf.iter().fold(0f32, |max, sample| max.max(sample.abs()));
+        let target = if peak > LIMITER_THRESHOLD {
+            LIMITER_THRESHOLD / peak
+        } else {
+            1.0
+        };
+
+        // Attack immediately, release with a ramp over the frame.
+        let (from, to) = if target < self.gain {
+            (target, target)
+        } else {
+            (
+                self.gain,
+                self.gain + (target - self.gain) * LIMITER_RELEASE,
+            )
+        };
+
+        self.gain = to;
+        let step = (to - from) / buf.len().max(1) as f32;
+        buf.iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                let gain = from + step * i as f32;
+                (sample * gain)
+                    .round()
+                    .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+            })
+            .collect()
+    }
+}
+
+/// Where an output delivers the mix.
+enum MixerTarget {
+    Track(Arc<AudioTrack>),
+    Sink(Sinker<Arc<AudioFrame>>),
+}
+
+struct MixerOutput {
+    id: u32,
+    target: MixerTarget,
+    exclude: Option<u32>,
+    limiter: Limiter,
+}
+
+struct AudioMixerInner {
+    options: AudioMixerOptions,
+    inputs: RwLock<Vec<Arc<MixerInputShared>>>,
+    outputs: Mutex<Vec<MixerOutput>>,
+    ids: AtomicU32,
+    timestamp: Mutex<usize>,
+    // Start of the next window on the mixer timeline, in samples per
+    // channel, `None` until the first input joins.
+    playout: Mutex<Option<i64>>,
+}
+
+impl AudioMixerInner {
+    fn new(options: AudioMixerOptions) -> Self {
+        assert!(options.channels > 0);
+        assert_eq!(options.sample_rate % 100, 0);
+
+        Self {
+            inputs: RwLock::new(Vec::new()),
+            outputs: Mutex::new(Vec::new()),
+            playout: Mutex::new(None),
+            timestamp: Mutex::new(0),
+            ids: AtomicU32::new(0),
+            options,
+        }
+    }
+
+    fn add_input(&self) -> AudioMixerInput {
+        let options = self.options;
+        let shared = Arc::new(MixerInputShared {
+            id: self.ids.fetch_add(1, Ordering::Relaxed),
+            state: Mutex::new(InputState {
+                pending: BTreeMap::new(),
+                fifo: VecDeque::new(),
+                converter: FormatConverter::new(options.sample_rate, options.channels),
+                offset: None,
+                fifo_start: 0,
+                muted: false,
+                sequence: 0,
+                played: 0,
+                late: 0,
+                gain: 1.0,
+            }),
+            options,
+        });
+
+        self.inputs.write().unwrap().push(shared.clone());
+        AudioMixerInput { shared }
+    }
+
+    fn push_output(&self, target: MixerTarget, exclude: Option<u32>) -> u32 {
+        let id = self.ids.fetch_add(1, Ordering::Relaxed);
+        self.outputs.lock().unwrap().push(MixerOutput {
+            limiter: Limiter { gain: 1.0 },
+            exclude,
+            target,
+            id,
+        });
+
+        id
+    }
+
+    /// Mix one 10ms frame and push it to all outputs.
+    fn tick(&self) {
+        let channels = self.options.channels;
+        let len = self.options.sample_rate / 100 * channels as usize;
+        let inputs = self.inputs.read().unwrap();
+
+        // The mix starts with the earliest frame of the first inputs that
+        // are ready, all inputs take the same window of the timeline.
+        let start = {
+            let mut playout = self.playout.lock().unwrap();
+            if playout.is_none() {
+                *playout = inputs
+                    .iter()
+                    .filter_map(|input| input.state.lock().unwrap().ready(&self.options))
+                    .min();
+            }
+
+            let start = *playout;
+            if let Some(playout) = playout.as_mut() {
+                *playout += (self.options.sample_rate / 100) as i64;
+            }
+
+            start
+        };
+
+        let mut total = vec![0f32; len];
+        let mut contributions = Vec::new();
+        for input in inputs.iter() {
+            let mut state = input.state.lock().unwrap();
+            let samples = match start.and_then(|start| state.take(start, &self.options)) {
+                Some(samples) => samples,
+                None => continue,
+            };
+
+            if state.muted {
+                continue;
+            }
+
+            let contribution = samples
+                .iter()
+                .map(|sample| *sample as f32 * state.gain)
+                .collect::<Vec<f32>>();
+
+            for (sum, sample) in total.iter_mut().zip(&contribution) {
+                *sum += sample;
+            }
+
+            contributions.push((input.id, contribution));
+        }
+
+        let timestamp = {
+            let mut timestamp = self.timestamp.lock().unwrap();
+            let current = *timestamp;
+            *timestamp += FRAME_DURATION.as_millis() as usize;
+            current
+        };
+
+        for output in self.outputs.lock().unwrap().iter_mut() {
+            let excluded = output
+                .exclude
+                .and_then(|id| contributions.iter().find(|(input, _)| *input == id));
+
+            // Mix minus, the excluded participant does not hear itself.
+            let samples = match excluded {
+                Some((_, contribution)) => {
+                    let mix = total
+                        .iter()
+                        .zip(contribution)
+                        .map(|(sum, sample)| sum - sample)
+                        .collect::<Vec<f32>>();
+                    output.limiter.process(&mix)
+                }
+                None => output.limiter.process(&total),
+            };
+
+            let frame =
+                AudioFrame::from_pcm(self.options.sample_rate, channels, timestamp, samples);
+            match &output.target {
+                MixerTarget::Track(track) => track.add_frame(&frame),
+                MixerTarget::Sink(sinker) => sinker.sink.on_data(Arc::new(frame)),
+            }
+        }
+    }
+}
+
+/// Mixes many audio tracks into one.
+///
+/// Every input has its own jitter buffer that orders the frames by
+/// timestamp, the mixer runs on its own clock and pushes one 10ms frame per
+/// tick into each output track. Inputs are aligned by their timestamps
+/// before summing, missing frames are mixed as silence. An output can
+/// exclude one input to produce a mix minus, so that a participant does not
+/// receive its own audio back.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let alice_track = AudioTrack::new("alice")?;
+/// # let bob_track = AudioTrack::new("bob")?;
+/// # let bob_output_track = AudioTrack::new("bob-output")?;
+/// let mixer = AudioMixer::new(AudioMixerOptions::default());
+///
+/// let alice = mixer.add_input();
+/// let _alice_sink = alice_track.add_sink(Sinker::new(alice.clone()));
+///
+/// let bob = mixer.add_input();
+/// let _bob_sink = bob_track.add_sink(Sinker::new(bob.clone()));
+///
+/// // Bob receives everyone but himself.
+/// mixer.add_output(bob_output_track, Some(bob.id()));
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioMixer {
+    inner: Arc<AudioMixerInner>,
+    closed: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl AudioMixer {
+    /// Create an audio mixer, the mixer clock starts immediately.
+    pub fn new(options: AudioMixerOptions) -> Self {
+        let inner = Arc::new(AudioMixerInner::new(options));
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let inner = inner.clone();
+            let closed = closed.clone();
+            thread::spawn(move || {
+                let mut pacer = Pacer::new(FRAME_DURATION);
+                while !closed.load(Ordering::Relaxed) {
+                    pacer.wait();
+                    inner.tick();
+                }
+            })
+        };
+
+        Self {
+            handle: Some(handle),
+            closed,
+            inner,
+        }
+    }
+
+    /// Add a new input to the mix.
+    pub fn add_input(&self) -> AudioMixerInput {
+        self.inner.add_input()
+    }
+
+    /// Remove an input from the mix, frames that are still pushed to it are
+    /// ignored.
+    pub fn remove_input(&self, id: u32) {
+        self.inner
+            .inputs
+            .write()
+            .unwrap()
+            .retain(|input| input.id != id);
+    }
+
+    /// Push the mix into a local audio track, optionally leaving one input
+    /// out. Returns the id of the output.
+    pub fn add_output(&self, track: Arc<AudioTrack>, exclude: Option<u32>) -> u32 {
+        self.inner.push_output(MixerTarget::Track(track), exclude)
+    }
+
+    /// Deliver the mix to a sink instead of a track, such as a recorder,
+    /// optionally leaving one input out. Returns the id of the output.
+    pub fn add_sink_output(&self, sink: Sinker<Arc<AudioFrame>>, exclude: Option<u32>) -> u32 {
+        self.inner.push_output(MixerTarget::Sink(sink), exclude)
+    }
+
+    /// Stop pushing the mix into an output.
+    pub fn remove_output(&self, id: u32) {
+        self.inner
+            .outputs
+            .lock()
+            .unwrap()
+            .retain(|output| output.id != id);
+    }
+}
+
+impl Drop for AudioMixer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: AudioMixerOptions = AudioMixerOptions {
+        sample_rate: 8000,
+        channels: 1,
+        jitter_frames: 2,
+        max_buffered_frames: 20,
+    };
+
+    fn push(input: &AudioMixerInput, timestamp: usize, value: i16) {
+        let frame = AudioFrame::from_pcm(8000, 1, timestamp, vec![value; 80]);
+        input.on_data(Arc::new(frame));
+    }
+
+    fn output(mixer: &AudioMixerInner, exclude: Option<u32>) -> Arc<Mutex<Vec<Vec<i16>>>> {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let frames = frames.clone();
+            Sinker::from_fn(move |frame: Arc<AudioFrame>| {
+                frames.lock().unwrap().push((*frame).as_ref().to_vec())
+            })
+        };
+
+        mixer.push_output(MixerTarget::Sink(sink), exclude);
+        frames
+    }
+
+    /// The first sample of every mixed frame, the test frames are constant.
+    fn levels(frames: &Mutex<Vec<Vec<i16>>>) -> Vec<i16> {
+        let frames = frames.lock().unwrap();
+        for frame in frames.iter() {
+            assert_eq!(frame.len(), 80);
+            assert!(frame.iter().all(|sample| *sample == frame[0]));
+        }
+
+        frames.iter().map(|frame| frame[0]).collect()
+    }
+
+    #[test]
+    fn align() {
+        let mixer = AudioMixerInner::new(OPTIONS);
+        let frames = output(&mixer, None);
+
+        // Bob starts talking 20ms after Alice, and his frames arrive first.
+        let alice = mixer.add_input();
+        let bob = mixer.add_input();
+        for i in 2..6 {
+            push(&bob, i * 10, 1000 * i as i16);
+        }
+
+        for i in 0..6 {
+            push(&alice, i * 10, 10 * i as i16);
+        }
+
+        for _ in 0..6 {
+            mixer.tick();
+        }
+
+        assert_eq!(levels(&frames), [0, 10, 2020, 3030, 4040, 5050]);
+    }
+
+    #[test]
+    fn gap() {
+        let mixer = AudioMixerInner::new(OPTIONS);
+        let frames = output(&mixer, None);
+
+        let input = mixer.add_input();
+        for i in [0, 1, 3, 5, 4] {
+            push(&input, i * 10, 1 + i as i16);
+        }
+
+        for _ in 0..7 {
+            mixer.tick();
+        }
+
+        assert_eq!(levels(&frames), [1, 2, 0, 4, 5, 6, 0]);
+    }
+
+    #[test]
+    fn late_frames() {
+        let mixer = AudioMixerInner::new(OPTIONS);
+        let frames = output(&mixer, None);
+
+        let input = mixer.add_input();
+        push(&input, 0, 1);
+        push(&input, 10, 2);
+        mixer.tick();
+        mixer.tick();
+
+        // Already played, dropped.
+        push(&input, 0, 100);
+        push(&input, 20, 3);
+        mixer.tick();
+
+        assert_eq!(levels(&frames), [1, 2, 3]);
+    }
+
+    #[test]
+    fn unrelated_clocks() {
+        let mixer = AudioMixerInner::new(OPTIONS);
+        let frames = output(&mixer, None);
+
+        let alice = mixer.add_input();
+        let bob = mixer.add_input();
+        for i in 0..3 {
+            push(&alice, i * 10, 1);
+            push(&bob, 60_000 + i * 10, 10);
+        }
+
+        for _ in 0..3 {
+            mixer.tick();
+        }
+
+        assert_eq!(levels(&frames), [11, 11, 11]);
+    }
+
+    #[test]
+    fn mix_minus() {
+        let mixer = AudioMixerInner::new(OPTIONS);
+        let alice = mixer.add_input();
+        let bob = mixer.add_input();
+
+        let all = output(&mixer, None);
+        let to_alice = output(&mixer, Some(alice.id()));
+        let to_bob = output(&mixer, Some(bob.id()));
+
+        for i in 0..2 {
+            push(&alice, i * 10, 1);
+            push(&bob, i * 10, 10);
+        }
+
+        bob.set_gain(2.0);
+        mixer.tick();
+        mixer.tick();
+
+        assert_eq!(levels(&all), [21, 21]);
+        assert_eq!(levels(&to_alice), [20, 20]);
+        assert_eq!(levels(&to_bob), [1, 1]);
+    }
+}
@@ -0,0 +1,308 @@
+use std::f64::consts::PI;
+
+use crate::{AudioFrame, ChannelLayout, ChannelMixer};
+
+/// Number of filter taps per polyphase branch when the sample rate is not
+/// reduced, downsampling widens the filter by the decimation ratio.
+const TAPS_PER_PHASE: usize = 32;
+
+/// Kaiser window shape, gives roughly 85dB of stopband attenuation.
+const KAISER_BETA: f64 = 8.6;
+
+/// Keep the passband edge slightly below nyquist so that the transition band
+/// ends before aliasing starts.
+const CUTOFF_RATIO: f64 = 0.95;
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Zeroth order modified bessel function of the first kind.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..64 {
+        term *= half / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-12 {
+            break;
+        }
+    }
+
+    sum
+}
+
+/// Polyphase windowed sinc sample rate converter for interleaved pcm.
+///
+/// The conversion ratio is reduced to `up / down`, the input is virtually
+/// upsampled by `up`, low-pass filtered and decimated by `down`, with only
+/// the filter branch that lands on an output sample being evaluated. The
+/// filter history is kept between calls, so feeding a stream in 10ms frames
+/// gives the same result as converting it in one piece.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # let frame = AudioFrame::from_pcm(48000, 2, 0, vec![0; 960]);
+/// // Remote audio is 48khz stereo, the speech pipeline wants 16khz mono.
+/// let mut resampler = Resampler::new(48000, 16000, 1);
+/// let mixer = ChannelMixer::new(ChannelLayout::Stereo, ChannelLayout::Mono);
+///
+/// let mono = mixer.process_frame(&frame);
+/// let output = resampler.process_frame(&mono);
+/// ```
+pub struct Resampler {
+    input_rate: usize,
+    output_rate: usize,
+    channels: usize,
+    up: usize,
+    down: usize,
+    taps: usize,
+    // Polyphase filter bank, `up` branches of `taps` coefficients each.
+    filters: Vec<f32>,
+    // Interleaved input, the first `taps - 1` frames are history from the
+    // previous call.
+    pending: Vec<f32>,
+    // Position of the next output sample on the upsampled time axis,
+    // relative to the first frame of `pending`.
+    position: usize,
+}
+
+impl Resampler {
+    /// Create a resampler for interleaved pcm with the given channel count.
+    pub fn new(input_rate: usize, output_rate: usize, channels: u8) -> Self {
+        assert!(input_rate > 0 && output_rate > 0 && channels > 0);
+
+        let divisor = gcd(input_rate, output_rate);
+        let up = output_rate / divisor;
+        let down = input_rate / divisor;
+
+        // When reducing the sample rate, the cutoff has to move down to the
+        // output nyquist, and the filter gets longer to keep the same
+        // transition width.
+        let ratio = (output_rate as f64 / input_rate as f64).min(1.0);
+        let taps = ((TAPS_PER_PHASE as f64 / ratio).ceil() as usize).max(TAPS_PER_PHASE);
+        let cutoff = 0.5 * ratio * CUTOFF_RATIO / up as f64;
+
+        let len = taps * up;
+        let center = (len - 1) as f64 / 2.0;
+        let norm = bessel_i0(KAISER_BETA);
+        let prototype = (0..len)
+            .map(|i| {
+                let t = i as f64 - center;
+                let sinc = if t == 0.0 {
+                    2.0 * cutoff
+                } else {
+                    (2.0 * PI * cutoff * t).sin() / (PI * t)
+                };
+
+                let x = 2.0 * t / (len - 1).max(1) as f64;
+                let window = bessel_i0(KAISER_BETA * (1.0 - x * x).max(0.0).sqrt()) / norm;
+                sinc * window * up as f64
+            })
+            .collect::<Vec<f64>>();
+
+        // Split the prototype into branches, branch `p` holds the
+        // coefficients `p, p + up, p + 2 * up, ...`.
+        let mut filters = vec![0.0; len];
+        for phase in 0..up {
+            for tap in 0..taps {
+                filters[phase * taps + tap] = prototype[phase + tap * up] as f32;
+            }
+        }
+
+        let channels = channels as usize;
+        Self {
+            pending: vec![0.0; (taps - 1) * channels],
+            position: (taps - 1) * up,
+            output_rate,
+            input_rate,
+            channels,
+            filters,
+            taps,
+            down,
+            up,
+        }
+    }
+
+    /// The input sample rate.
+    pub fn input_rate(&self) -> usize {
+        self.input_rate
+    }
+
+    /// The output sample rate.
+    pub fn output_rate(&self) -> usize {
+        self.output_rate
+    }
+
+    /// The delay introduced by the filter, in output samples per channel.
+    pub fn latency(&self) -> usize {
+        if self.up == self.down {
+            return 0;
+        }
+
+        self.taps * self.up / 2 / self.down
+    }
+
+    /// Convert interleaved pcm and append the result to `output`.
+    ///
+    /// The input length must be a multiple of the channel count.
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        debug_assert_eq!(input.len() % self.channels, 0);
+
+        let channels = self.channels;
+        if self.up == self.down {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        self.pending.extend(input.iter().map(|s| *s as f32));
+        let frames = self.pending.len() / channels;
+
+        while self.position / self.up < frames {
+            let base = self.position / self.up;
+            let phase = self.position % self.up;
+            let filter = &self.filters[phase * self.taps..(phase + 1) * self.taps];
+            for channel in 0..channels {
+                let mut sum = 0.0;
+                for (tap, coeff) in filter.iter().enumerate() {
+                    sum += coeff * self.pending[(base - tap) * channels + channel];
+                }
+
+                output.push(sum.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            }
+
+            self.position += self.down;
+        }
+
+        // Keep the last `taps - 1` frames as history for the next call.
+        let consumed = frames - (self.taps - 1);
+        self.pending.drain(..consumed * channels);
+        self.position -= consumed * self.up;
+    }
+
+    /// Convert an audio frame, the timestamp of the input is kept.
+    pub fn process_frame(&mut self, frame: &AudioFrame) -> AudioFrame {
+        assert_eq!(frame.sample_rate(), self.input_rate);
+        assert_eq!(frame.channels() as usize, self.channels);
+
+        let mut output = Vec::with_capacity(
+            frame.as_ref().len() * self.output_rate / self.input_rate + self.channels,
+        );
+
+        self.process(frame.as_ref(), &mut output);
+        AudioFrame::from_pcm(
+            self.output_rate,
+            self.channels as u8,
+            frame.timestamp() as usize,
+            output,
+        )
+    }
+
+    /// Drop the filter history, as if the resampler was just created.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.pending.resize((self.taps - 1) * self.channels, 0.0);
+        self.position = (self.taps - 1) * self.up;
+    }
+}
+
+/// Converts frames of any sample rate and channel layout to one format,
+/// the converter is rebuilt when the input format changes.
+pub(crate) struct FormatConverter {
+    sample_rate: usize,
+    channels: u8,
+    input: Option<(usize, u8, ChannelMixer, Resampler)>,
+}
+
+impl FormatConverter {
+    pub(crate) fn new(sample_rate: usize, channels: u8) -> Self {
+        Self {
+            input: None,
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Convert a frame to interleaved pcm in the output format, returns
+    /// `None` for channel counts without a known layout.
+    pub(crate) fn convert(&mut self, frame: &AudioFrame) -> Option<Vec<i16>> {
+        let input = ChannelLayout::from_channels(frame.channels())?;
+        let output = ChannelLayout::from_channels(self.channels)?;
+
+        if self
+            .input
+            .as_ref()
+            .map(|(rate, channels, _, _)| {
+                *rate != frame.sample_rate() || *channels != frame.channels()
+            })
+            .unwrap_or(true)
+        {
+            self.input = Some((
+                frame.sample_rate(),
+                frame.channels(),
+                ChannelMixer::new(input, output),
+                Resampler::new(frame.sample_rate(), self.sample_rate, self.channels),
+            ));
+        }
+
+        let (_, _, mixer, resampler) = self.input.as_mut()?;
+        let mut mixed = Vec::with_capacity(frame.frames() * self.channels as usize);
+        mixer.process(frame.as_ref(), &mut mixed);
+
+        let mut samples = Vec::with_capacity(self.sample_rate / 100 * self.channels as usize);
+        resampler.process(&mixed, &mut samples);
+        Some(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn sine(sample_rate: usize, frequency: f64, frames: usize, channels: usize) -> Vec<i16> {
+        (0..frames)
+            .flat_map(|i| {
+                let value = (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin();
+                std::iter::repeat_n((value * 16000.0) as i16, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunked_input() {
+        for (input_rate, output_rate, channels) in [(44100, 48000, 2), (48000, 16000, 1)] {
+            let input = sine(input_rate, 440.0, input_rate / 2, channels);
+
+            let mut whole = Vec::new();
+            Resampler::new(input_rate, output_rate, channels as u8).process(&input, &mut whole);
+
+            let mut resampler = Resampler::new(input_rate, output_rate, channels as u8);
+            let mut chunked = Vec::new();
+            let mut rest = &input[..];
+            for size in [1, 7, 113, 441, 1009].iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+
+                let (chunk, tail) = rest.split_at((size * channels).min(rest.len()));
+                resampler.process(chunk, &mut chunked);
+                rest = tail;
+            }
+
+            assert_eq!(chunked, whole);
+
+            // Half a second of input gives half a second of output, less
+            // the samples still in the filter.
+            let frames = whole.len() / channels;
+            assert!(frames <= output_rate / 2);
+            assert!(frames + resampler.latency() * 2 >= output_rate / 2);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{AudioFrameChunker, AudioTrack};
+
+/// A producer of audio samples.
+///
+/// Unlike a video source, an audio source does not keep its own cadence, it
+/// is pulled by the driver as fast as the track consumes audio.
+pub trait AudioSource: Send {
+    /// The sample rate of the produced audio, a multiple of 100.
+    fn sample_rate(&self) -> usize;
+
+    /// The channel count of the produced audio.
+    fn channels(&self) -> u8;
+
+    /// Fill the buffer with interleaved samples, returns the number of
+    /// samples written. Returning 0 ends the source.
+    fn read(&mut self, buf: &mut [i16]) -> usize;
+}
+
+impl<T: AudioSource + ?Sized> AudioSource for Box<T> {
+    fn sample_rate(&self) -> usize {
+        (**self).sample_rate()
+    }
+
+    fn channels(&self) -> u8 {
+        (**self).channels()
+    }
+
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        (**self).read(buf)
+    }
+}
+
+/// Runs an audio source on a dedicated thread and pushes its audio into a
+/// local audio track, in 10ms frames at real time speed.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let source = ToneGenerator::new(Tone::Sine(440.0), 48000, 1);
+/// let track = AudioTrack::new("tone")?;
+/// let driver = AudioSourceDriver::spawn(source, track.clone());
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioSourceDriver {
+    closed: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl AudioSourceDriver {
+    /// Start pulling audio from the source, the driver stops when the
+    /// source ends or the driver is dropped.
+    pub fn spawn<S: AudioSource + 'static>(mut source: S, track: Arc<AudioTrack>) -> Self {
+        let closed = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let closed = closed.clone();
+            thread::spawn(move || {
+                let mut chunker = AudioFrameChunker::new(source.sample_rate(), source.channels());
+                chunker.set_pacing(true);
+
+                let mut buf = vec![0i16; chunker.frame_size() * source.channels() as usize];
+                while !closed.load(Ordering::Relaxed) {
+                    let size = source.read(&mut buf);
+                    if size == 0 {
+                        break;
+                    }
+
+                    chunker.write(&track, &buf[..size]);
+                }
+
+                if let Some(frame) = chunker.flush() {
+                    track.add_frame(&frame);
+                }
+            })
+        };
+
+        Self {
+            handle: Some(handle),
+            closed,
+        }
+    }
+
+    /// Whether the source has ended.
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map(|handle| handle.is_finished())
+            .unwrap_or(true)
+    }
+
+    /// Stop the driver and wait for the thread.
+    pub fn stop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for AudioSourceDriver {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,205 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::AudioFrame;
+
+/// The speaker layout of interleaved pcm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelLayout {
+    Mono,
+    /// Left, right.
+    Stereo,
+    /// Front left, front right, center, low frequency, surround left,
+    /// surround right, in the wave format channel order.
+    Surround51,
+}
+
+impl ChannelLayout {
+    /// The number of channels of the layout.
+    pub fn channels(&self) -> u8 {
+        match self {
+            Self::Mono => 1,
+            Self::Stereo => 2,
+            Self::Surround51 => 6,
+        }
+    }
+
+    /// Get the default layout for a channel count, if there is one.
+    pub fn from_channels(channels: u8) -> Option<Self> {
+        match channels {
+            1 => Some(Self::Mono),
+            2 => Some(Self::Stereo),
+            6 => Some(Self::Surround51),
+            _ => None,
+        }
+    }
+}
+
+/// Up and down mixing between channel layouts.
+///
+/// Down mixing follows the ITU-R BS.775 coefficients, the center and the
+/// surround channels are folded into left and right at -3dB, and the low
+/// frequency channel is dropped. Up mixing never invents content, mono goes
+/// to the center of a 5.1 layout and stereo to the front pair.
+pub struct ChannelMixer {
+    input: ChannelLayout,
+    output: ChannelLayout,
+    // Mixing matrix, one row of input gains per output channel.
+    matrix: Vec<Vec<f32>>,
+}
+
+impl ChannelMixer {
+    /// Create a mixer that converts from the input to the output layout.
+    pub fn new(input: ChannelLayout, output: ChannelLayout) -> Self {
+        use ChannelLayout::*;
+
+        let c = FRAC_1_SQRT_2;
+        let matrix = match (input, output) {
+            (Mono, Mono) => vec![vec![1.0]],
+            (Stereo, Stereo) => vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+            (Mono, Stereo) => vec![vec![1.0], vec![1.0]],
+            (Stereo, Mono) => vec![vec![0.5, 0.5]],
+            (Mono, Surround51) => vec![
+                vec![0.0],
+                vec![0.0],
+                vec![1.0],
+                vec![0.0],
+                vec![0.0],
+                vec![0.0],
+            ],
+            (Stereo, Surround51) => vec![
+                vec![1.0, 0.0],
+                vec![0.0, 1.0],
+                vec![0.0, 0.0],
+                vec![0.0, 0.0],
+                vec![0.0, 0.0],
+                vec![0.0, 0.0],
+            ],
+            (Surround51, Surround51) => (0..6)
+                .map(|i| (0..6).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+                .collect(),
+            (Surround51, Stereo) => {
+                let gain = 1.0 / (1.0 + c + c);
+                vec![
+                    vec![gain, 0.0, c * gain, 0.0, c * gain, 0.0],
+                    vec![0.0, gain, c * gain, 0.0, 0.0, c * gain],
+                ]
+            }
+            (Surround51, Mono) => {
+                let gain = 0.5 / (1.0 + c + c);
+                vec![vec![gain, gain, 2.0 * c * gain, 0.0, c * gain, c * gain]]
+            }
+        };
+
+        Self {
+            matrix,
+            output,
+            input,
+        }
+    }
+
+    /// The input layout.
+    pub fn input(&self) -> ChannelLayout {
+        self.input
+    }
+
+    /// The output layout.
+    pub fn output(&self) -> ChannelLayout {
+        self.output
+    }
+
+    /// Mix interleaved pcm and append the result to `output`.
+    ///
+    /// The input length must be a multiple of the input channel count.
+    pub fn process(&self, input: &[i16], output: &mut Vec<i16>) {
+        let channels = self.input.channels() as usize;
+        debug_assert_eq!(input.len() % channels, 0);
+
+        if self.input == self.output {
+            output.extend_from_slice(input);
+            return;
+        }
+
+        output.reserve(input.len() / channels * self.output.channels() as usize);
+        for frame in input.chunks_exact(channels) {
+            for row in &self.matrix {
+                let sum = row
+                    .iter()
+                    .zip(frame)
+                    .map(|(gain, sample)| gain * *sample as f32)
+                    .sum::<f32>();
+
+                output.push(sum.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16);
+            }
+        }
+    }
+
+    /// Mix an audio frame, the sample rate and timestamp are kept.
+    pub fn process_frame(&self, frame: &AudioFrame) -> AudioFrame {
+        assert_eq!(frame.channels(), self.input.channels());
+
+        let mut output = Vec::new();
+        self.process(frame.as_ref(), &mut output);
+        AudioFrame::from_pcm(
+            frame.sample_rate(),
+            self.output.channels(),
+            frame.timestamp() as usize,
+            output,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mix(mixer: &ChannelMixer, input: &[i16]) -> Vec<i16> {
+        let mut output = Vec::new();
+        mixer.process(input, &mut output);
+        output
+    }
+
+    #[test]
+    fn surround_to_stereo() {
+        let mixer = ChannelMixer::new(ChannelLayout::Surround51, ChannelLayout::Stereo);
+        assert_eq!(
+            mix(
+                &mixer,
+                &[
+                    1000, 0, 0, 0, 0, 0, // left
+                    0, 1000, 0, 0, 0, 0, // right
+                    0, 0, 1000, 0, 0, 0, // center
+                    0, 0, 0, 1000, 0, 0, // low frequency
+                    0, 0, 0, 0, 1000, 0, // left surround
+                    0, 0, 0, 0, 0, 1000, // right surround
+                ]
+            ),
+            [414, 0, 0, 414, 293, 293, 0, 0, 293, 0, 0, 293]
+        );
+
+        // Full scale on all channels mixes to full scale, not beyond.
+        let output = mix(&mixer, &[i16::MAX; 6]);
+        assert!(output.iter().all(|sample| *sample >= i16::MAX - 1));
+    }
+
+    #[test]
+    fn surround_to_mono() {
+        let mixer = ChannelMixer::new(ChannelLayout::Surround51, ChannelLayout::Mono);
+        assert_eq!(
+            mix(
+                &mixer,
+                &[
+                    1000, 0, 0, 0, 0, 0, // left
+                    0, 1000, 0, 0, 0, 0, // right
+                    0, 0, 1000, 0, 0, 0, // center
+                    0, 0, 0, 1000, 0, 0, // low frequency
+                    0, 0, 0, 0, 1000, 0, // left surround
+                    0, 0, 0, 0, 0, 1000, // right surround
+                ]
+            ),
+            [207, 207, 293, 0, 146, 146]
+        );
+
+        let output = mix(&mixer, &[i16::MAX; 6]);
+        assert!(output[0] >= i16::MAX - 1);
+    }
+}
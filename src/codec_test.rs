@@ -0,0 +1,53 @@
+use crate::{
+    MediaEngine, MediaStream, MediaStreamTrack, Observer, RTCConfiguration, RTCPeerConnection,
+};
+
+struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+/// Negotiate the track between two connections that use the codecs of the
+/// engine, returns the offer and the answer sdp.
+pub(crate) fn negotiate<F>(engine: F, track: MediaStreamTrack) -> (String, String)
+where
+    F: Fn() -> MediaEngine,
+{
+    let config = RTCConfiguration::default();
+    let offerer = RTCPeerConnection::with_media_engine(&config, engine(), NoopObserver).unwrap();
+    let answerer = RTCPeerConnection::with_media_engine(&config, engine(), NoopObserver).unwrap();
+
+    let stream = MediaStream::new("stream").unwrap();
+    offerer.add_track(track, stream).unwrap();
+
+    futures::executor::block_on(async {
+        let offer = offerer.create_offer().await.unwrap();
+        offerer.set_local_description(&offer).await.unwrap();
+        answerer.set_remote_description(&offer).await.unwrap();
+
+        let answer = answerer.create_answer().await.unwrap();
+        answerer.set_local_description(&answer).await.unwrap();
+        offerer.set_remote_description(&answer).await.unwrap();
+        (offer.sdp, answer.sdp)
+    })
+}
+
+/// The rtpmaps of the first media section of the kind, as in
+/// `L16/48000/2`, in order of preference.
+pub(crate) fn rtpmaps(sdp: &str, kind: &str) -> Vec<String> {
+    let media = format!("m={} ", kind);
+    let formats = sdp
+        .lines()
+        .find(|line| line.starts_with(media.as_str()))
+        .map(|line| line.split(' ').skip(3).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    formats
+        .iter()
+        .filter_map(|pt| {
+            let prefix = format!("a=rtpmap:{} ", pt);
+            sdp.lines()
+                .find_map(|line| line.strip_prefix(prefix.as_str()))
+                .map(|rtpmap| rtpmap.to_string())
+        })
+        .collect()
+}
@@ -14,6 +14,7 @@ use crate::{
     cstr::{from_c_str, StringError},
     rtc_peerconnection::RawRTCPeerConnection,
     rtc_session_description::RawRTCSessionDescription,
+    trace::{rtc_event, rtc_span, Span},
     Promisify, PromisifyExt, RTCSessionDescription,
 };
 
@@ -53,7 +54,7 @@ impl fmt::Display for CreateDescriptionError {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd)]
+#[derive(PartialEq, Eq, PartialOrd, Debug)]
 pub(crate) enum CreateDescriptionKind {
     Offer,
     Answer,
@@ -88,6 +89,7 @@ pub struct CreateDescriptionObserver {
     kind: CreateDescriptionKind,
     pc: *const RawRTCPeerConnection,
     ret: Arc<AtomicPtr<Result<RTCSessionDescription, CreateDescriptionError>>>,
+    span: Span,
 }
 
 unsafe impl Send for CreateDescriptionObserver {}
@@ -98,9 +100,29 @@ impl PromisifyExt for CreateDescriptionObserver {
     type Err = CreateDescriptionError;
 
     fn handle(&self, waker: Arc<AtomicWaker>) -> Result<(), Self::Err> {
+        rtc_event!(DEBUG, parent: &self.span, "create description started");
+
         let ret = self.ret.clone();
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
         let ctx = Box::into_raw(Box::new(CreateDescriptionContext {
             callback: Box::new(move |res| {
+                #[cfg(feature = "tracing")]
+                match &res {
+                    Ok(desc) => rtc_event!(
+                        DEBUG,
+                        parent: &span,
+                        size = desc.sdp.len(),
+                        "create description completed"
+                    ),
+                    Err(e) => rtc_event!(
+                        WARN,
+                        parent: &span,
+                        error = ?e,
+                        "create description failed"
+                    ),
+                }
+
                 ret.store(Box::into_raw(Box::new(res)), Ordering::Relaxed);
                 waker.wake();
             }),
@@ -127,8 +149,13 @@ impl PromisifyExt for CreateDescriptionObserver {
 
 pub type CreateDescriptionFuture = Promisify<CreateDescriptionObserver>;
 impl CreateDescriptionFuture {
-    pub(crate) fn create(pc: *const RawRTCPeerConnection, kind: CreateDescriptionKind) -> Self {
+    pub(crate) fn create(
+        pc: *const RawRTCPeerConnection,
+        kind: CreateDescriptionKind,
+        parent: &Span,
+    ) -> Self {
         Promisify::new(CreateDescriptionObserver {
+            span: rtc_span!(DEBUG, parent: parent, "create_description", ?kind),
             ret: Arc::new(AtomicPtr::new(std::ptr::null_mut())),
             kind,
             pc,
@@ -1,9 +1,16 @@
 use std::ffi::{c_char, CStr, CString};
 
-#[derive(Debug)]
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_free_string(str: *const c_char);
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum StringError {
     NulError,
     Utf8Error,
+    /// Native returned no string.
+    NullPointer,
 }
 
 pub(crate) fn to_c_str(str: &str) -> Result<*const c_char, StringError> {
@@ -22,6 +29,18 @@ pub(crate) fn from_c_str(str: *const c_char) -> Result<String, StringError> {
     })
 }
 
+/// Copy a string allocated by native and release it with the native
+/// allocator.
+pub(crate) fn take_native_c_str(str: *const c_char) -> Result<String, StringError> {
+    if str.is_null() {
+        return Err(StringError::NullPointer);
+    }
+
+    let value = from_c_str(str);
+    unsafe { rtc_free_string(str) }
+    value
+}
+
 pub(crate) fn c_str_to_str(str: *const c_char) -> Result<&'static str, StringError> {
     Ok(unsafe {
         CStr::from_ptr(str)
@@ -0,0 +1,388 @@
+use std::{
+    ffi::c_char,
+    slice::from_raw_parts,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::{cstr::c_str_to_str, MediaStreamTrackKind};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_send_encoded_frame(frame: *const crate::encoded_frame::RawEncodedFrame);
+
+    pub(crate) fn rtc_free_encoded_frame(frame: *const crate::encoded_frame::RawEncodedFrame);
+
+    pub(crate) fn rtc_set_encoded_frame_data(
+        frame: *const crate::encoded_frame::RawEncodedFrame,
+        data: *const u8,
+        size: usize,
+    );
+}
+
+/// The native transformable frame, owned by rust from the transform
+/// callback until it is either sent on or freed.
+#[repr(C)]
+pub(crate) struct RawEncodedFrame {
+    kind: MediaStreamTrackKind,
+    data: *const u8,
+    size: usize,
+    key_frame: bool,
+    ssrc: u32,
+    rtp_timestamp: u32,
+    payload_type: u8,
+    mime_type: *const c_char,
+    // -1 if unknown.
+    capture_time_ms: i64,
+    // video
+    width: u32,
+    height: u32,
+    frame_id: i64,
+    spatial_index: i32,
+    temporal_index: i32,
+    // audio
+    sequence_number: i32,
+}
+
+/// Owns the native frame and the replacement payload.
+struct EncodedFrameInner {
+    raw: *const RawEncodedFrame,
+    data: Option<Vec<u8>>,
+}
+
+unsafe impl Send for EncodedFrameInner {}
+unsafe impl Sync for EncodedFrameInner {}
+
+impl EncodedFrameInner {
+    fn raw(&self) -> &RawEncodedFrame {
+        unsafe { &*self.raw }
+    }
+
+    fn data(&self) -> &[u8] {
+        match &self.data {
+            Some(data) => data,
+            None => {
+                let raw = self.raw();
+                if raw.data.is_null() {
+                    &[]
+                } else {
+                    unsafe { from_raw_parts(raw.data, raw.size) }
+                }
+            }
+        }
+    }
+
+    fn mime_type(&self) -> &str {
+        c_str_to_str(self.raw().mime_type).unwrap_or("")
+    }
+
+    fn capture_time(&self) -> Option<i64> {
+        Some(self.raw().capture_time_ms).filter(|time| *time >= 0)
+    }
+
+    /// Hand the frame back to the native pipeline, with the replaced
+    /// payload if there is one.
+    fn send(mut self) {
+        if let Some(data) = self.data.take() {
+            unsafe { rtc_set_encoded_frame_data(self.raw, data.as_ptr(), data.len()) }
+        }
+
+        unsafe { rtc_send_encoded_frame(self.raw) }
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for EncodedFrameInner {
+    fn drop(&mut self) {
+        unsafe { rtc_free_encoded_frame(self.raw) }
+    }
+}
+
+/// An encoded video frame on its way between the encoder and the
+/// packetizer, or between the depacketizer and the decoder.
+pub struct EncodedVideoFrame {
+    inner: EncodedFrameInner,
+}
+
+impl EncodedVideoFrame {
+    /// The encoded payload.
+    pub fn data(&self) -> &[u8] {
+        self.inner.data()
+    }
+
+    /// Replace the payload, e.g. with an encrypted copy.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.inner.data = Some(data);
+    }
+
+    /// Whether the frame can be decoded without any earlier frame.
+    pub fn is_key_frame(&self) -> bool {
+        self.inner.raw().key_frame
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.inner.raw().ssrc
+    }
+
+    pub fn rtp_timestamp(&self) -> u32 {
+        self.inner.raw().rtp_timestamp
+    }
+
+    pub fn payload_type(&self) -> u8 {
+        self.inner.raw().payload_type
+    }
+
+    /// The codec mime type, as in "video/VP8".
+    pub fn mime_type(&self) -> &str {
+        self.inner.mime_type()
+    }
+
+    /// The capture time in milliseconds, if known.
+    pub fn capture_time(&self) -> Option<i64> {
+        self.inner.capture_time()
+    }
+
+    pub fn width(&self) -> u32 {
+        self.inner.raw().width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.inner.raw().height
+    }
+
+    /// The frame id from the dependency descriptor, if negotiated.
+    pub fn frame_id(&self) -> Option<i64> {
+        Some(self.inner.raw().frame_id).filter(|id| *id >= 0)
+    }
+
+    /// The simulcast or svc spatial layer of the frame.
+    pub fn spatial_index(&self) -> u32 {
+        self.inner.raw().spatial_index.max(0) as u32
+    }
+
+    /// The svc temporal layer of the frame.
+    pub fn temporal_index(&self) -> u32 {
+        self.inner.raw().temporal_index.max(0) as u32
+    }
+}
+
+/// An encoded audio frame on its way between the encoder and the
+/// packetizer, or between the depacketizer and the decoder.
+pub struct EncodedAudioFrame {
+    inner: EncodedFrameInner,
+}
+
+impl EncodedAudioFrame {
+    /// The encoded payload.
+    pub fn data(&self) -> &[u8] {
+        self.inner.data()
+    }
+
+    /// Replace the payload, e.g. with an encrypted copy.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.inner.data = Some(data);
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.inner.raw().ssrc
+    }
+
+    pub fn rtp_timestamp(&self) -> u32 {
+        self.inner.raw().rtp_timestamp
+    }
+
+    pub fn payload_type(&self) -> u8 {
+        self.inner.raw().payload_type
+    }
+
+    /// The codec mime type, as in "audio/opus".
+    pub fn mime_type(&self) -> &str {
+        self.inner.mime_type()
+    }
+
+    /// The capture time in milliseconds, if known.
+    pub fn capture_time(&self) -> Option<i64> {
+        self.inner.capture_time()
+    }
+
+    /// The rtp sequence number, only known on the receive side.
+    pub fn sequence_number(&self) -> Option<u16> {
+        u16::try_from(self.inner.raw().sequence_number).ok()
+    }
+}
+
+/// An encoded frame of either kind.
+pub enum EncodedFrame {
+    Video(EncodedVideoFrame),
+    Audio(EncodedAudioFrame),
+}
+
+impl EncodedFrame {
+    pub(crate) fn from_raw(raw: *const RawEncodedFrame) -> Self {
+        assert!(!raw.is_null());
+        let inner = EncodedFrameInner { raw, data: None };
+        match inner.raw().kind {
+            MediaStreamTrackKind::Video => Self::Video(EncodedVideoFrame { inner }),
+            MediaStreamTrackKind::Audio => Self::Audio(EncodedAudioFrame { inner }),
+        }
+    }
+
+    pub fn kind(&self) -> MediaStreamTrackKind {
+        match self {
+            Self::Video(_) => MediaStreamTrackKind::Video,
+            Self::Audio(_) => MediaStreamTrackKind::Audio,
+        }
+    }
+
+    /// The encoded payload.
+    pub fn data(&self) -> &[u8] {
+        self.inner().data()
+    }
+
+    /// Replace the payload.
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        match self {
+            Self::Video(frame) => frame.set_data(data),
+            Self::Audio(frame) => frame.set_data(data),
+        }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.inner().raw().ssrc
+    }
+
+    pub fn rtp_timestamp(&self) -> u32 {
+        self.inner().raw().rtp_timestamp
+    }
+
+    fn inner(&self) -> &EncodedFrameInner {
+        match self {
+            Self::Video(frame) => &frame.inner,
+            Self::Audio(frame) => &frame.inner,
+        }
+    }
+
+    fn into_inner(self) -> EncodedFrameInner {
+        match self {
+            Self::Video(frame) => frame.inner,
+            Self::Audio(frame) => frame.inner,
+        }
+    }
+}
+
+impl From<EncodedVideoFrame> for EncodedFrame {
+    fn from(frame: EncodedVideoFrame) -> Self {
+        Self::Video(frame)
+    }
+}
+
+impl From<EncodedAudioFrame> for EncodedFrame {
+    fn from(frame: EncodedAudioFrame) -> Self {
+        Self::Audio(frame)
+    }
+}
+
+/// Where transformed frames go, cheap to clone and can be moved to other
+/// threads to send frames later.
+///
+/// Frames sent after the transform has been removed are discarded.
+#[derive(Clone)]
+pub struct EncodedFrameSink {
+    active: Arc<AtomicBool>,
+}
+
+impl EncodedFrameSink {
+    /// Pass the frame on to the packetizer or the decoder.
+    pub fn send(&self, frame: impl Into<EncodedFrame>) {
+        if self.is_active() {
+            frame.into().into_inner().send();
+        }
+    }
+
+    /// Whether the transform is still installed.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+}
+
+/// Access to the encoded frames of a sender or a receiver, also known as
+/// insertable streams.
+///
+/// Every frame is handed to `transform` and it is up to the transform
+/// what happens next: send it right away, modify the payload first, keep
+/// it and send it later, or drop it to discard the frame.
+///
+/// ```no_run
+/// # use librtc::*;
+/// struct Passthrough;
+///
+/// impl EncodedFrameTransform for Passthrough {
+///     fn transform(&self, frame: EncodedFrame, sink: &EncodedFrameSink) {
+///         sink.send(frame);
+///     }
+/// }
+///
+/// # fn install(sender: &RTCRtpSender) {
+/// sender.set_transform(Passthrough);
+/// # }
+/// ```
+pub trait EncodedFrameTransform: Send + Sync {
+    fn transform(&self, frame: EncodedFrame, sink: &EncodedFrameSink);
+}
+
+/// The installed transform, handed to native as the callback context.
+pub(crate) struct TransformContext {
+    transform: Box<dyn EncodedFrameTransform>,
+    sink: EncodedFrameSink,
+}
+
+#[no_mangle]
+extern "C" fn on_encoded_frame(ctx: &TransformContext, frame: *const RawEncodedFrame) {
+    let frame = EncodedFrame::from_raw(frame);
+    if ctx.sink.is_active() {
+        ctx.transform.transform(frame, &ctx.sink);
+    }
+}
+
+/// The transform of a sender or a receiver, shared by both.
+#[derive(Default)]
+pub(crate) struct TransformSlot {
+    context: Mutex<Option<Box<TransformContext>>>,
+}
+
+impl TransformSlot {
+    /// Install the transform, replacing the previous one. `attach`
+    /// registers the context with native.
+    pub(crate) fn set<F, D>(&self, transform: Box<dyn EncodedFrameTransform>, attach: F, detach: D)
+    where
+        F: FnOnce(extern "C" fn(&TransformContext, *const RawEncodedFrame), &TransformContext),
+        D: FnOnce(),
+    {
+        let mut context = self.context.lock().unwrap();
+        if let Some(previous) = context.take() {
+            detach();
+            previous.sink.active.store(false, Ordering::Relaxed);
+        }
+
+        let ctx = Box::new(TransformContext {
+            sink: EncodedFrameSink {
+                active: Arc::new(AtomicBool::new(true)),
+            },
+            transform,
+        });
+
+        attach(on_encoded_frame, &ctx);
+        let _ = context.insert(ctx);
+    }
+
+    /// Remove the transform, native must not call the context once
+    /// `detach` returned.
+    pub(crate) fn remove<D: FnOnce()>(&self, detach: D) {
+        if let Some(previous) = self.context.lock().unwrap().take() {
+            detach();
+            previous.sink.active.store(false, Ordering::Relaxed);
+        }
+    }
+}
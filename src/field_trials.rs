@@ -0,0 +1,159 @@
+use std::{error::Error, ffi::c_char, fmt, sync::Mutex};
+
+use crate::cstr::to_c_str;
+
+extern "C" {
+    pub(crate) fn rtc_set_field_trials(trials: *const c_char);
+}
+
+#[derive(Debug)]
+pub enum FieldTrialsError {
+    /// The trials are not `Name/Group/` pairs, the invalid part is given.
+    InvalidFormat(String),
+    /// The global field trials can only be set once per process.
+    AlreadySet,
+}
+
+impl Error for FieldTrialsError {}
+
+impl fmt::Display for FieldTrialsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFormat(part) => write!(f, "invalid field trials: {:?}", part),
+            Self::AlreadySet => write!(f, "field trials are already set"),
+        }
+    }
+}
+
+// Native refers to the string for the rest of the process, it is never
+// freed.
+static FIELD_TRIALS: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Set the field trials of the whole process, in the native format, as
+/// in `WebRTC-Video-BalancedDegradation/Enabled/WebRTC-Foo/Disabled/`.
+///
+/// Call it once before any peer connection is created, the trials of a
+/// single factory can be set with `PeerConnectionFactoryBuilder`.
+pub fn set_field_trials(trials: &str) -> Result<(), FieldTrialsError> {
+    check_format(trials)?;
+
+    let mut current = FIELD_TRIALS.lock().unwrap();
+    if current.is_some() {
+        return Err(FieldTrialsError::AlreadySet);
+    }
+
+    let raw = to_c_str(trials).map_err(|_| FieldTrialsError::InvalidFormat(trials.to_string()))?;
+    unsafe { rtc_set_field_trials(raw) }
+    let _ = current.insert(raw as usize);
+    Ok(())
+}
+
+/// Check that the trials are `Name/Group/` pairs.
+fn check_format(trials: &str) -> Result<(), FieldTrialsError> {
+    let parts = trials.split('/').collect::<Vec<_>>();
+    let (last, pairs) = parts.split_last().unwrap();
+    if !last.is_empty() {
+        return Err(FieldTrialsError::InvalidFormat(last.to_string()));
+    }
+
+    for pair in pairs.chunks(2) {
+        if pair.len() != 2 || pair.iter().any(|part| part.is_empty()) {
+            return Err(FieldTrialsError::InvalidFormat(pair.join("/")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Set the field trials of the whole process from trial names and their
+/// groups.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), FieldTrialsError> {
+/// set_field_trials_from_map([
+///     ("WebRTC-Video-BalancedDegradation", "Enabled"),
+///     ("WebRTC-Audio-Red-For-Opus", "Enabled"),
+/// ])?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_field_trials_from_map<I, K, V>(trials: I) -> Result<(), FieldTrialsError>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    set_field_trials(&join_trials(trials)?)
+}
+
+/// Join trial names and groups into the native format.
+fn join_trials<I, K, V>(trials: I) -> Result<String, FieldTrialsError>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut joined = String::new();
+    for (name, group) in trials {
+        let (name, group) = (name.as_ref(), group.as_ref());
+        if name.contains('/') || group.contains('/') {
+            return Err(FieldTrialsError::InvalidFormat(format!(
+                "{}/{}",
+                name, group
+            )));
+        }
+
+        joined.push_str(name);
+        joined.push('/');
+        joined.push_str(group);
+        joined.push('/');
+    }
+
+    Ok(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        assert!(check_format("").is_ok());
+        assert!(check_format("A/B/").is_ok());
+        assert!(check_format("WebRTC-Foo/Enabled/WebRTC-Bar/Disabled/").is_ok());
+
+        for (trials, part) in [
+            ("A/B", "B"),
+            ("A//", "A/"),
+            ("/B/", "/B"),
+            ("A/B/C/", "C"),
+            ("A/B/C", "C"),
+        ] {
+            assert!(
+                matches!(
+                    check_format(trials),
+                    Err(FieldTrialsError::InvalidFormat(invalid)) if invalid == part
+                ),
+                "{}",
+                trials
+            );
+        }
+    }
+
+    #[test]
+    fn map() {
+        assert_eq!(
+            join_trials([("A", "B"), ("WebRTC-Foo", "Enabled")]).unwrap(),
+            "A/B/WebRTC-Foo/Enabled/"
+        );
+        assert_eq!(join_trials(Vec::<(&str, &str)>::new()).unwrap(), "");
+
+        for trials in [[("A/B", "C")], [("A", "B/C")]] {
+            assert!(matches!(
+                join_trials(trials),
+                Err(FieldTrialsError::InvalidFormat(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+use futures::Stream;
+
+use crate::{SinkExt, SinkHandle};
+
+/// What a frame stream does when frames arrive faster than they are
+/// consumed and the queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FramePolicy {
+    /// Drop the oldest queued frame to make room for the new one.
+    DropOldest,
+    /// Drop the incoming frame, the queued frames are kept.
+    DropNewest,
+    /// Only keep the most recent frame, the capacity is ignored. Suited for
+    /// rendering, where a stale frame is worth nothing.
+    KeepLatest,
+}
+
+struct QueueState<T> {
+    frames: VecDeque<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct FrameQueueShared<T> {
+    capacity: usize,
+    policy: FramePolicy,
+    state: Mutex<QueueState<T>>,
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// The sink half of a frame stream, registered to the track.
+///
+/// The sink only queues the frame and wakes the consumer, so the webrtc
+/// thread is never blocked by slow processing.
+pub(crate) struct FrameQueue<T> {
+    shared: Arc<FrameQueueShared<T>>,
+}
+
+impl<T: Send> SinkExt for FrameQueue<T> {
+    type Item = T;
+
+    fn on_data(&self, frame: T) {
+        self.shared.received.fetch_add(1, Ordering::Relaxed);
+
+        let waker = {
+            let mut state = self.shared.state.lock().unwrap();
+            let dropped = match self.shared.policy {
+                FramePolicy::DropOldest => {
+                    let mut dropped = 0;
+                    while state.frames.len() >= self.shared.capacity {
+                        state.frames.pop_front();
+                        dropped += 1;
+                    }
+
+                    state.frames.push_back(frame);
+                    dropped
+                }
+                FramePolicy::DropNewest => {
+                    if state.frames.len() >= self.shared.capacity {
+                        1
+                    } else {
+                        state.frames.push_back(frame);
+                        0
+                    }
+                }
+                FramePolicy::KeepLatest => {
+                    let dropped = state.frames.len() as u64;
+                    state.frames.clear();
+                    state.frames.push_back(frame);
+                    dropped
+                }
+            };
+
+            self.shared.dropped.fetch_add(dropped, Ordering::Relaxed);
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for FrameQueue<T> {
+    // The sink is dropped together with the track, which ends the stream.
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.shared.state.lock().unwrap();
+            state.closed = true;
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// An asynchronous stream of the frames of a track.
+///
+/// Frames are queued on the webrtc thread and consumed from any async
+/// runtime, when the consumer falls behind frames are dropped according to
+/// the policy. The sink is removed from the track when the stream is
+/// dropped, and the stream ends when the track is dropped.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use futures::StreamExt;
+/// # use librtc::*;
+/// # async fn render(_frame: &VideoFrame) {}
+/// # async fn run(track: Arc<VideoTrack>) {
+/// let mut frames = track.frames(4, FramePolicy::KeepLatest);
+/// while let Some(frame) = frames.next().await {
+///     render(&frame).await;
+/// }
+/// # }
+/// ```
+pub struct FrameStream<T> {
+    shared: Arc<FrameQueueShared<T>>,
+    handle: Option<SinkHandle>,
+}
+
+impl<T> FrameStream<T> {
+    /// Create the stream and the sink that feeds it.
+    pub(crate) fn channel(capacity: usize, policy: FramePolicy) -> (FrameQueue<T>, Self) {
+        let shared = Arc::new(FrameQueueShared {
+            capacity: capacity.max(1),
+            state: Mutex::new(QueueState {
+                frames: VecDeque::with_capacity(capacity.max(1)),
+                closed: false,
+                waker: None,
+            }),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            policy,
+        });
+
+        (
+            FrameQueue {
+                shared: shared.clone(),
+            },
+            Self {
+                handle: None,
+                shared,
+            },
+        )
+    }
+
+    /// Keep the sink registered for as long as the stream lives.
+    pub(crate) fn attach(&mut self, handle: SinkHandle) {
+        let _ = self.handle.insert(handle);
+    }
+
+    /// Number of frames delivered by the track so far.
+    pub fn received(&self) -> u64 {
+        self.shared.received.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames waiting to be consumed.
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().frames.len()
+    }
+}
+
+impl<T> Stream for FrameStream<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.frames.pop_front() {
+            Some(frame) => Poll::Ready(Some(frame)),
+            None if state.closed => Poll::Ready(None),
+            None => {
+                let _ = state.waker.insert(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use crate::VideoFrame;
+
+/// A borrowed image plane with its row stride.
+struct Plane<'a> {
+    data: &'a [u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Bilinear scaling of one image plane, in 16.16 fixed point.
+fn scale_plane(src: &Plane, dst: &mut [u8], dst_stride: usize, width: usize, height: usize) {
+    if width == 0 || height == 0 || src.width == 0 || src.height == 0 {
+        return;
+    }
+
+    if src.width == width && src.height == height {
+        for row in 0..height {
+            dst[row * dst_stride..row * dst_stride + width]
+                .copy_from_slice(&src.data[row * src.stride..row * src.stride + width]);
+        }
+
+        return;
+    }
+
+    // Map the centers of the destination pixels onto the source.
+    let position = |i: usize, src_len: usize, dst_len: usize| -> (usize, usize, u32) {
+        let center = (((2 * i + 1) * src_len) << 16) / (2 * dst_len);
+        let pos = center.saturating_sub(1 << 15);
+        let index = (pos >> 16).min(src_len - 1);
+        let next = (index + 1).min(src_len - 1);
+        (index, next, (pos & 0xffff) as u32)
+    };
+
+    let columns = (0..width)
+        .map(|x| position(x, src.width, width))
+        .collect::<Vec<_>>();
+
+    for y in 0..height {
+        let (y0, y1, fy) = position(y, src.height, height);
+        let row0 = &src.data[y0 * src.stride..];
+        let row1 = &src.data[y1 * src.stride..];
+        let out = &mut dst[y * dst_stride..y * dst_stride + width];
+        for (pixel, (x0, x1, fx)) in out.iter_mut().zip(&columns) {
+            let top = row0[*x0] as u32 * (0x10000 - fx) + row0[*x1] as u32 * fx;
+            let bottom = row1[*x0] as u32 * (0x10000 - fx) + row1[*x1] as u32 * fx;
+            let value = ((top >> 8) * (0x10000 - fy) + (bottom >> 8) * fy) >> 24;
+            *pixel = value as u8;
+        }
+    }
+}
+
+/// An owned i420 image, the planes are tightly packed.
+///
+/// The buffer is the working type for producing and processing video in
+/// rust, it can be scaled, drawn into and turned into a video frame for
+/// `VideoTrack::add_frame`.
+#[derive(Clone, Debug)]
+pub struct I420Buffer {
+    width: u32,
+    height: u32,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl I420Buffer {
+    /// Create a black image.
+    pub fn new(width: u32, height: u32) -> Self {
+        let chroma = (width.div_ceil(2) * height.div_ceil(2)) as usize;
+        Self {
+            y: vec![16; (width * height) as usize],
+            u: vec![128; chroma],
+            v: vec![128; chroma],
+            height,
+            width,
+        }
+    }
+
+    /// Copy a video frame into a new buffer.
+    pub fn from_frame(frame: &VideoFrame) -> Self {
+        Self::from_frame_scaled(frame, frame.width(), frame.height())
+    }
+
+    /// Copy a video frame into a new buffer of a different size.
+    pub fn from_frame_scaled(frame: &VideoFrame, width: u32, height: u32) -> Self {
+        let mut buffer = Self::new(width, height);
+        let (frame_width, frame_height) = (frame.width() as usize, frame.height() as usize);
+        let planes = [
+            Plane {
+                data: frame.data_y(),
+                stride: frame.stride_y(),
+                width: frame_width,
+                height: frame_height,
+            },
+            Plane {
+                data: frame.data_u(),
+                stride: frame.stride_u(),
+                width: frame_width.div_ceil(2),
+                height: frame_height.div_ceil(2),
+            },
+            Plane {
+                data: frame.data_v(),
+                stride: frame.stride_v(),
+                width: frame_width.div_ceil(2),
+                height: frame_height.div_ceil(2),
+            },
+        ];
+
+        buffer.scale_planes_from(&planes);
+        buffer
+    }
+
+    /// Create a buffer from tightly packed planes.
+    ///
+    /// Returns `None` if the plane sizes do not match the dimensions.
+    pub fn from_planes(
+        width: u32,
+        height: u32,
+        y: Vec<u8>,
+        u: Vec<u8>,
+        v: Vec<u8>,
+    ) -> Option<Self> {
+        let chroma = (width.div_ceil(2) * height.div_ceil(2)) as usize;
+        if y.len() != (width * height) as usize || u.len() != chroma || v.len() != chroma {
+            return None;
+        }
+
+        Some(Self {
+            height,
+            width,
+            y,
+            u,
+            v,
+        })
+    }
+
+    /// get image width
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// get image height
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// get y plane stride
+    pub fn stride_y(&self) -> usize {
+        self.width as usize
+    }
+
+    /// get u and v plane stride
+    pub fn stride_uv(&self) -> usize {
+        self.width.div_ceil(2) as usize
+    }
+
+    /// get y plane
+    pub fn data_y(&self) -> &[u8] {
+        &self.y
+    }
+
+    /// get u plane
+    pub fn data_u(&self) -> &[u8] {
+        &self.u
+    }
+
+    /// get v plane
+    pub fn data_v(&self) -> &[u8] {
+        &self.v
+    }
+
+    /// get all planes for writing
+    pub fn planes_mut(&mut self) -> (&mut [u8], &mut [u8], &mut [u8]) {
+        (&mut self.y, &mut self.u, &mut self.v)
+    }
+
+    /// Fill the whole image with one color.
+    pub fn fill(&mut self, color: [u8; 3]) {
+        self.y.fill(color[0]);
+        self.u.fill(color[1]);
+        self.v.fill(color[2]);
+    }
+
+    /// Fill a rectangle with one color, the rectangle is clipped to the
+    /// image.
+    ///
+    /// Chroma is subsampled, so odd positions and sizes are rounded to the
+    /// enclosing chroma samples.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3]) {
+        let right = (x + width).min(self.width);
+        let bottom = (y + height).min(self.height);
+        if x >= right || y >= bottom {
+            return;
+        }
+
+        let stride = self.stride_y();
+        for row in y..bottom {
+            let offset = row as usize * stride;
+            self.y[offset + x as usize..offset + right as usize].fill(color[0]);
+        }
+
+        let stride = self.stride_uv();
+        for row in y / 2..bottom.div_ceil(2) {
+            let offset = row as usize * stride;
+            let range = offset + (x / 2) as usize..offset + right.div_ceil(2) as usize;
+            self.u[range.clone()].fill(color[1]);
+            self.v[range].fill(color[2]);
+        }
+    }
+
+    /// Scale the image to a new size.
+    pub fn scale(&self, width: u32, height: u32) -> Self {
+        let mut buffer = Self::new(width, height);
+        let planes = [
+            Plane {
+                stride: self.stride_y(),
+                width: self.width as usize,
+                height: self.height as usize,
+                data: &self.y,
+            },
+            Plane {
+                stride: self.stride_uv(),
+                width: self.stride_uv(),
+                height: self.height.div_ceil(2) as usize,
+                data: &self.u,
+            },
+            Plane {
+                stride: self.stride_uv(),
+                width: self.stride_uv(),
+                height: self.height.div_ceil(2) as usize,
+                data: &self.v,
+            },
+        ];
+
+        buffer.scale_planes_from(&planes);
+        buffer
+    }
+
+    /// Copy another image into this one with its top left corner at the
+    /// given position, the part outside of this image is cut off.
+    ///
+    /// The position is rounded down to even coordinates, so that the chroma
+    /// planes line up.
+    pub fn blit(&mut self, src: &I420Buffer, x: u32, y: u32) {
+        let (x, y) = (x & !1, y & !1);
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let width = src.width.min(self.width - x) as usize;
+        let height = src.height.min(self.height - y) as usize;
+        let (dst_stride, src_stride) = (self.stride_y(), src.stride_y());
+        for row in 0..height {
+            let dst = (y as usize + row) * dst_stride + x as usize;
+            let from = row * src_stride;
+            self.y[dst..dst + width].copy_from_slice(&src.y[from..from + width]);
+        }
+
+        let width = width.div_ceil(2);
+        let height = height.div_ceil(2);
+        let (dst_stride, src_stride) = (self.stride_uv(), src.stride_uv());
+        let width = width.min(dst_stride - x as usize / 2);
+        let height = height.min((self.height as usize).div_ceil(2) - y as usize / 2);
+        for row in 0..height {
+            let dst = (y as usize / 2 + row) * dst_stride + x as usize / 2;
+            let from = row * src_stride;
+            self.u[dst..dst + width].copy_from_slice(&src.u[from..from + width]);
+            self.v[dst..dst + width].copy_from_slice(&src.v[from..from + width]);
+        }
+    }
+
+    /// Turn the buffer into a video frame, the timestamp is in milliseconds.
+    pub fn into_frame(self, timestamp: usize) -> VideoFrame {
+        VideoFrame::from_buffer(self, timestamp)
+    }
+
+    fn scale_planes_from(&mut self, planes: &[Plane; 3]) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let (stride_y, stride_uv) = (self.stride_y(), self.stride_uv());
+
+        scale_plane(&planes[0], &mut self.y, stride_y, width, height);
+        scale_plane(
+            &planes[1],
+            &mut self.u,
+            stride_uv,
+            chroma_width,
+            chroma_height,
+        );
+        scale_plane(
+            &planes[2],
+            &mut self.v,
+            stride_uv,
+            chroma_width,
+            chroma_height,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn odd_size_frame() {
+        // 5x5 has 3x3 chroma, the last chroma row only covers one luma row.
+        let y = (0..25).collect::<Vec<u8>>();
+        let u = (100..109).collect::<Vec<u8>>();
+        let v = (200..209).collect::<Vec<u8>>();
+        let buffer = I420Buffer::from_planes(5, 5, y, u, v).unwrap();
+        let frame = VideoFrame::from_buffer(buffer.clone(), 0);
+
+        let copy = I420Buffer::from_frame(&frame);
+        assert_eq!(copy.data_y(), buffer.data_y());
+        assert_eq!(copy.data_u(), buffer.data_u());
+        assert_eq!(copy.data_v(), buffer.data_v());
+    }
+}
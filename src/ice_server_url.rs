@@ -0,0 +1,217 @@
+use std::{error::Error, fmt, net::Ipv6Addr, str::FromStr};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IceUrlError {
+    /// The url is not a `stun:`, `stuns:`, `turn:` or `turns:` url.
+    UnknownScheme,
+    MissingHost,
+    /// The host is not a host name, an ipv4 address or an ipv6 address in
+    /// brackets.
+    InvalidHost,
+    InvalidPort,
+    /// A query other than `?transport=udp` or `?transport=tcp` on a turn
+    /// url, stun urls take no query.
+    InvalidQuery,
+}
+
+impl Error for IceUrlError {}
+
+impl fmt::Display for IceUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownScheme => write!(f, "not a stun or turn url"),
+            Self::MissingHost => write!(f, "missing host"),
+            Self::InvalidHost => write!(f, "invalid host"),
+            Self::InvalidPort => write!(f, "invalid port"),
+            Self::InvalidQuery => write!(f, "invalid query"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IceUrlScheme {
+    Stun,
+    Stuns,
+    Turn,
+    Turns,
+}
+
+impl IceUrlScheme {
+    /// Whether the server is a turn server, which needs credentials.
+    pub fn is_turn(&self) -> bool {
+        matches!(self, Self::Turn | Self::Turns)
+    }
+
+    /// Whether the connection to the server is secured with tls.
+    pub fn is_secure(&self) -> bool {
+        matches!(self, Self::Stuns | Self::Turns)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IceTransportProtocol {
+    Udp,
+    Tcp,
+}
+
+/// A parsed stun or turn url, as in RFC 7064 and RFC 7065.
+///
+/// ```
+/// # use librtc::*;
+/// # fn main() -> Result<(), IceUrlError> {
+/// let url = "turn:turn.example.com?transport=tcp".parse::<IceServerUrl>()?;
+///
+/// assert_eq!(url.scheme, IceUrlScheme::Turn);
+/// assert_eq!(url.host, "turn.example.com");
+/// assert_eq!(url.port, 3478);
+/// assert_eq!(url.transport, Some(IceTransportProtocol::Tcp));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IceServerUrl {
+    pub scheme: IceUrlScheme,
+    /// The host name or address, ipv6 addresses without the brackets.
+    pub host: String,
+    /// The given port, or the default port of the scheme.
+    pub port: u16,
+    /// The transport of a turn url, if given.
+    pub transport: Option<IceTransportProtocol>,
+}
+
+impl FromStr for IceServerUrl {
+    type Err = IceUrlError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let (scheme, rest) = url.split_once(':').ok_or(IceUrlError::UnknownScheme)?;
+        let scheme = match scheme.to_ascii_lowercase().as_str() {
+            "stun" => IceUrlScheme::Stun,
+            "stuns" => IceUrlScheme::Stuns,
+            "turn" => IceUrlScheme::Turn,
+            "turns" => IceUrlScheme::Turns,
+            _ => return Err(IceUrlError::UnknownScheme),
+        };
+
+        let (authority, query) = match rest.split_once('?') {
+            Some((authority, query)) => (authority, Some(query)),
+            None => (rest, None),
+        };
+
+        let transport = match query {
+            None => None,
+            Some(query) if scheme.is_turn() => match query.strip_prefix("transport=") {
+                Some("udp") => Some(IceTransportProtocol::Udp),
+                Some("tcp") => Some(IceTransportProtocol::Tcp),
+                _ => return Err(IceUrlError::InvalidQuery),
+            },
+            Some(_) => return Err(IceUrlError::InvalidQuery),
+        };
+
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(rest) => {
+                let (host, rest) = rest.split_once(']').ok_or(IceUrlError::InvalidHost)?;
+                host.parse::<Ipv6Addr>()
+                    .map_err(|_| IceUrlError::InvalidHost)?;
+                let port = match rest {
+                    "" => None,
+                    _ => Some(rest.strip_prefix(':').ok_or(IceUrlError::InvalidHost)?),
+                };
+
+                (host, port)
+            }
+            None => {
+                let (host, port) = match authority.split_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (authority, None),
+                };
+
+                let is_valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+                if !host.chars().all(is_valid) {
+                    return Err(IceUrlError::InvalidHost);
+                }
+
+                (host, port)
+            }
+        };
+
+        if host.is_empty() {
+            return Err(IceUrlError::MissingHost);
+        }
+
+        let port = match port {
+            Some(port) => match port.parse::<u16>() {
+                Ok(port) if port > 0 => port,
+                _ => return Err(IceUrlError::InvalidPort),
+            },
+            None if scheme.is_secure() => 5349,
+            None => 3478,
+        };
+
+        Ok(Self {
+            host: host.to_string(),
+            scheme,
+            port,
+            transport,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Result<IceServerUrl, IceUrlError> {
+        url.parse()
+    }
+
+    #[test]
+    fn urls() {
+        let url = parse("turn:[2001:db8::1]:3479?transport=udp").unwrap();
+        assert_eq!(url.scheme, IceUrlScheme::Turn);
+        assert_eq!(url.host, "2001:db8::1");
+        assert_eq!(url.port, 3479);
+        assert_eq!(url.transport, Some(IceTransportProtocol::Udp));
+
+        let url = parse("stun:[::1]").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 3478));
+
+        let url = parse("STUN:stun.l.google.com:19302").unwrap();
+        assert_eq!(url.scheme, IceUrlScheme::Stun);
+        assert_eq!((url.host.as_str(), url.port), ("stun.l.google.com", 19302));
+
+        for (url, scheme, port) in [
+            ("stun:192.0.2.1", IceUrlScheme::Stun, 3478),
+            ("turn:192.0.2.1", IceUrlScheme::Turn, 3478),
+            ("stuns:192.0.2.1", IceUrlScheme::Stuns, 5349),
+            ("turns:192.0.2.1", IceUrlScheme::Turns, 5349),
+        ] {
+            let url = parse(url).unwrap();
+            assert_eq!((url.scheme, url.port, url.transport), (scheme, port, None));
+        }
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for (url, error) in [
+            ("http://example.com", IceUrlError::UnknownScheme),
+            ("example.com", IceUrlError::UnknownScheme),
+            ("stun:example.com?transport=udp", IceUrlError::InvalidQuery),
+            ("stuns:example.com?transport=tcp", IceUrlError::InvalidQuery),
+            ("turn:example.com?transport=sctp", IceUrlError::InvalidQuery),
+            ("turn:example.com?foo=bar", IceUrlError::InvalidQuery),
+            ("turn:example.com:0", IceUrlError::InvalidPort),
+            ("turn:example.com:65536", IceUrlError::InvalidPort),
+            ("turn:example.com:", IceUrlError::InvalidPort),
+            ("stun:", IceUrlError::MissingHost),
+            ("stun::3478", IceUrlError::MissingHost),
+            ("stun:[]", IceUrlError::InvalidHost),
+            ("stun:[::1", IceUrlError::InvalidHost),
+            ("stun:[::1]3478", IceUrlError::InvalidHost),
+            ("stun:[example.com]", IceUrlError::InvalidHost),
+            ("stun:user@example.com", IceUrlError::InvalidHost),
+            ("stun:2001:db8::1", IceUrlError::InvalidPort),
+        ] {
+            assert_eq!(parse(url), Err(error), "{}", url);
+        }
+    }
+}
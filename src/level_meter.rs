@@ -0,0 +1,284 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{AudioFrame, SinkExt};
+
+/// The lowest reported level, used for digital silence.
+const MIN_DBFS: f32 = -127.0;
+
+/// The webrtc audio level is the absolute peak over this many frames.
+const LEVEL_UPDATE_FRAMES: u32 = 10;
+
+fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        MIN_DBFS
+    } else {
+        (20.0 * (amplitude / 32768.0).log10()).max(MIN_DBFS)
+    }
+}
+
+/// The measured level of the most recent audio frame.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioLevel {
+    /// Root mean square of the frame, in dBFS.
+    pub rms_dbfs: f32,
+    /// Absolute peak sample of the frame, in dBFS.
+    pub peak_dbfs: f32,
+    /// Smoothed audio level in the webrtc scale, from 0.0 for silence to
+    /// 1.0 for full scale, the same value that is reported as `audioLevel`
+    /// in the stats.
+    pub level: f32,
+    /// Whether voice activity is detected, including the hangover time.
+    pub voice_activity: bool,
+    /// The timestamp of the measured frame.
+    pub timestamp: i64,
+}
+
+impl Default for AudioLevel {
+    fn default() -> Self {
+        Self {
+            rms_dbfs: MIN_DBFS,
+            peak_dbfs: MIN_DBFS,
+            voice_activity: false,
+            timestamp: 0,
+            level: 0.0,
+        }
+    }
+}
+
+/// Tuning of the energy and zero crossing voice activity detector.
+#[derive(Clone, Copy, Debug)]
+pub struct VadOptions {
+    /// How far the frame energy has to rise above the tracked noise floor to
+    /// count as voice, in dB.
+    pub margin_db: f32,
+    /// Frames quieter than this never count as voice, in dBFS.
+    pub min_dbfs: f32,
+    /// Frames with a higher zero crossing rate are considered noise, the
+    /// rate is the fraction of adjacent samples that change sign.
+    pub max_zero_crossing_rate: f32,
+    /// Number of consecutive voice frames before activity is reported.
+    pub attack_frames: u32,
+    /// Number of frames activity is held after the last voice frame.
+    pub hangover_frames: u32,
+}
+
+impl Default for VadOptions {
+    fn default() -> Self {
+        Self {
+            margin_db: 9.0,
+            min_dbfs: -50.0,
+            max_zero_crossing_rate: 0.35,
+            attack_frames: 3,
+            hangover_frames: 30,
+        }
+    }
+}
+
+struct MeterState {
+    level: AudioLevel,
+    // webrtc level smoothing.
+    abs_max: i32,
+    count: u32,
+    current: f32,
+    // voice activity detection.
+    noise_floor: Option<f32>,
+    voice_frames: u32,
+    hangover: u32,
+}
+
+type LevelCallback = Box<dyn Fn(&AudioLevel) + Send + Sync>;
+
+struct LevelMeterInner {
+    options: VadOptions,
+    state: Mutex<MeterState>,
+    subscribers: RwLock<Vec<LevelCallback>>,
+}
+
+/// Audio level meter and voice activity detector for audio tracks.
+///
+/// The meter is an audio track sink, it measures every frame that reaches
+/// the track. Clones share the same state, so one clone can be registered
+/// to the track while another one is queried.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let track = AudioTrack::new("audio")?;
+/// let meter = LevelMeter::new();
+/// let _sink = track.add_sink(Sinker::new(meter.clone()));
+///
+/// meter.subscribe(|level| {
+///     if level.voice_activity {
+///         println!("speaking, level: {}", level.level);
+///     }
+/// });
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct LevelMeter {
+    inner: Arc<LevelMeterInner>,
+}
+
+impl Default for LevelMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LevelMeter {
+    /// Create a level meter with the default voice activity detection.
+    pub fn new() -> Self {
+        Self::with_options(VadOptions::default())
+    }
+
+    /// Create a level meter with custom voice activity detection.
+    pub fn with_options(options: VadOptions) -> Self {
+        Self {
+            inner: Arc::new(LevelMeterInner {
+                subscribers: RwLock::new(Vec::new()),
+                state: Mutex::new(MeterState {
+                    level: AudioLevel::default(),
+                    noise_floor: None,
+                    voice_frames: 0,
+                    current: 0.0,
+                    hangover: 0,
+                    abs_max: 0,
+                    count: 0,
+                }),
+                options,
+            }),
+        }
+    }
+
+    /// Get the level of the most recent frame.
+    pub fn level(&self) -> AudioLevel {
+        self.inner.state.lock().unwrap().level
+    }
+
+    /// Whether voice activity is currently detected.
+    pub fn is_speaking(&self) -> bool {
+        self.level().voice_activity
+    }
+
+    /// Call the handler with the level of every measured frame.
+    ///
+    /// The handler is called on the webrtc thread that delivers the frames,
+    /// so it should not block.
+    pub fn subscribe<F>(&self, handler: F)
+    where
+        F: Fn(&AudioLevel) + Send + Sync + 'static,
+    {
+        self.inner
+            .subscribers
+            .write()
+            .unwrap()
+            .push(Box::new(handler));
+    }
+
+    /// Measure one frame and update the smoothed level and voice activity.
+    pub fn process(&self, frame: &AudioFrame) -> AudioLevel {
+        let samples = frame.as_ref();
+        let channels = (frame.channels() as usize).max(1);
+
+        let mut peak = 0i32;
+        let mut energy = 0f64;
+        for sample in samples {
+            peak = peak.max((*sample as i32).abs());
+            energy += *sample as f64 * *sample as f64;
+        }
+
+        let rms = if samples.is_empty() {
+            0.0
+        } else {
+            (energy / samples.len() as f64).sqrt() as f32
+        };
+
+        // Zero crossings are counted on the first channel only.
+        let mut crossings = 0;
+        let mut previous = None;
+        for sample in samples.iter().step_by(channels) {
+            if let Some(previous) = previous {
+                if (previous < 0) != (*sample < 0) {
+                    crossings += 1;
+                }
+            }
+
+            previous = Some(*sample);
+        }
+
+        let zero_crossing_rate = crossings as f32 / (samples.len() / channels).max(1) as f32;
+
+        let level = {
+            let options = &self.inner.options;
+            let mut state = self.inner.state.lock().unwrap();
+
+            // Same as the webrtc audio level, the absolute peak over the
+            // last ten frames, decaying when the signal goes down.
+            state.abs_max = state.abs_max.max(peak);
+            state.count += 1;
+            if state.count >= LEVEL_UPDATE_FRAMES {
+                state.current = state.abs_max.min(i16::MAX as i32) as f32 / i16::MAX as f32;
+                state.abs_max >>= 2;
+                state.count = 0;
+            }
+
+            let rms_dbfs = to_dbfs(rms);
+            let noise_floor = *state.noise_floor.get_or_insert(rms_dbfs);
+            let is_voice = rms_dbfs > (noise_floor + options.margin_db).max(options.min_dbfs)
+                && zero_crossing_rate <= options.max_zero_crossing_rate;
+
+            // The noise floor follows quiet frames quickly and loud frames
+            // slowly, and barely moves while someone is talking.
+            let rate = if is_voice {
+                0.001
+            } else if rms_dbfs < noise_floor {
+                0.2
+            } else {
+                0.01
+            };
+
+            state.noise_floor = Some(noise_floor + (rms_dbfs - noise_floor) * rate);
+
+            let mut voice_activity = state.level.voice_activity;
+            if is_voice {
+                state.voice_frames += 1;
+                if state.voice_frames >= options.attack_frames {
+                    state.hangover = options.hangover_frames;
+                    voice_activity = true;
+                }
+            } else {
+                state.voice_frames = 0;
+                if state.hangover > 0 {
+                    state.hangover -= 1;
+                } else {
+                    voice_activity = false;
+                }
+            }
+
+            state.level = AudioLevel {
+                peak_dbfs: to_dbfs(peak as f32),
+                timestamp: frame.timestamp(),
+                level: state.current,
+                voice_activity,
+                rms_dbfs,
+            };
+
+            state.level
+        };
+
+        for handler in self.inner.subscribers.read().unwrap().iter() {
+            handler(&level);
+        }
+
+        level
+    }
+}
+
+impl SinkExt for LevelMeter {
+    type Item = Arc<AudioFrame>;
+
+    fn on_data(&self, frame: Arc<AudioFrame>) {
+        self.process(frame.as_ref());
+    }
+}
@@ -8,45 +8,135 @@
 //! video, voice, and generic data to be sent between peers, allowing
 //! developers to build powerful voice- and video-communication solutions.
 
+mod audio_chunker;
+mod audio_codec;
+mod audio_device;
 mod audio_frame;
+mod audio_mixer;
+mod audio_resampler;
+mod audio_source;
 mod audio_track;
 mod auto_ptr;
+mod channel_mixer;
+#[cfg(test)]
+mod codec_test;
 mod create_description_observer;
 mod cstr;
+mod encoded_frame;
+mod field_trials;
+mod frame_stream;
+mod i420_buffer;
+mod ice_server_url;
+mod level_meter;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod logging;
+mod media_engine;
 mod media_stream;
 mod media_stream_track;
 mod observer;
+mod pacer;
 mod promisify;
+mod rtc_certificate;
 mod rtc_datachannel;
 mod rtc_icecandidate;
 mod rtc_peerconnection;
 mod rtc_peerconnection_configure;
+mod rtc_peerconnection_factory;
+mod rtc_rtp_receiver;
+mod rtc_rtp_sender;
 mod rtc_session_description;
 mod set_description_observer;
+#[cfg(feature = "sframe")]
+mod sframe;
 mod sink;
+mod test_pattern;
+mod tone_generator;
+mod trace;
+#[cfg(feature = "turn-rest")]
+mod turn_rest;
+mod video_codec;
+mod video_compositor;
 mod video_frame;
+mod video_source;
 mod video_track;
+mod wav;
+mod xorshift;
+mod y4m;
 
+pub use audio_chunker::AudioFrameChunker;
+pub use audio_codec::{
+    AudioDecoder, AudioDecoderFactory, AudioEncoder, AudioEncoderFactory, AudioFormat,
+    L16AudioCodec,
+};
+pub use audio_device::{AudioDeviceModule, AudioTransport, VirtualAudioDevice};
 pub use audio_frame::AudioFrame;
+pub use audio_mixer::{AudioMixer, AudioMixerInput, AudioMixerOptions};
+pub use audio_resampler::Resampler;
+pub use audio_source::{AudioSource, AudioSourceDriver};
 pub use audio_track::AudioTrack;
+pub use channel_mixer::{ChannelLayout, ChannelMixer};
 pub use create_description_observer::{CreateDescriptionError, CreateDescriptionObserver};
 pub use cstr::StringError;
+pub use encoded_frame::{
+    EncodedAudioFrame, EncodedFrame, EncodedFrameSink, EncodedFrameTransform, EncodedVideoFrame,
+};
+pub use field_trials::{set_field_trials, set_field_trials_from_map, FieldTrialsError};
+pub use frame_stream::{FramePolicy, FrameStream};
+pub use i420_buffer::I420Buffer;
+pub use ice_server_url::{IceServerUrl, IceTransportProtocol, IceUrlError, IceUrlScheme};
+pub use level_meter::{AudioLevel, LevelMeter, VadOptions};
+#[cfg(any(feature = "log", feature = "tracing"))]
+pub use logging::{init_native_logging, LogSeverity};
+pub use media_engine::{CodecError, MediaEngine};
 pub use media_stream::{MediaStream, MediaStreamError};
-pub use media_stream_track::{MediaStreamTrack, MediaStreamTrackKind};
+pub use media_stream_track::{MediaStreamTrack, MediaStreamTrackKind, MediaStreamTrackState};
 pub use observer::{
-    IceConnectionState, IceGatheringState, Observer, PeerConnectionState, SignalingState,
+    IceConnectionState, IceGatheringState, Observer, PeerConnectionState, RTCTrackEvent,
+    SignalingState,
 };
 pub use promisify::{Promisify, PromisifyExt, SpawnBlocking};
+pub use rtc_certificate::{
+    CertificateError, KeyType, RTCCertificate, RTCCertificatePem, RTCDtlsFingerprint,
+    RTCRemoteCertificates,
+};
 pub use rtc_datachannel::{
     DataChannel, DataChannelOptions, DataChannelPriority, DataChannelState, RTCDataChannel,
 };
 pub use rtc_icecandidate::RTCIceCandidate;
 pub use rtc_peerconnection::{RTCError, RTCPeerConnection};
 pub use rtc_peerconnection_configure::{
-    BundlePolicy, IceTransportPolicy, RTCConfiguration, RTCIceServer, RtcpMuxPolicy,
+    BundlePolicy, ContinualGatheringPolicy, IceTransportPolicy, RTCConfiguration,
+    RTCConfigurationError, RTCIceServer, RtcpMuxPolicy,
+};
+pub use rtc_peerconnection_factory::{
+    AdapterType, PeerConnectionFactory, PeerConnectionFactoryBuilder, ThreadOptions, ThreadPriority,
 };
+pub use rtc_rtp_receiver::RTCRtpReceiver;
+pub use rtc_rtp_sender::RTCRtpSender;
 pub use rtc_session_description::{RTCSessionDescription, RTCSessionDescriptionType};
 pub use set_description_observer::{SetDescriptionError, SetDescriptionObserver};
-pub use sink::{SinkExt, Sinker};
+#[cfg(feature = "sframe")]
+pub use sframe::{
+    CipherSuite, KeyProvider, MemoryKeyProvider, SFrameDecryptor, SFrameEncryptor, SFrameError,
+    SFrameOptions, SFrameStats,
+};
+pub use sink::{SinkExt, SinkHandle, Sinker};
+pub use test_pattern::{
+    TestPattern, TestPatternAnalyzer, TestPatternOptions, TestPatternSource, TestPatternStamp,
+    TestPatternStats,
+};
+pub use tone_generator::{detect_tone, Tone, ToneGenerator};
+#[cfg(feature = "turn-rest")]
+pub use turn_rest::{TurnRestOptions, TurnRestRefresher};
+pub use video_codec::{
+    EncodedImage, PassthroughVideoCodec, VideoCodecSettings, VideoDecoder, VideoDecoderFactory,
+    VideoEncoder, VideoEncoderFactory, VideoFormat,
+};
+pub use video_compositor::{
+    CompositorLayout, VideoCompositor, VideoCompositorInput, VideoCompositorOptions,
+};
 pub use video_frame::VideoFrame;
+pub use video_source::{VideoSource, VideoSourceDriver, VideoSourceWants};
 pub use video_track::VideoTrack;
+pub use wav::{WavError, WavReader, WavSpec, WavWriter};
+pub use y4m::{Y4mError, Y4mHeader, Y4mReader, Y4mWriter};
@@ -0,0 +1,89 @@
+use std::ffi::c_char;
+
+use crate::cstr::c_str_to_str;
+
+extern "C" {
+    pub(crate) fn rtc_set_log_sink(
+        sink: *const crate::logging::RawLogSink,
+        min_severity: crate::logging::LogSeverity,
+    );
+}
+
+/// The severity of a native log message, as `rtc::LoggingSeverity`.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogSeverity {
+    /// Verbose messages which may contain sensitive data.
+    Sensitive,
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+impl From<LogSeverity> for log::Level {
+    fn from(severity: LogSeverity) -> Self {
+        match severity {
+            LogSeverity::Sensitive => Self::Trace,
+            LogSeverity::Verbose => Self::Debug,
+            LogSeverity::Info => Self::Info,
+            LogSeverity::Warning => Self::Warn,
+            LogSeverity::Error => Self::Error,
+        }
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawLogSink {
+    on_message: extern "C" fn(LogSeverity, *const c_char, *const c_char),
+}
+
+static LOG_SINK: RawLogSink = RawLogSink {
+    on_message: on_native_log,
+};
+
+/// Forward the native `RTC_LOG` output at or above the severity to the
+/// `tracing` subscriber, or to the `log` logger without the `tracing`
+/// feature.
+///
+/// The records have the `webrtc` target, records of a connection with a
+/// log tag carry the tag, see `RTCPeerConnection::set_log_tag`.
+/// Calling it again changes the severity.
+pub fn init_native_logging(min_severity: LogSeverity) {
+    unsafe { rtc_set_log_sink(&LOG_SINK, min_severity) }
+}
+
+/// The tag is null for messages outside of a tagged connection.
+#[no_mangle]
+extern "C" fn on_native_log(severity: LogSeverity, tag: *const c_char, message: *const c_char) {
+    let tag = (!tag.is_null()).then(|| c_str_to_str(tag).ok()).flatten();
+    if let Ok(message) = c_str_to_str(message) {
+        forward(severity, tag, message.trim_end());
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn forward(severity: LogSeverity, tag: Option<&str>, message: &str) {
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!(target: "webrtc", $level, tag, "{}", message)
+        };
+    }
+
+    match severity {
+        LogSeverity::Sensitive => event!(tracing::Level::TRACE),
+        LogSeverity::Verbose => event!(tracing::Level::DEBUG),
+        LogSeverity::Info => event!(tracing::Level::INFO),
+        LogSeverity::Warning => event!(tracing::Level::WARN),
+        LogSeverity::Error => event!(tracing::Level::ERROR),
+    }
+}
+
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+fn forward(severity: LogSeverity, tag: Option<&str>, message: &str) {
+    match tag {
+        Some(tag) => log::log!(target: "webrtc", severity.into(), "[{}] {}", tag, message),
+        None => log::log!(target: "webrtc", severity.into(), "{}", message),
+    }
+}
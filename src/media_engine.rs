@@ -0,0 +1,251 @@
+use std::{
+    error::Error,
+    ffi::{c_char, c_int},
+    fmt,
+};
+
+use crate::{
+    audio_codec::{
+        AudioDecoderFactoryRef, AudioEncoderFactoryRef, RawAudioDecoderFactory,
+        RawAudioEncoderFactory,
+    },
+    audio_device::{AudioDeviceModuleRef, RawAudioDeviceModule},
+    cstr::{free_cstring, to_c_str, StringError},
+    video_codec::{
+        RawVideoDecoderFactory, RawVideoEncoderFactory, VideoDecoderFactoryRef,
+        VideoEncoderFactoryRef,
+    },
+    AudioDecoderFactory, AudioDeviceModule, AudioEncoderFactory, VideoDecoderFactory,
+    VideoEncoderFactory,
+};
+
+/// Errors reported by rust codecs to the native codec pipeline.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The codec settings or the input are not supported.
+    InvalidParameter,
+    /// The codec was used before it was initialized.
+    Uninitialized,
+    /// Any other failure, the message is logged by the native side.
+    Failed(String),
+}
+
+impl CodecError {
+    /// The matching webrtc codec return code.
+    pub(crate) fn code(&self) -> c_int {
+        match self {
+            Self::InvalidParameter => -4,
+            Self::Uninitialized => -7,
+            Self::Failed(_) => -1,
+        }
+    }
+}
+
+impl Error for CodecError {}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidParameter => write!(f, "invalid codec parameter"),
+            Self::Uninitialized => write!(f, "codec is not initialized"),
+            Self::Failed(message) => write!(f, "codec failed: {}", message),
+        }
+    }
+}
+
+/// The return code of a codec call.
+pub(crate) fn codec_result<T>(result: Result<T, CodecError>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(e) => e.code(),
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawCodecParameter {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+/// The fmtp parameters of a codec format as c strings, the strings are
+/// freed on drop.
+pub(crate) struct RawCodecParameters(Vec<RawCodecParameter>);
+
+impl RawCodecParameters {
+    pub(crate) fn new(parameters: &[(String, String)]) -> Result<Self, StringError> {
+        // Drop frees the parameters converted before a failure.
+        let mut raw = Self(Vec::with_capacity(parameters.len()));
+        for (key, value) in parameters {
+            let key = to_c_str(key)?;
+            let value = to_c_str(value).inspect_err(|_| free_cstring(key))?;
+            raw.0.push(RawCodecParameter { key, value });
+        }
+
+        Ok(raw)
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const RawCodecParameter {
+        self.0.as_ptr()
+    }
+
+    pub(crate) fn len(&self) -> c_int {
+        self.0.len() as c_int
+    }
+}
+
+impl Drop for RawCodecParameters {
+    fn drop(&mut self) {
+        for parameter in &self.0 {
+            free_cstring(parameter.key);
+            free_cstring(parameter.value);
+        }
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawMediaEngine {
+    // Null pointers keep the built-in factories.
+    video_encoder_factory: *const RawVideoEncoderFactory,
+    video_decoder_factory: *const RawVideoDecoderFactory,
+    audio_encoder_factory: *const RawAudioEncoderFactory,
+    audio_decoder_factory: *const RawAudioDecoderFactory,
+    audio_device_module: *const RawAudioDeviceModule,
+}
+
+/// The codecs and the audio device of a peer connection.
+///
+/// By default a peer connection uses the codecs and the audio device built
+/// into the native library, a media engine replaces them with
+/// implementations in rust.
+/// The codecs are advertised in the sdp and selected through the normal
+/// offer/answer negotiation.
+/// Formats with a nul character in their name or parameters are reported
+/// as a `StringError` when a factory is built with the engine.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn connect(
+/// #     config: RTCConfiguration,
+/// #     observer: impl Observer + 'static,
+/// # ) -> Result<(), RTCError> {
+/// let engine = MediaEngine::default()
+///     .with_video_encoder_factory(PassthroughVideoCodec)
+///     .with_video_decoder_factory(PassthroughVideoCodec)
+///     .with_audio_encoder_factory(L16AudioCodec::default())
+///     .with_audio_decoder_factory(L16AudioCodec::default());
+///
+/// let pc = RTCPeerConnection::with_media_engine(&config, engine, observer)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MediaEngine {
+    video_encoder_factory: Option<Box<VideoEncoderFactoryRef>>,
+    video_decoder_factory: Option<Box<VideoDecoderFactoryRef>>,
+    audio_encoder_factory: Option<Box<AudioEncoderFactoryRef>>,
+    audio_decoder_factory: Option<Box<AudioDecoderFactoryRef>>,
+    audio_device_module: Option<Box<AudioDeviceModuleRef>>,
+    // The first factory whose formats native can not take, reported when
+    // the engine is used.
+    error: Option<StringError>,
+}
+
+impl MediaEngine {
+    /// Encode outgoing video with the codecs of the factory.
+    pub fn with_video_encoder_factory<T: VideoEncoderFactory + 'static>(
+        mut self,
+        factory: T,
+    ) -> Self {
+        match VideoEncoderFactoryRef::new(Box::new(factory)) {
+            Ok(factory) => self.video_encoder_factory = Some(factory),
+            Err(e) => self.set_error(e),
+        }
+
+        self
+    }
+
+    /// Decode incoming video with the codecs of the factory.
+    pub fn with_video_decoder_factory<T: VideoDecoderFactory + 'static>(
+        mut self,
+        factory: T,
+    ) -> Self {
+        match VideoDecoderFactoryRef::new(Box::new(factory)) {
+            Ok(factory) => self.video_decoder_factory = Some(factory),
+            Err(e) => self.set_error(e),
+        }
+
+        self
+    }
+
+    /// Encode outgoing audio with the codecs of the factory.
+    pub fn with_audio_encoder_factory<T: AudioEncoderFactory + 'static>(
+        mut self,
+        factory: T,
+    ) -> Self {
+        match AudioEncoderFactoryRef::new(Box::new(factory)) {
+            Ok(factory) => self.audio_encoder_factory = Some(factory),
+            Err(e) => self.set_error(e),
+        }
+
+        self
+    }
+
+    /// Decode incoming audio with the codecs of the factory.
+    pub fn with_audio_decoder_factory<T: AudioDecoderFactory + 'static>(
+        mut self,
+        factory: T,
+    ) -> Self {
+        match AudioDecoderFactoryRef::new(Box::new(factory)) {
+            Ok(factory) => self.audio_decoder_factory = Some(factory),
+            Err(e) => self.set_error(e),
+        }
+
+        self
+    }
+
+    /// Record and play audio through the device instead of the sound card.
+    pub fn with_audio_device_module<T: AudioDeviceModule + 'static>(mut self, device: T) -> Self {
+        self.audio_device_module = Some(AudioDeviceModuleRef::new(Box::new(device)));
+        self
+    }
+
+    /// The native view of the engine, only valid as long as the engine.
+    /// Fails if the formats of a factory contain a nul character.
+    pub(crate) fn get_raw(&self) -> Result<RawMediaEngine, StringError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        Ok(RawMediaEngine {
+            video_encoder_factory: self
+                .video_encoder_factory
+                .as_ref()
+                .map(|factory| factory.get_raw())
+                .unwrap_or(std::ptr::null()),
+            video_decoder_factory: self
+                .video_decoder_factory
+                .as_ref()
+                .map(|factory| factory.get_raw())
+                .unwrap_or(std::ptr::null()),
+            audio_encoder_factory: self
+                .audio_encoder_factory
+                .as_ref()
+                .map(|factory| factory.get_raw())
+                .unwrap_or(std::ptr::null()),
+            audio_decoder_factory: self
+                .audio_decoder_factory
+                .as_ref()
+                .map(|factory| factory.get_raw())
+                .unwrap_or(std::ptr::null()),
+            audio_device_module: self
+                .audio_device_module
+                .as_ref()
+                .map(|device| device.get_raw())
+                .unwrap_or(std::ptr::null()),
+        })
+    }
+
+    fn set_error(&mut self, e: StringError) {
+        let _ = self.error.get_or_insert(e);
+    }
+}
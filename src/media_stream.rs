@@ -1,8 +1,13 @@
-use std::{error::Error, ffi::c_char, fmt, sync::Arc};
+use std::{
+    error::Error,
+    ffi::c_char,
+    fmt,
+    sync::{Arc, RwLock},
+};
 
 use crate::{
     cstr::{free_cstring, to_c_str, StringError},
-    MediaStreamTrack,
+    AudioTrack, MediaStreamTrack, VideoTrack,
 };
 
 #[derive(Debug)]
@@ -19,14 +24,18 @@ impl fmt::Display for MediaStreamError {
     }
 }
 
+type TrackHandler = Box<dyn Fn(&MediaStreamTrack) + Send + Sync>;
+
 /// The MediaStream interface represents a stream of media content.
 ///
 /// A stream consists of several tracks, such as video or audio tracks. Each
 /// track is specified as an instance of MediaStreamTrack.
 pub struct MediaStream {
     pub id: String,
-    pub tracks: Vec<MediaStreamTrack>,
     pub(crate) raw_id: *const c_char,
+    tracks: RwLock<Vec<MediaStreamTrack>>,
+    on_add_track: RwLock<Option<TrackHandler>>,
+    on_remove_track: RwLock<Option<TrackHandler>>,
 }
 
 unsafe impl Send for MediaStream {}
@@ -38,12 +47,122 @@ impl MediaStream {
     /// that contains a specified list of tracks.
     pub fn new(id: &str) -> Result<Arc<Self>, MediaStreamError> {
         Ok(Arc::new(Self {
-            tracks: Vec::with_capacity(10),
             raw_id: to_c_str(id).map_err(|e| MediaStreamError::StringError(e))?,
+            tracks: RwLock::new(Vec::with_capacity(10)),
+            on_add_track: RwLock::new(None),
+            on_remove_track: RwLock::new(None),
             id: id.to_string(),
         }))
     }
 
+    /// Adds a track to the stream, a track that is already in the stream is
+    /// not added again.
+    ///
+    /// Like in the browser, this does not fire the add track event, which
+    /// is reserved for tracks added by the remote side.
+    pub fn add_track(&self, track: MediaStreamTrack) -> bool {
+        let mut tracks = self.tracks.write().unwrap();
+        if tracks.iter().any(|item| item.id() == track.id()) {
+            return false;
+        }
+
+        tracks.push(track);
+        true
+    }
+
+    /// Removes a track from the stream, returns the removed track if it was
+    /// in the stream.
+    ///
+    /// Like in the browser, this does not fire the remove track event.
+    pub fn remove_track(&self, id: &str) -> Option<MediaStreamTrack> {
+        let mut tracks = self.tracks.write().unwrap();
+        let index = tracks.iter().position(|item| item.id() == id)?;
+        Some(tracks.remove(index))
+    }
+
+    /// Returns all tracks of the stream, regardless of their kind.
+    pub fn get_tracks(&self) -> Vec<MediaStreamTrack> {
+        self.tracks.read().unwrap().clone()
+    }
+
+    /// Returns the audio tracks of the stream.
+    pub fn get_audio_tracks(&self) -> Vec<Arc<AudioTrack>> {
+        self.tracks
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|track| match track {
+                MediaStreamTrack::Audio(track) => Some(track.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the video tracks of the stream.
+    pub fn get_video_tracks(&self) -> Vec<Arc<VideoTrack>> {
+        self.tracks
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|track| match track {
+                MediaStreamTrack::Video(track) => Some(track.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the track with the given id, if it is in the stream.
+    pub fn get_track_by_id(&self, id: &str) -> Option<MediaStreamTrack> {
+        self.tracks
+            .read()
+            .unwrap()
+            .iter()
+            .find(|track| track.id() == id)
+            .cloned()
+    }
+
+    /// Called when the remote side adds a track to the stream. Replaces the
+    /// previous handler.
+    pub fn on_add_track<F>(&self, handler: F)
+    where
+        F: Fn(&MediaStreamTrack) + Send + Sync + 'static,
+    {
+        let _ = self.on_add_track.write().unwrap().insert(Box::new(handler));
+    }
+
+    /// Called when the remote side removes a track from the stream.
+    /// Replaces the previous handler.
+    pub fn on_remove_track<F>(&self, handler: F)
+    where
+        F: Fn(&MediaStreamTrack) + Send + Sync + 'static,
+    {
+        let _ = self
+            .on_remove_track
+            .write()
+            .unwrap()
+            .insert(Box::new(handler));
+    }
+
+    /// Add a track on behalf of the remote side and fire the add track
+    /// event.
+    pub(crate) fn add_remote_track(&self, track: MediaStreamTrack) {
+        if self.add_track(track.clone()) {
+            if let Some(handler) = self.on_add_track.read().unwrap().as_ref() {
+                handler(&track);
+            }
+        }
+    }
+
+    /// Remove a track on behalf of the remote side and fire the remove track
+    /// event.
+    pub(crate) fn remove_remote_track(&self, id: &str) {
+        if let Some(track) = self.remove_track(id) {
+            if let Some(handler) = self.on_remove_track.read().unwrap().as_ref() {
+                handler(&track);
+            }
+        }
+    }
+
     /// A string containing a 36-character universally unique identifier (UUID)
     /// for the object.
     pub(crate) fn get_id(&self) -> *const c_char {
@@ -1,10 +1,18 @@
 use std::{
     ffi::{c_char, c_void},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 
-use crate::{media_stream::MediaStreamError, AudioTrack, VideoTrack};
+use crate::{
+    cstr::{take_native_c_str, StringError},
+    media_stream::MediaStreamError,
+    AudioTrack, VideoTrack,
+};
 
+#[allow(improper_ctypes)]
 extern "C" {
     pub(crate) fn rtc_free_frame(frame: *const c_void);
     pub(crate) fn rtc_remove_media_stream_track_frame_h(
@@ -14,6 +22,40 @@ extern "C" {
     pub(crate) fn rtc_free_media_stream_track(
         track: *const crate::media_stream_track::RawMediaStreamTrack,
     );
+
+    pub(crate) fn rtc_get_media_stream_track_id(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    ) -> *const c_char;
+
+    pub(crate) fn rtc_get_media_stream_track_enabled(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    ) -> bool;
+
+    pub(crate) fn rtc_set_media_stream_track_enabled(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+        enabled: bool,
+    );
+
+    pub(crate) fn rtc_get_media_stream_track_state(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    ) -> crate::media_stream_track::MediaStreamTrackState;
+
+    pub(crate) fn rtc_stop_media_stream_track(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    );
+
+    pub(crate) fn rtc_set_media_stream_track_event_h(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+        handler: extern "C" fn(
+            &crate::media_stream_track::TrackEvents,
+            crate::media_stream_track::MediaStreamTrackEvent,
+        ),
+        ctx: &crate::media_stream_track::TrackEvents,
+    );
+
+    pub(crate) fn rtc_remove_media_stream_track_event_h(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    );
 }
 
 #[repr(i32)]
@@ -23,6 +65,111 @@ pub enum MediaStreamTrackKind {
     Audio,
 }
 
+/// The state of a track, a track never goes back to live once it has
+/// ended.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaStreamTrackState {
+    /// The track is active, the source is connected and producing media.
+    Live,
+    /// The source is not providing any more data, and will never provide
+    /// more data, either because the track was stopped or because the
+    /// remote side stopped sending.
+    Ended,
+}
+
+/// Notifications from the native track, only constructed by native.
+#[allow(dead_code)]
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MediaStreamTrackEvent {
+    Mute,
+    Unmute,
+    Ended,
+}
+
+type TrackEventHandler = Box<dyn Fn() + Send + Sync>;
+
+/// Track event handlers, shared by the audio and video tracks.
+#[derive(Default)]
+pub(crate) struct TrackEvents {
+    muted: AtomicBool,
+    on_mute: RwLock<Option<TrackEventHandler>>,
+    on_unmute: RwLock<Option<TrackEventHandler>>,
+    on_ended: RwLock<Option<TrackEventHandler>>,
+}
+
+impl TrackEvents {
+    /// Start receiving native track events, the events object has to
+    /// outlive the registration.
+    pub(crate) fn register(&self, raw: *const RawMediaStreamTrack) {
+        unsafe { rtc_set_media_stream_track_event_h(raw, on_track_event, self) }
+    }
+
+    pub(crate) fn unregister(&self, raw: *const RawMediaStreamTrack) {
+        unsafe { rtc_remove_media_stream_track_event_h(raw) }
+    }
+
+    pub(crate) fn muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_on_mute(&self, handler: TrackEventHandler) {
+        let _ = self.on_mute.write().unwrap().insert(handler);
+    }
+
+    pub(crate) fn set_on_unmute(&self, handler: TrackEventHandler) {
+        let _ = self.on_unmute.write().unwrap().insert(handler);
+    }
+
+    pub(crate) fn set_on_ended(&self, handler: TrackEventHandler) {
+        let _ = self.on_ended.write().unwrap().insert(handler);
+    }
+}
+
+#[no_mangle]
+extern "C" fn on_track_event(ctx: &TrackEvents, event: MediaStreamTrackEvent) {
+    let handler = match event {
+        MediaStreamTrackEvent::Mute => {
+            ctx.muted.store(true, Ordering::Relaxed);
+            &ctx.on_mute
+        }
+        MediaStreamTrackEvent::Unmute => {
+            ctx.muted.store(false, Ordering::Relaxed);
+            &ctx.on_unmute
+        }
+        MediaStreamTrackEvent::Ended => &ctx.on_ended,
+    };
+
+    if let Some(handler) = handler.read().unwrap().as_ref() {
+        handler();
+    }
+}
+
+/// Native track getters, shared by the audio and video tracks.
+///
+/// The id is a copy owned by the caller, it is read into a string and the
+/// native copy is released.
+pub(crate) fn get_track_id(raw: *const RawMediaStreamTrack) -> Result<String, StringError> {
+    take_native_c_str(unsafe { rtc_get_media_stream_track_id(raw) })
+}
+
+pub(crate) fn get_track_enabled(raw: *const RawMediaStreamTrack) -> bool {
+    unsafe { rtc_get_media_stream_track_enabled(raw) }
+}
+
+pub(crate) fn set_track_enabled(raw: *const RawMediaStreamTrack, enabled: bool) {
+    unsafe { rtc_set_media_stream_track_enabled(raw, enabled) }
+}
+
+pub(crate) fn get_track_state(raw: *const RawMediaStreamTrack) -> MediaStreamTrackState {
+    unsafe { rtc_get_media_stream_track_state(raw) }
+}
+
+pub(crate) fn stop_track(raw: *const RawMediaStreamTrack) {
+    unsafe { rtc_stop_media_stream_track(raw) }
+}
+
 #[repr(C)]
 pub(crate) struct RawMediaStreamTrack {
     /// Returns a string set to "audio" if the track is an audio track and to
@@ -65,14 +212,81 @@ impl MediaStreamTrack {
         Ok(Self::Audio(AudioTrack::new(label)?))
     }
 
+    /// Returns a string containing a unique identifier (GUID) for the
+    /// track.
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Audio(track) => track.id(),
+            Self::Video(track) => track.id(),
+        }
+    }
+
+    /// Returns whether the track is an audio or a video track.
+    pub fn kind(&self) -> MediaStreamTrackKind {
+        match self {
+            Self::Audio(_) => MediaStreamTrackKind::Audio,
+            Self::Video(_) => MediaStreamTrackKind::Video,
+        }
+    }
+
+    /// Returns the label of the track source.
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Audio(track) => track.label(),
+            Self::Video(track) => track.label(),
+        }
+    }
+
+    /// Returns whether the track is allowed to render the source stream.
+    pub fn enabled(&self) -> bool {
+        match self {
+            Self::Audio(track) => track.enabled(),
+            Self::Video(track) => track.enabled(),
+        }
+    }
+
+    /// A disabled track produces silence or black frames instead of the
+    /// source media.
+    pub fn set_enabled(&self, enabled: bool) {
+        match self {
+            Self::Audio(track) => track.set_enabled(enabled),
+            Self::Video(track) => track.set_enabled(enabled),
+        }
+    }
+
+    /// Returns whether the track is currently unable to provide media data
+    /// due to a technical issue, such as the remote side not sending.
+    pub fn muted(&self) -> bool {
+        match self {
+            Self::Audio(track) => track.muted(),
+            Self::Video(track) => track.muted(),
+        }
+    }
+
+    /// Returns the status of the track.
+    pub fn ready_state(&self) -> MediaStreamTrackState {
+        match self {
+            Self::Audio(track) => track.ready_state(),
+            Self::Video(track) => track.ready_state(),
+        }
+    }
+
+    /// Stops the track, the source is released and the track is ended.
+    pub fn stop(&self) {
+        match self {
+            Self::Audio(track) => track.stop(),
+            Self::Video(track) => track.stop(),
+        }
+    }
+
     /// Created through the original media stream track, video and audio
     /// are processed separately.
-    pub(crate) fn from_raw(raw: *const RawMediaStreamTrack) -> Self {
+    pub(crate) fn from_raw(raw: *const RawMediaStreamTrack) -> Result<Self, StringError> {
         assert!(!raw.is_null());
-        match unsafe { (*raw).kind } {
-            MediaStreamTrackKind::Audio => Self::Audio(AudioTrack::from_raw(raw)),
-            MediaStreamTrackKind::Video => Self::Video(VideoTrack::from_raw(raw)),
-        }
+        Ok(match unsafe { (*raw).kind } {
+            MediaStreamTrackKind::Audio => Self::Audio(AudioTrack::from_raw(raw)?),
+            MediaStreamTrackKind::Video => Self::Video(VideoTrack::from_raw(raw)?),
+        })
     }
 
     /// get raw media stream track ptr.
@@ -1,9 +1,19 @@
-use std::fmt::Debug;
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int},
+    fmt::Debug,
+    slice::from_raw_parts,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    media_stream_track::RawMediaStreamTrack, rtc_datachannel::RawRTCDataChannel,
-    rtc_icecandidate::RawRTCIceCandidate, DataChannel, MediaStreamTrack, RTCDataChannel,
-    RTCIceCandidate,
+    cstr::c_str_to_str,
+    media_stream_track::RawMediaStreamTrack,
+    rtc_datachannel::RawRTCDataChannel,
+    rtc_icecandidate::RawRTCIceCandidate,
+    rtc_rtp_receiver::{rtc_free_rtp_receiver, RawRTCRtpReceiver},
+    trace::{rtc_event, Span},
+    DataChannel, MediaStream, MediaStreamTrack, RTCDataChannel, RTCIceCandidate, RTCRtpReceiver,
 };
 
 /// This state essentially represents the aggregate state of all ICE
@@ -129,6 +139,28 @@ pub enum IceConnectionState {
     Max,
 }
 
+/// The track event is sent when a new incoming track has been negotiated
+/// for a specific receiver.
+pub struct RTCTrackEvent {
+    /// The track which has been added to the connection.
+    pub track: MediaStreamTrack,
+    /// The streams the track belongs to, as announced by the remote side in
+    /// the msid of the media section. The same remote stream is always
+    /// reported as the same object, so the audio and video of one
+    /// participant can be grouped by the stream.
+    pub streams: Vec<Arc<MediaStream>>,
+    /// The receiver of the track, gives access to the encoded frames.
+    pub receiver: Arc<RTCRtpReceiver>,
+}
+
+#[repr(C)]
+pub(crate) struct RawRTCTrackEvent {
+    track: *const RawMediaStreamTrack,
+    stream_ids: *const *const c_char,
+    stream_ids_size: c_int,
+    receiver: *const RawRTCRtpReceiver,
+}
+
 /// PeerConnection callback interface, used for RTCPeerConnection events.
 /// Application should implement these methods.
 #[allow(unused)]
@@ -173,7 +205,7 @@ pub trait Observer {
     /// The track event is sent to the ontrack event handler on
     /// RTCPeerConnections after a new track has been added to an
     /// RTCRtpReceiver which is part of the connection.
-    fn on_track(&self, track: MediaStreamTrack) {}
+    fn on_track(&self, event: RTCTrackEvent) {}
     /// A datachannel event is sent to an RTCPeerConnection instance when an
     /// RTCDataChannel has been added to the connection, as a result of the
     /// remote peer calling RTCPeerConnection.createDataChannel().
@@ -183,13 +215,56 @@ pub trait Observer {
 /// wrapper observer trait impl.
 pub struct ObserverRef {
     data: Box<dyn Observer>,
+    // Remote streams by id, so that all tracks of a remote stream end up in
+    // the same stream object. A stream is kept until the remote side removes
+    // its last track.
+    streams: Mutex<HashMap<String, Arc<MediaStream>>>,
+    // The span of the connection.
+    span: Span,
 }
 
 impl ObserverRef {
-    pub fn new<T: Observer + 'static>(data: T) -> Self {
+    pub fn new<T: Observer + 'static>(data: T, span: Span) -> Self {
         Self {
+            streams: Mutex::new(HashMap::new()),
             data: Box::new(data),
+            span,
+        }
+    }
+
+    /// Get the remote stream with the given id, or create it if this is the
+    /// first track of the stream.
+    fn get_or_create_stream(&self, id: &str) -> Option<Arc<MediaStream>> {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(stream) = streams.get(id) {
+            return Some(stream.clone());
+        }
+
+        let stream = MediaStream::new(id).ok()?;
+        streams.insert(id.to_string(), stream.clone());
+        Some(stream)
+    }
+
+    /// Remove a remote track from its streams, and forget the streams that
+    /// have no track left.
+    fn remove_remote_track(&self, track_id: &str) {
+        let streams = self
+            .streams
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<Arc<MediaStream>>>();
+
+        // The stream handlers run without holding the lock.
+        for stream in &streams {
+            stream.remove_remote_track(track_id);
         }
+
+        self.streams
+            .lock()
+            .unwrap()
+            .retain(|_, stream| !stream.get_tracks().is_empty());
     }
 }
 
@@ -203,8 +278,9 @@ pub(crate) struct TEvents {
     on_ice_candidate: extern "C" fn(*mut ObserverRef, *const RawRTCIceCandidate),
     on_renegotiation_needed: extern "C" fn(*mut ObserverRef),
     on_ice_connection_change: extern "C" fn(*mut ObserverRef, IceConnectionState),
-    on_track: extern "C" fn(*mut ObserverRef, *const RawMediaStreamTrack),
+    on_track: extern "C" fn(*mut ObserverRef, *const RawRTCTrackEvent),
     on_connection_change: extern "C" fn(*mut ObserverRef, PeerConnectionState),
+    on_remove_track: extern "C" fn(*mut ObserverRef, *const c_char),
 }
 
 /// events callback const ref.
@@ -217,48 +293,119 @@ pub(crate) const EVENTS: TEvents = TEvents {
     on_ice_connection_change,
     on_track,
     on_connection_change,
+    on_remove_track,
 };
 
 extern "C" fn on_signaling_change(ctx: *mut ObserverRef, state: SignalingState) {
     assert!(!ctx.is_null());
-    (unsafe { &mut *ctx }).data.on_signaling_change(state);
+    let ctx = unsafe { &mut *ctx };
+    rtc_event!(DEBUG, parent: &ctx.span, ?state, "signaling state changed");
+    ctx.data.on_signaling_change(state);
 }
 
 extern "C" fn on_connection_change(ctx: *mut ObserverRef, state: PeerConnectionState) {
     assert!(!ctx.is_null());
-    (unsafe { &mut *ctx }).data.on_connection_change(state);
+    let ctx = unsafe { &mut *ctx };
+    rtc_event!(INFO, parent: &ctx.span, ?state, "connection state changed");
+    ctx.data.on_connection_change(state);
 }
 
 extern "C" fn on_ice_gathering_change(ctx: *mut ObserverRef, state: IceGatheringState) {
     assert!(!ctx.is_null());
-    (unsafe { &mut *ctx }).data.on_ice_gathering_change(state);
+    let ctx = unsafe { &mut *ctx };
+    rtc_event!(DEBUG, parent: &ctx.span, ?state, "ice gathering state changed");
+    ctx.data.on_ice_gathering_change(state);
 }
 
 extern "C" fn on_ice_candidate(ctx: *mut ObserverRef, candidate: *const RawRTCIceCandidate) {
     assert!(!ctx.is_null());
     assert!(!candidate.is_null());
+    let ctx = unsafe { &mut *ctx };
     let candidate = RTCIceCandidate::try_from(unsafe { &*candidate }).unwrap();
-    (unsafe { &mut *ctx }).data.on_ice_candidate(candidate);
+    rtc_event!(
+        DEBUG,
+        parent: &ctx.span,
+        candidate = %candidate.candidate,
+        sdp_mid = %candidate.sdp_mid,
+        "local ice candidate gathered"
+    );
+
+    ctx.data.on_ice_candidate(candidate);
 }
 
 extern "C" fn on_renegotiation_needed(ctx: *mut ObserverRef) {
     assert!(!ctx.is_null());
-    (unsafe { &mut *ctx }).data.on_renegotiation_needed();
+    let ctx = unsafe { &mut *ctx };
+    rtc_event!(DEBUG, parent: &ctx.span, "renegotiation needed");
+    ctx.data.on_renegotiation_needed();
 }
 
 extern "C" fn on_ice_connection_change(ctx: *mut ObserverRef, state: IceConnectionState) {
     assert!(!ctx.is_null());
-    (unsafe { &mut *ctx }).data.on_ice_connection_change(state);
+    let ctx = unsafe { &mut *ctx };
+    rtc_event!(DEBUG, parent: &ctx.span, ?state, "ice connection state changed");
+    ctx.data.on_ice_connection_change(state);
 }
 
 extern "C" fn on_datachannel(ctx: *mut ObserverRef, channel: *const RawRTCDataChannel) {
     assert!(!ctx.is_null() && !channel.is_null());
-    let channel = DataChannel::from_raw(channel);
-    (unsafe { &mut *ctx }).data.on_data_channel(channel);
+    let ctx = unsafe { &mut *ctx };
+    let channel = DataChannel::from_raw(channel, &ctx.span);
+    rtc_event!(DEBUG, parent: &ctx.span, label = channel.label(), "data channel received");
+    ctx.data.on_data_channel(channel);
 }
 
-extern "C" fn on_track(ctx: *mut ObserverRef, track: *const RawMediaStreamTrack) {
-    assert!(!ctx.is_null() && !track.is_null());
-    let track = MediaStreamTrack::from_raw(track);
-    (unsafe { &mut *ctx }).data.on_track(track);
+extern "C" fn on_track(ctx: *mut ObserverRef, event: *const RawRTCTrackEvent) {
+    assert!(!ctx.is_null() && !event.is_null());
+    let ctx = unsafe { &mut *ctx };
+    let event = unsafe { &*event };
+    let track = match MediaStreamTrack::from_raw(event.track) {
+        Ok(track) => track,
+        Err(_) => {
+            rtc_event!(WARN, parent: &ctx.span, "remote track without an id");
+            unsafe { rtc_free_rtp_receiver(event.receiver) }
+            return;
+        }
+    };
+
+    let stream_ids = if event.stream_ids.is_null() {
+        &[]
+    } else {
+        unsafe { from_raw_parts(event.stream_ids, event.stream_ids_size as usize) }
+    };
+
+    let streams = stream_ids
+        .iter()
+        .filter_map(|id| c_str_to_str(*id).ok())
+        .filter_map(|id| ctx.get_or_create_stream(id))
+        .collect::<Vec<Arc<MediaStream>>>();
+
+    for stream in &streams {
+        stream.add_remote_track(track.clone());
+    }
+
+    rtc_event!(
+        DEBUG,
+        parent: &ctx.span,
+        streams = streams.len(),
+        "remote track"
+    );
+
+    let receiver = Arc::new(RTCRtpReceiver::from_raw(event.receiver, track.clone()));
+    ctx.data.on_track(RTCTrackEvent {
+        track,
+        streams,
+        receiver,
+    });
+}
+
+extern "C" fn on_remove_track(ctx: *mut ObserverRef, track_id: *const c_char) {
+    assert!(!ctx.is_null() && !track_id.is_null());
+    let ctx = unsafe { &mut *ctx };
+    let Ok(track_id) = c_str_to_str(track_id) else {
+        return;
+    };
+
+    rtc_event!(DEBUG, parent: &ctx.span, track_id, "remote track removed");
+    ctx.remove_remote_track(track_id);
 }
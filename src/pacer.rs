@@ -0,0 +1,41 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// If the producer falls behind the clock by more than this many intervals,
+/// the pacer gives up catching up and starts counting again from the
+/// current time, otherwise a burst of frames would be pushed at once.
+const MAX_LAG_INTERVALS: u32 = 10;
+
+/// Keeps a producer in step with the wall clock.
+pub(crate) struct Pacer {
+    interval: Duration,
+    start: Option<Instant>,
+    ticks: u32,
+}
+
+impl Pacer {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            start: None,
+            ticks: 0,
+            interval,
+        }
+    }
+
+    /// Block the current thread until the next tick is due.
+    pub(crate) fn wait(&mut self) {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        let due = start + self.interval * self.ticks;
+        if due > now {
+            thread::sleep(due - now);
+        } else if now - due > self.interval * MAX_LAG_INTERVALS {
+            self.start = Some(now);
+            self.ticks = 0;
+        }
+
+        self.ticks += 1;
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    error::Error,
+    ffi::{c_char, c_int, c_void},
+    fmt,
+    slice::from_raw_parts,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cstr::{c_str_to_str, free_cstring, from_c_str, to_c_str, StringError};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_generate_certificate(
+        key: *const crate::rtc_certificate::RawKeyParams,
+        expires_ms: u64,
+    ) -> *const crate::rtc_certificate::RawRTCCertificate;
+
+    pub(crate) fn rtc_certificate_from_pem(
+        private_key: *const c_char,
+        certificate: *const c_char,
+    ) -> *const crate::rtc_certificate::RawRTCCertificate;
+
+    pub(crate) fn rtc_free_certificate(cert: *const crate::rtc_certificate::RawRTCCertificate);
+
+    pub(crate) fn rtc_get_remote_certificates(
+        peer: *const crate::rtc_peerconnection::RawRTCPeerConnection,
+    ) -> *const crate::rtc_certificate::RawRemoteCertificates;
+
+    pub(crate) fn rtc_free_remote_certificates(
+        certs: *const crate::rtc_certificate::RawRemoteCertificates,
+    );
+}
+
+#[derive(Debug)]
+pub enum CertificateError {
+    /// Rsa keys have to be 1024 to 8192 bits.
+    InvalidKeySize(u32),
+    GenerateFailed,
+    /// The private key or the certificate is not valid pem.
+    InvalidPem,
+    StringError(StringError),
+}
+
+impl Error for CertificateError {}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKeySize(bits) => write!(f, "invalid rsa key size: {}", bits),
+            Self::GenerateFailed => write!(f, "failed to generate certificate"),
+            Self::InvalidPem => write!(f, "invalid pem"),
+            Self::StringError(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+/// The key of a generated certificate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    /// An ecdsa key on the p-256 curve, the default of browsers.
+    EcdsaP256,
+    /// A rsa key with the given modulus size in bits.
+    Rsa(u32),
+}
+
+#[repr(C)]
+pub(crate) struct RawKeyParams {
+    kind: c_int, // 0 ecdsa p-256, 1 rsa
+    rsa_bits: c_int,
+}
+
+#[repr(C)]
+pub(crate) struct RawRTCCertificate {
+    certificate: *const c_void,
+    private_key_pem: *const c_char,
+    certificate_pem: *const c_char,
+    fingerprint_algorithm: *const c_char,
+    fingerprint: *const c_char,
+    expires_ms: u64,
+}
+
+#[repr(C)]
+pub(crate) struct RawDerCertificate {
+    data: *const u8,
+    size: usize,
+}
+
+#[repr(C)]
+pub(crate) struct RawRemoteCertificates {
+    // Leaf first.
+    certificates: *const RawDerCertificate,
+    certificates_size: c_int,
+    fingerprint_algorithm: *const c_char,
+    fingerprint: *const c_char,
+}
+
+/// A certificate fingerprint, as in the `a=fingerprint` sdp attribute.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RTCDtlsFingerprint {
+    /// The hash function, as in `sha-256`.
+    pub algorithm: String,
+    /// The hash as upper case hex bytes separated by colons.
+    pub value: String,
+}
+
+impl RTCDtlsFingerprint {
+    fn from_raw(algorithm: *const c_char, value: *const c_char) -> Option<Self> {
+        if algorithm.is_null() || value.is_null() {
+            return None;
+        }
+
+        Some(Self {
+            algorithm: c_str_to_str(algorithm).ok()?.to_string(),
+            value: c_str_to_str(value).ok()?.to_string(),
+        })
+    }
+}
+
+/// A certificate and its private key in pem, to persist a certificate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RTCCertificatePem {
+    pub private_key: String,
+    pub certificate: String,
+}
+
+struct CertificateInner {
+    raw: *const RawRTCCertificate,
+}
+
+unsafe impl Send for CertificateInner {}
+unsafe impl Sync for CertificateInner {}
+
+impl Drop for CertificateInner {
+    fn drop(&mut self) {
+        unsafe { rtc_free_certificate(self.raw) }
+    }
+}
+
+/// The certificate a connection authenticates its DTLS transports with.
+///
+/// Connections generate a certificate each, setting a certificate in the
+/// configuration keeps the fingerprint of the connections the same, so it
+/// can be pinned by the remote side.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use librtc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let cert = RTCCertificate::generate(KeyType::EcdsaP256, Duration::from_secs(86400 * 30))?;
+/// std::fs::write("cert.json", serde_json::to_string(&cert.to_pem()?)?)?;
+///
+/// let config = RTCConfiguration {
+///     certificates: Some(vec![cert]),
+///     ..Default::default()
+/// };
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RTCCertificate {
+    inner: Arc<CertificateInner>,
+}
+
+impl RTCCertificate {
+    /// Generate a self-signed certificate, valid for `expires` from now.
+    pub fn generate(key_type: KeyType, expires: Duration) -> Result<Self, CertificateError> {
+        let key = match key_type {
+            KeyType::EcdsaP256 => RawKeyParams {
+                kind: 0,
+                rsa_bits: 0,
+            },
+            KeyType::Rsa(bits) if (1024..=8192).contains(&bits) => RawKeyParams {
+                kind: 1,
+                rsa_bits: bits as c_int,
+            },
+            KeyType::Rsa(bits) => return Err(CertificateError::InvalidKeySize(bits)),
+        };
+
+        let raw = unsafe { rtc_generate_certificate(&key, expires.as_millis() as u64) };
+        Self::from_raw(raw).ok_or(CertificateError::GenerateFailed)
+    }
+
+    /// Load a certificate stored with `to_pem`.
+    pub fn from_pem(pem: &RTCCertificatePem) -> Result<Self, CertificateError> {
+        let private_key = to_c_str(&pem.private_key).map_err(CertificateError::StringError)?;
+        let certificate = match to_c_str(&pem.certificate) {
+            Ok(certificate) => certificate,
+            Err(e) => {
+                free_cstring(private_key);
+                return Err(CertificateError::StringError(e));
+            }
+        };
+
+        let raw = unsafe { rtc_certificate_from_pem(private_key, certificate) };
+        free_cstring(private_key);
+        free_cstring(certificate);
+        Self::from_raw(raw).ok_or(CertificateError::InvalidPem)
+    }
+
+    /// Export the certificate and its private key, to load it again with
+    /// `from_pem`.
+    pub fn to_pem(&self) -> Result<RTCCertificatePem, CertificateError> {
+        let raw = self.raw();
+        let pem = |str: *const c_char| {
+            if str.is_null() {
+                return Err(CertificateError::StringError(StringError::NullPointer));
+            }
+
+            from_c_str(str).map_err(CertificateError::StringError)
+        };
+
+        Ok(RTCCertificatePem {
+            private_key: pem(raw.private_key_pem)?,
+            certificate: pem(raw.certificate_pem)?,
+        })
+    }
+
+    /// The fingerprints the certificate is announced with in the sdp.
+    pub fn get_fingerprints(&self) -> Vec<RTCDtlsFingerprint> {
+        let raw = self.raw();
+        RTCDtlsFingerprint::from_raw(raw.fingerprint_algorithm, raw.fingerprint)
+            .into_iter()
+            .collect()
+    }
+
+    /// The time after which the certificate is no longer valid.
+    pub fn expires(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.raw().expires_ms)
+    }
+
+    /// The native certificate, shared with native by reference counting.
+    pub(crate) fn get_raw(&self) -> *const c_void {
+        self.raw().certificate
+    }
+
+    fn raw(&self) -> &RawRTCCertificate {
+        unsafe { &*self.inner.raw }
+    }
+
+    fn from_raw(raw: *const RawRTCCertificate) -> Option<Self> {
+        (!raw.is_null()).then(|| Self {
+            inner: Arc::new(CertificateInner { raw }),
+        })
+    }
+}
+
+impl fmt::Debug for RTCCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RTCCertificate")
+            .field("fingerprints", &self.get_fingerprints())
+            .field("expires", &self.expires())
+            .finish()
+    }
+}
+
+/// The certificates the remote side authenticated the DTLS transport with.
+#[derive(Clone, Debug)]
+pub struct RTCRemoteCertificates {
+    /// The fingerprint of the leaf certificate, to compare with the
+    /// fingerprint announced in the remote description or by signaling.
+    pub fingerprint: RTCDtlsFingerprint,
+    /// The certificate chain in der, leaf first.
+    pub chain: Vec<Vec<u8>>,
+}
+
+impl RTCRemoteCertificates {
+    /// Copy the remote certificates of the connection, none until the
+    /// DTLS handshake has completed.
+    pub(crate) fn get(
+        peer: *const crate::rtc_peerconnection::RawRTCPeerConnection,
+    ) -> Option<Self> {
+        let raw = unsafe { rtc_get_remote_certificates(peer) };
+        if raw.is_null() {
+            return None;
+        }
+
+        let certs = unsafe { &*raw };
+        let chain = if certs.certificates.is_null() {
+            &[]
+        } else {
+            unsafe { from_raw_parts(certs.certificates, certs.certificates_size as usize) }
+        };
+
+        let this = RTCDtlsFingerprint::from_raw(certs.fingerprint_algorithm, certs.fingerprint)
+            .map(|fingerprint| Self {
+                chain: chain
+                    .iter()
+                    .map(|der| unsafe { from_raw_parts(der.data, der.size) }.to_vec())
+                    .collect(),
+                fingerprint,
+            });
+
+        unsafe { rtc_free_remote_certificates(raw) }
+        this
+    }
+}
@@ -1,13 +1,14 @@
 use std::{
-    collections::HashMap,
     ffi::{c_char, c_int, c_void},
     slice::from_raw_parts,
-    sync::{Arc, RwLock},
+    sync::Arc,
 };
 
 use crate::{
-    cstr::{free_cstring, to_c_str},
-    Sinker,
+    cstr::{c_str_to_str, free_cstring, to_c_str},
+    sink::{SinkKey, SinkRegistry},
+    trace::{rtc_event, rtc_span, Span},
+    SinkHandle, Sinker,
 };
 
 #[allow(improper_ctypes)]
@@ -32,6 +33,18 @@ extern "C" {
         channel: *const crate::rtc_datachannel::RawRTCDataChannel,
     );
 
+    #[cfg(feature = "tracing")]
+    pub(crate) fn rtc_set_data_channel_state_h(
+        channel: *const crate::rtc_datachannel::RawRTCDataChannel,
+        handler: extern "C" fn(&crate::DataChannel, crate::rtc_datachannel::DataChannelState),
+        ctx: &crate::DataChannel,
+    );
+
+    #[cfg(feature = "tracing")]
+    pub(crate) fn rtc_remove_data_channel_state_h(
+        channel: *const crate::rtc_datachannel::RawRTCDataChannel,
+    );
+
     pub(crate) fn rtc_free_data_channel(channel: *const crate::rtc_datachannel::RawRTCDataChannel);
 }
 
@@ -152,7 +165,9 @@ impl Into<RawDataChannelOptions> for &DataChannelOptions {
 /// connection can have up to a theoretical maximum of 65,534 data channels.
 pub struct DataChannel {
     raw: *const RawRTCDataChannel,
-    sinks: RwLock<HashMap<u8, Sinker<Vec<u8>>>>,
+    sinks: SinkRegistry<Vec<u8>>,
+    // The span of the connection the channel belongs to.
+    span: Span,
 }
 
 unsafe impl Send for DataChannel {}
@@ -165,64 +180,150 @@ impl DataChannel {
     /// Sends data across the data channel to the remote peer.
     pub fn send(&self, buf: &[u8]) {
         assert!(!unsafe { &*self.raw }.remote);
+        rtc_event!(
+            TRACE,
+            parent: &self.span,
+            label = self.label(),
+            size = buf.len(),
+            "data channel send"
+        );
         unsafe { rtc_send_data_channel_msg(self.raw, buf.as_ptr(), buf.len() as c_int) }
     }
 
+    /// The name of the channel, it does not have to be unique.
+    pub fn label(&self) -> &str {
+        c_str_to_str(unsafe { &*self.raw }.label).unwrap_or_default()
+    }
+
     /// Returns a string which indicates the state of the data channel's
     /// underlying data connection.
     pub fn get_state(&self) -> DataChannelState {
         unsafe { rtc_get_data_channel_state(self.raw) }
     }
 
-    /// Register channel data sink, one channel can register multiple sinks.
-    /// The sink id cannot be repeated, otherwise the sink implementation will
-    /// be overwritten.
+    /// Register channel data sink, one channel can register any number of
+    /// sinks. The sink stays registered until the returned handle is
+    /// dropped.
+    ///
+    /// The handle must not be dropped from inside a sink callback of the
+    /// same channel, removing the sink waits for the callbacks to return and
+    /// would deadlock.
+    pub fn add_sink(self: &Arc<Self>, sink: Sinker<Vec<u8>>) -> SinkHandle {
+        let key = self.sinks.next_key();
+        self.insert_sink(key, sink);
+
+        let this = Arc::downgrade(self);
+        SinkHandle::new(move || {
+            if let Some(this) = this.upgrade() {
+                this.remove_sink_by_key(key);
+            }
+        })
+    }
+
+    /// Call the handler with every message received on the channel, until
+    /// the returned handle is dropped.
+    pub fn on_message<F>(self: &Arc<Self>, handler: F) -> SinkHandle
+    where
+        F: Fn(Vec<u8>) + Send + 'static,
+    {
+        self.add_sink(Sinker::from_fn(handler))
+    }
+
+    /// Register channel data sink with a fixed id, a sink with the same id
+    /// is overwritten.
+    #[deprecated(note = "use `add_sink`, which returns a handle")]
     pub fn register_sink(&self, id: u8, sink: Sinker<Vec<u8>>) {
-        assert!(unsafe { &*self.raw }.remote);
-        let mut sinks = self.sinks.write().unwrap();
+        self.insert_sink(SinkKey::Id(id), sink);
+    }
 
-        // Register for the first time, register the callback function to
-        // webrtc native, and then do not need to register again.
-        if sinks.is_empty() {
-            unsafe { rtc_set_data_channel_msg_h(self.raw, on_channal_data, self) }
+    /// Delete the sink registered with a fixed id, if it exists, it will
+    /// return the deleted sink.
+    #[deprecated(note = "drop the handle returned by `add_sink` instead")]
+    pub fn remove_sink(&self, id: u8) -> Option<Sinker<Vec<u8>>> {
+        self.remove_sink_by_key(SinkKey::Id(id))
+    }
+
+    /// Create data channel from raw type ptr, the events of the channel are
+    /// recorded in the span of its connection.
+    pub(crate) fn from_raw(raw: *const RawRTCDataChannel, span: &Span) -> Arc<Self> {
+        assert!(!raw.is_null());
+        let this = Arc::new(Self {
+            sinks: SinkRegistry::new(rtc_span!(
+                DEBUG,
+                parent: span,
+                "sinks",
+                kind = "data_channel",
+                label = c_str_to_str(unsafe { &*raw }.label).unwrap_or_default()
+            )),
+            span: span.clone(),
+            raw,
+        });
+
+        // The state handler only records events.
+        #[cfg(feature = "tracing")]
+        unsafe {
+            rtc_set_data_channel_state_h(raw, on_channel_state, &this)
         }
 
-        sinks.insert(id, sink);
+        this
     }
 
-    /// Delete the registered sink, if it exists, it will return the deleted
-    /// sink.
-    pub fn remove_sink(&self, id: u8) -> Option<Sinker<Vec<u8>>> {
+    fn insert_sink(&self, key: SinkKey, sink: Sinker<Vec<u8>>) {
         assert!(unsafe { &*self.raw }.remote);
-        let mut sinks = self.sinks.write().unwrap();
-        let value = sinks.remove(&id);
-        if sinks.is_empty() {
-            unsafe { rtc_remove_data_channel_msg_h(self.raw) }
-        }
 
-        value
+        // Register for the first time, register the callback function to
+        // webrtc native, and then do not need to register again.
+        self.sinks.insert(key, sink, || unsafe {
+            rtc_set_data_channel_msg_h(self.raw, on_channal_data, self)
+        });
     }
 
-    /// Create data channel from raw type ptr.
-    pub(crate) fn from_raw(raw: *const RawRTCDataChannel) -> Arc<Self> {
-        assert!(!raw.is_null());
-        Arc::new(Self {
-            sinks: RwLock::new(HashMap::new()),
-            raw,
-        })
+    fn remove_sink_by_key(&self, key: SinkKey) -> Option<Sinker<Vec<u8>>> {
+        assert!(unsafe { &*self.raw }.remote);
+        self.sinks
+            .remove(key, || unsafe { rtc_remove_data_channel_msg_h(self.raw) })
     }
 
     fn on_data(this: &Self, data: Vec<u8>) {
-        for sinker in this.sinks.read().unwrap().values() {
-            sinker.sink.on_data(data.clone());
-        }
+        rtc_event!(
+            TRACE,
+            parent: &this.span,
+            label = this.label(),
+            size = data.len(),
+            "data channel message"
+        );
+        this.sinks.on_data(data);
     }
 }
 
 impl Drop for DataChannel {
     fn drop(&mut self) {
-        unsafe { rtc_remove_data_channel_msg_h(self.raw) }
-        unsafe { rtc_free_data_channel(self.raw) }
+        unsafe {
+            #[cfg(feature = "tracing")]
+            rtc_remove_data_channel_state_h(self.raw);
+            rtc_remove_data_channel_msg_h(self.raw);
+            rtc_free_data_channel(self.raw);
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[no_mangle]
+extern "C" fn on_channel_state(ctx: &DataChannel, state: DataChannelState) {
+    match state {
+        DataChannelState::Open => {
+            rtc_event!(DEBUG, parent: &ctx.span, label = ctx.label(), "data channel opened")
+        }
+        DataChannelState::Closed => {
+            rtc_event!(DEBUG, parent: &ctx.span, label = ctx.label(), "data channel closed")
+        }
+        _ => rtc_event!(
+            TRACE,
+            parent: &ctx.span,
+            label = ctx.label(),
+            ?state,
+            "data channel state changed"
+        ),
     }
 }
 
@@ -10,17 +10,22 @@ use crate::{
     create_description_observer::{CreateDescriptionFuture, CreateDescriptionKind},
     cstr::{free_cstring, to_c_str, StringError},
     observer::{ObserverRef, EVENTS},
+    rtc_certificate::RTCRemoteCertificates,
     rtc_datachannel::RawDataChannelOptions,
     rtc_icecandidate::RawRTCIceCandidate,
     rtc_peerconnection_configure::RawRTCPeerConnectionConfigure,
+    rtc_rtp_sender::{rtc_get_rtp_sender, RTCRtpSender},
     set_description_observer::{SetDescriptionFuture, SetDescriptionKind},
-    DataChannel, DataChannelOptions, MediaStream, MediaStreamTrack, Observer, RTCConfiguration,
-    RTCDataChannel, RTCIceCandidate, RTCSessionDescription,
+    trace::{rtc_event, rtc_span, Span},
+    DataChannel, DataChannelOptions, MediaEngine, MediaStream, MediaStreamTrack, Observer,
+    PeerConnectionFactory, RTCConfiguration, RTCConfigurationError, RTCDataChannel,
+    RTCIceCandidate, RTCSessionDescription,
 };
 
 #[allow(improper_ctypes)]
 extern "C" {
     pub(crate) fn rtc_create_peer_connection(
+        factory: *const crate::rtc_peerconnection_factory::RawPeerConnectionFactory,
         config: *const crate::rtc_peerconnection_configure::RawRTCPeerConnectionConfigure,
         events: *const crate::observer::TEvents,
         observer: *mut crate::observer::ObserverRef,
@@ -48,6 +53,16 @@ extern "C" {
         options: *const crate::rtc_datachannel::RawDataChannelOptions,
     ) -> *const crate::rtc_datachannel::RawRTCDataChannel;
 
+    pub(crate) fn rtc_set_configuration(
+        peer: *const crate::rtc_peerconnection::RawRTCPeerConnection,
+        config: *const crate::rtc_peerconnection_configure::RawRTCPeerConnectionConfigure,
+    ) -> c_int;
+
+    pub(crate) fn rtc_set_log_tag(
+        peer: *const crate::rtc_peerconnection::RawRTCPeerConnection,
+        tag: *const c_char,
+    );
+
     pub(crate) fn rtc_close(peer: *const crate::rtc_peerconnection::RawRTCPeerConnection);
 }
 
@@ -56,9 +71,13 @@ pub(crate) type RawRTCPeerConnection = c_void;
 #[derive(Debug)]
 pub enum RTCError {
     CreateRTCFailed,
+    CreateFactoryFailed,
     AddTrackFailed(i32),
     AddIceCandidateFailed,
     RemoveTrackFailed(i32),
+    GetSenderFailed,
+    SetConfigurationFailed(i32),
+    InvalidConfiguration(RTCConfigurationError),
     StringError(StringError),
 }
 
@@ -82,6 +101,11 @@ pub struct RTCPeerConnection {
     observer: HeapPointer<ObserverRef>,
     #[allow(dead_code)]
     config: HeapPointer<RawRTCPeerConnectionConfigure>,
+    // The factory owns the threads and codecs of the connection, it is
+    // dropped after the connection is closed.
+    #[allow(dead_code)]
+    factory: Arc<PeerConnectionFactory>,
+    span: Span,
 }
 
 unsafe impl Send for RTCPeerConnection {}
@@ -91,27 +115,68 @@ impl RTCPeerConnection {
     /// The RTCPeerConnection constructor returns a newly-created
     /// RTCPeerConnection, which represents a connection between the local
     /// device and a remote peer.
+    ///
+    /// The connection is created from the default factory, see
+    /// `PeerConnectionFactory` to configure threads, codecs and networks.
     pub fn new<T: Observer + 'static>(
         config_: &RTCConfiguration,
         observer_: T,
     ) -> Result<Arc<Self>, RTCError> {
+        Self::create(
+            PeerConnectionFactory::default_factory()?,
+            config_,
+            observer_,
+        )
+    }
+
+    /// Create a peer connection which uses the codecs of the media engine
+    /// instead of the codecs built into the native library.
+    ///
+    /// This creates a factory for the one connection, prefer sharing a
+    /// factory built with `PeerConnectionFactory::builder` instead.
+    pub fn with_media_engine<T: Observer + 'static>(
+        config_: &RTCConfiguration,
+        engine: MediaEngine,
+        observer_: T,
+    ) -> Result<Arc<Self>, RTCError> {
+        let factory = PeerConnectionFactory::builder()
+            .media_engine(engine)
+            .build()?;
+        Self::create(factory, config_, observer_)
+    }
+
+    pub(crate) fn create<T: Observer + 'static>(
+        factory: Arc<PeerConnectionFactory>,
+        config_: &RTCConfiguration,
+        observer_: T,
+    ) -> Result<Arc<Self>, RTCError> {
+        config_.validate().map_err(RTCError::InvalidConfiguration)?;
+
+        let raw_config = config_.get_raw().map_err(RTCError::StringError)?;
+
+        let span = rtc_span!(INFO, "peer_connection", id = crate::trace::next_id());
         let observer = HeapPointer::new();
         let config = HeapPointer::new();
         let raw = unsafe {
             rtc_create_peer_connection(
-                config.set(config_.get_raw()),
+                factory.get_raw(),
+                config.set(raw_config),
                 &EVENTS,
-                observer.set(ObserverRef::new(observer_)),
+                observer.set(ObserverRef::new(observer_, span.clone())),
             )
         };
 
         if raw.is_null() {
+            rtc_event!(WARN, parent: &span, "peer connection creation failed");
             Err(RTCError::CreateRTCFailed)
         } else {
+            rtc_event!(INFO, parent: &span, "peer connection created");
             Ok(Arc::new(Self {
                 tracks: Mutex::new(Vec::with_capacity(10)),
                 observer,
                 config,
+                factory,
+                span,
                 raw,
             }))
         }
@@ -126,7 +191,7 @@ impl RTCPeerConnection {
     /// signaling channel to a potential peer to request a connection or to
     /// update the configuration of an existing connection.
     pub fn create_offer(&self) -> CreateDescriptionFuture {
-        CreateDescriptionFuture::create(self.raw, CreateDescriptionKind::Offer)
+        CreateDescriptionFuture::create(self.raw, CreateDescriptionKind::Offer, &self.span)
     }
 
     /// The create_answer() method on the RTCPeerConnection interface creates an
@@ -138,7 +203,7 @@ impl RTCPeerConnection {
     /// then be sent to the source of the offer to continue the negotiation
     /// process.
     pub fn create_answer(&self) -> CreateDescriptionFuture {
-        CreateDescriptionFuture::create(self.raw, CreateDescriptionKind::Answer)
+        CreateDescriptionFuture::create(self.raw, CreateDescriptionKind::Answer, &self.span)
     }
 
     /// The RTCPeerConnection method setLocalDescription() changes the local
@@ -149,7 +214,7 @@ impl RTCPeerConnection {
         &'b self,
         desc: &'b RTCSessionDescription,
     ) -> SetDescriptionFuture<'b> {
-        SetDescriptionFuture::create(self.raw, desc, SetDescriptionKind::Local)
+        SetDescriptionFuture::create(self.raw, desc, SetDescriptionKind::Local, &self.span)
     }
 
     /// The RTCPeerConnection method setRemoteDescription() sets the specified
@@ -160,7 +225,7 @@ impl RTCPeerConnection {
         &'b self,
         desc: &'b RTCSessionDescription,
     ) -> SetDescriptionFuture<'b> {
-        SetDescriptionFuture::create(self.raw, desc, SetDescriptionKind::Remote)
+        SetDescriptionFuture::create(self.raw, desc, SetDescriptionKind::Remote, &self.span)
     }
 
     /// When a web site or app using RTCPeerConnection receives a new ICE
@@ -188,6 +253,15 @@ impl RTCPeerConnection {
     pub fn add_ice_candidate<'b>(&'b self, candidate: &'b RTCIceCandidate) -> Result<(), RTCError> {
         let raw: RawRTCIceCandidate = candidate.try_into().map_err(|e| RTCError::StringError(e))?;
         let ret = unsafe { rtc_add_ice_candidate(self.raw, &raw) };
+        rtc_event!(
+            DEBUG,
+            parent: &self.span,
+            candidate = %candidate.candidate,
+            sdp_mid = %candidate.sdp_mid,
+            added = ret,
+            "remote ice candidate"
+        );
+
         if !ret {
             return Err(RTCError::AddIceCandidateFailed);
         }
@@ -196,19 +270,27 @@ impl RTCPeerConnection {
     }
 
     /// The RTCPeerConnection method addTrack() adds a new media track to the
-    /// set of tracks which will be transmitted to the other peer.
+    /// set of tracks which will be transmitted to the other peer, and
+    /// returns the sender which will be used to transmit the media.
     pub fn add_track(
         &self,
         track: MediaStreamTrack,
         stream: Arc<MediaStream>,
-    ) -> Result<(), RTCError> {
+    ) -> Result<Arc<RTCRtpSender>, RTCError> {
         let ret = unsafe { rtc_add_media_stream_track(self.raw, track.get_raw(), stream.get_id()) };
         if ret != 0 {
             return Err(RTCError::AddTrackFailed(ret));
         }
 
+        let raw = unsafe { rtc_get_rtp_sender(self.raw, track.get_raw()) };
+        if raw.is_null() {
+            return Err(RTCError::GetSenderFailed);
+        }
+
+        let sender = Arc::new(RTCRtpSender::from_raw(raw, track.clone()));
+        stream.add_track(track.clone());
         self.tracks.lock().unwrap().push((track, stream));
-        Ok(())
+        Ok(sender)
     }
 
     /// The `remove_track` method tells the local end of the connection to stop
@@ -239,12 +321,51 @@ impl RTCPeerConnection {
         let opt: RawDataChannelOptions = opt.into();
         let raw = unsafe { rtc_create_data_channel(self.raw, c_label, &opt) };
         free_cstring(c_label);
-        DataChannel::from_raw(raw)
+        rtc_event!(DEBUG, parent: &self.span, label, "data channel created");
+        DataChannel::from_raw(raw, &self.span)
+    }
+
+    /// Update the configuration of the connection, as in new ice servers
+    /// or credentials. Options which can not change after the connection
+    /// is created, such as the bundle policy, fail with a native error.
+    pub fn set_configuration(&self, config: &RTCConfiguration) -> Result<(), RTCError> {
+        config.validate().map_err(RTCError::InvalidConfiguration)?;
+
+        // Native copies the configuration.
+        let raw = config.get_raw().map_err(RTCError::StringError)?;
+        let ret = unsafe { rtc_set_configuration(self.raw, &raw) };
+        rtc_event!(DEBUG, parent: &self.span, ret, "configuration set");
+        if ret != 0 {
+            return Err(RTCError::SetConfigurationFailed(ret));
+        }
+
+        Ok(())
+    }
+
+    /// The certificates the remote side authenticated with, none until the
+    /// DTLS handshake has completed.
+    ///
+    /// Compare the fingerprint with the one announced by signaling to make
+    /// sure the connection is with the expected peer.
+    pub fn get_remote_certificates(&self) -> Option<RTCRemoteCertificates> {
+        RTCRemoteCertificates::get(self.raw)
+    }
+
+    /// Tag the native log messages of the connection, the tag is given to
+    /// the log sink to tell the connections of a process apart.
+    pub fn set_log_tag(&self, tag: &str) -> Result<(), RTCError> {
+        let c_tag = to_c_str(tag).map_err(RTCError::StringError)?;
+
+        // Native copies the tag, it can be freed right after the call.
+        unsafe { rtc_set_log_tag(self.raw, c_tag) }
+        free_cstring(c_tag);
+        Ok(())
     }
 }
 
 impl Drop for RTCPeerConnection {
     fn drop(&mut self) {
         unsafe { rtc_close(self.raw) }
+        rtc_event!(INFO, parent: &self.span, "peer connection closed");
     }
 }
@@ -1,7 +1,15 @@
-use std::ffi::{c_char, c_int};
+use std::{
+    error::Error,
+    ffi::{c_char, c_int, c_void},
+    fmt,
+    ops::RangeInclusive,
+};
+
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::auto_ptr::ArrayExt;
-use crate::cstr::{free_cstring, to_c_str};
+use crate::cstr::{free_cstring, to_c_str, StringError};
+use crate::{IceServerUrl, IceUrlError, RTCCertificate};
 
 /// How to handle negotiation of candidates when remote peer is not compatible
 /// with standard SDP BUNDLE.
@@ -17,7 +25,8 @@ use crate::cstr::{free_cstring, to_c_str};
 /// across a single 5-tuple; that is, from a single IP and port on one peer to a
 /// single IP and port on the other peer, using the same transport protocol.
 #[repr(i32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BundlePolicy {
     /// The ICE agent initially creates one RTCDtlsTransport for each type of
     /// content added: audio, video, and data channels. If the remote endpoint
@@ -39,7 +48,8 @@ pub enum BundlePolicy {
 /// The current ICE transport policy; if the policy isn't specified, all is
 /// assumed by default, allowing all candidates to be considered.
 #[repr(i32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum IceTransportPolicy {
     None = 1,
     /// Only ICE candidates whose IP addresses are being relayed, such as those
@@ -54,7 +64,8 @@ pub enum IceTransportPolicy {
 /// The RTCP mux policy to use when gathering ICE candidates,
 /// in order to support non-multiplexed RTCP.
 #[repr(i32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RtcpMuxPolicy {
     /// Instructs the ICE agent to gather both RTP and RTCP candidates.
     /// If the remote peer can multiplex RTCP,
@@ -68,6 +79,64 @@ pub enum RtcpMuxPolicy {
     Require,
 }
 
+/// Whether the ICE agent keeps gathering candidates after the first round,
+/// so that it can react to network changes.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContinualGatheringPolicy {
+    /// Gather candidates once, the ice gathering state then completes.
+    GatherOnce = 1,
+    /// Keep gathering candidates on new networks, the ice gathering state
+    /// never completes.
+    GatherContinually,
+}
+
+/// A native tri-state of an optional bool, -1 keeps the native default.
+fn option_bool(value: Option<bool>) -> c_int {
+    value.map(|value| value as c_int).unwrap_or(-1)
+}
+
+/// An optional string as a c string, null when unset.
+fn option_string(value: &Option<String>) -> Result<*const c_char, StringError> {
+    value
+        .as_ref()
+        .map(|s| to_c_str(s))
+        .unwrap_or(Ok(std::ptr::null()))
+}
+
+/// A list of strings as a c array of c strings, the strings converted
+/// before a failure are freed.
+fn string_list(
+    list: &Option<Vec<String>>,
+) -> Result<(*const *const c_char, c_int, c_int), StringError> {
+    let Some(list) = list else {
+        return Ok((std::ptr::null(), 0, 0));
+    };
+
+    let mut strings = Vec::with_capacity(list.len());
+    for s in list {
+        match to_c_str(s) {
+            Ok(ptr) => strings.push(ptr),
+            Err(e) => {
+                strings.into_iter().for_each(free_cstring);
+                return Err(e);
+            }
+        }
+    }
+
+    let (ptr, size, capacity) = strings.into_c_layout();
+    Ok((ptr.cast_const(), size as c_int, capacity as c_int))
+}
+
+unsafe fn free_string_list(ptr: *const *const c_char, size: c_int, capacity: c_int) {
+    if !ptr.is_null() {
+        for s in Vec::from_raw_parts(ptr.cast_mut(), size as usize, capacity as usize) {
+            free_cstring(s.cast_mut());
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub(crate) struct RawRTCIceServer {
@@ -107,12 +176,48 @@ pub(crate) struct RawRTCPeerConnectionConfigure {
     ice_servers_size: c_int,
     ice_servers_capacity: c_int,
     ice_candidate_pool_size: c_int,
+    // 0 for either port leaves the range open on that side.
+    min_port: c_int,
+    max_port: c_int,
+    allowed_interfaces: *const *const c_char,
+    allowed_interfaces_size: c_int,
+    allowed_interfaces_capacity: c_int,
+    denied_interfaces: *const *const c_char,
+    denied_interfaces_size: c_int,
+    denied_interfaces_capacity: c_int,
+    enable_ipv6: c_int,
+    enable_tcp_candidates: c_int,
+    continual_gathering_policy: c_int, // ContinualGatheringPolicy
+    include_loopback: c_int,
+    include_link_local: c_int,
+    // Native takes a reference of each certificate.
+    certificates: *const *const c_void,
+    certificates_size: c_int,
+    certificates_capacity: c_int,
 }
 
 impl Drop for RawRTCPeerConnectionConfigure {
     fn drop(&mut self) {
         unsafe {
             free_cstring(self.peer_identity.cast_mut());
+            free_string_list(
+                self.allowed_interfaces,
+                self.allowed_interfaces_size,
+                self.allowed_interfaces_capacity,
+            );
+            free_string_list(
+                self.denied_interfaces,
+                self.denied_interfaces_size,
+                self.denied_interfaces_capacity,
+            );
+            if !self.certificates.is_null() {
+                let _ = Vec::from_raw_parts(
+                    self.certificates.cast_mut(),
+                    self.certificates_size as usize,
+                    self.certificates_capacity as usize,
+                );
+            }
+
             if !self.ice_servers.is_null() {
                 let _ = Vec::from_raw_parts(
                     self.ice_servers.cast_mut(),
@@ -131,7 +236,8 @@ impl Drop for RawRTCPeerConnectionConfigure {
 /// used by the ICE agent; these are typically STUN and/or TURN servers.
 /// If this isn't specified, the connection attempt will be made with no STUN or
 /// TURN server available, which limits the connection to local peers.
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RTCIceServer {
     /// The credential to use when logging into the server.
     /// This is only used if the RTCIceServer represents a TURN server.
@@ -142,36 +248,106 @@ pub struct RTCIceServer {
     /// This required property is either a single string or an array of
     /// strings, each specifying a URL which can be used to connect to the
     /// server.
+    #[serde(default, deserialize_with = "deserialize_urls")]
     pub urls: Option<Vec<String>>,
 }
 
-impl Into<RawRTCIceServer> for &RTCIceServer {
-    fn into(self) -> RawRTCIceServer {
-        let (urls, urls_size, urls_capacity) = self
-            .urls
-            .as_ref()
-            .map(|v| {
-                v.iter()
-                    .map(|s| to_c_str(s).unwrap())
-                    .collect::<Vec<*const c_char>>()
-                    .into_c_layout()
-            })
-            .unwrap_or((std::ptr::null_mut(), 0, 0));
-        RawRTCIceServer {
-            credential: self
-                .credential
-                .as_ref()
-                .map(|s| to_c_str(s).unwrap())
-                .unwrap_or(std::ptr::null_mut()),
-            username: self
-                .username
-                .as_ref()
-                .map(|s| to_c_str(s).unwrap())
-                .unwrap_or(std::ptr::null_mut()),
-            urls_capacity: urls_capacity as c_int,
-            urls_size: urls_size as c_int,
-            urls,
+/// Browsers accept a single url as well as a list of urls.
+fn deserialize_urls<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Urls {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(
+        Option::<Urls>::deserialize(deserializer)?.map(|urls| match urls {
+            Urls::One(url) => vec![url],
+            Urls::Many(urls) => urls,
+        }),
+    )
+}
+
+impl RTCIceServer {
+    /// Check that the server has urls, that they are valid stun or turn
+    /// urls, and that turn servers have credentials.
+    pub fn validate(&self) -> Result<(), RTCConfigurationError> {
+        let urls = match self.urls.as_deref() {
+            None | Some([]) => return Err(RTCConfigurationError::NoUrls),
+            Some(urls) => urls,
+        };
+
+        check_nul(self.username.iter().chain(self.credential.iter()))?;
+        for url in urls {
+            let parsed = url
+                .parse::<IceServerUrl>()
+                .map_err(|e| RTCConfigurationError::InvalidUrl(url.clone(), e))?;
+            if parsed.scheme.is_turn() && (self.username.is_none() || self.credential.is_none()) {
+                return Err(RTCConfigurationError::MissingCredentials(url.clone()));
+            }
         }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum RTCConfigurationError {
+    /// An ice server without urls.
+    NoUrls,
+    InvalidUrl(String, IceUrlError),
+    /// A turn url of an ice server without a username or credential.
+    MissingCredentials(String),
+    /// The port range is empty or starts at port 0.
+    InvalidPortRange,
+    /// A string contains a nul character, which native can not take.
+    NulCharacter(String),
+}
+
+impl Error for RTCConfigurationError {}
+
+impl fmt::Display for RTCConfigurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoUrls => write!(f, "ice server has no urls"),
+            Self::InvalidUrl(url, e) => write!(f, "invalid ice server url {:?}: {}", url, e),
+            Self::MissingCredentials(url) => write!(f, "turn server {:?} has no credentials", url),
+            Self::InvalidPortRange => write!(f, "invalid port range"),
+            Self::NulCharacter(value) => write!(f, "nul character in {:?}", value),
+        }
+    }
+}
+
+fn check_nul<'a, I: IntoIterator<Item = &'a String>>(
+    values: I,
+) -> Result<(), RTCConfigurationError> {
+    match values.into_iter().find(|value| value.contains('\0')) {
+        Some(value) => Err(RTCConfigurationError::NulCharacter(value.clone())),
+        None => Ok(()),
+    }
+}
+
+impl TryFrom<&RTCIceServer> for RawRTCIceServer {
+    type Error = StringError;
+
+    fn try_from(value: &RTCIceServer) -> Result<Self, Self::Error> {
+        // Filled in one string at a time, so that drop frees the strings
+        // converted before a failure.
+        let mut raw = RawRTCIceServer {
+            credential: std::ptr::null(),
+            urls: std::ptr::null(),
+            urls_size: 0,
+            urls_capacity: 0,
+            username: std::ptr::null(),
+        };
+
+        raw.credential = option_string(&value.credential)?;
+        raw.username = option_string(&value.username)?;
+        (raw.urls, raw.urls_size, raw.urls_capacity) = string_list(&value.urls)?;
+        Ok(raw)
     }
 }
 
@@ -179,7 +355,11 @@ impl Into<RawRTCIceServer> for &RTCIceServer {
 ///
 /// The RTCPeerConnection is a newly-created RTCPeerConnection,
 /// which represents a connection between the local device and a remote peer.
-#[derive(Default, Debug)]
+///
+/// The configuration (de)serializes with the field names of the browser
+/// `RTCConfiguration`, as in `iceServers` and `iceTransportPolicy`.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct RTCConfiguration {
     /// Specifies how to handle negotiation of candidates when the remote peer
     /// is not compatible with the SDP BUNDLE standard. If the remote endpoint
@@ -219,42 +399,300 @@ pub struct RTCConfiguration {
     /// before you start trying to connect, so that they're already available
     /// for inspection when RTCPeerConnection.setLocalDescription() is called.
     pub ice_candidate_pool_size: Option<u8>,
+    /// The local ports candidates are bound to, for hosts behind a
+    /// firewall which only opens a fixed range.
+    pub port_range: Option<RangeInclusive<u16>>,
+    /// Only gather candidates on the network interfaces with these names,
+    /// as in `eth0`.
+    pub allowed_interfaces: Option<Vec<String>>,
+    /// Never gather candidates on the network interfaces with these names.
+    pub denied_interfaces: Option<Vec<String>>,
+    /// Gather candidates on ipv6 addresses, enabled by default.
+    pub enable_ipv6: Option<bool>,
+    /// Gather tcp candidates as well as udp candidates, enabled by default.
+    pub enable_tcp_candidates: Option<bool>,
+    /// Whether to keep gathering candidates after the first round, the
+    /// default is to gather once.
+    pub continual_gathering_policy: Option<ContinualGatheringPolicy>,
+    /// Gather candidates on loopback interfaces, disabled by default.
+    pub include_loopback: Option<bool>,
+    /// Gather candidates on link-local addresses, as in `fe80::/10` and
+    /// `169.254.0.0/16`, disabled by default.
+    pub include_link_local: Option<bool>,
+    /// The certificates the connection authenticates with, instead of a
+    /// certificate generated for the connection. Not (de)serialized, store
+    /// them with `RTCCertificate::to_pem`.
+    #[serde(skip)]
+    pub certificates: Option<Vec<RTCCertificate>>,
 }
 
 unsafe impl Send for RTCConfiguration {}
 unsafe impl Sync for RTCConfiguration {}
 
-impl Into<RawRTCPeerConnectionConfigure> for &RTCConfiguration {
-    fn into(self) -> RawRTCPeerConnectionConfigure {
-        let (ice_servers, ice_servers_size, ice_servers_capacity) = self
-            .ice_servers
+impl TryFrom<&RTCConfiguration> for RawRTCPeerConnectionConfigure {
+    type Error = StringError;
+
+    fn try_from(value: &RTCConfiguration) -> Result<Self, Self::Error> {
+        let (certificates, certificates_size, certificates_capacity) = value
+            .certificates
             .as_ref()
-            .map(|i| {
-                i.iter()
-                    .map(|s| s.into())
-                    .collect::<Vec<RawRTCIceServer>>()
+            .map(|v| {
+                v.iter()
+                    .map(|cert| cert.get_raw())
+                    .collect::<Vec<*const c_void>>()
                     .into_c_layout()
             })
             .unwrap_or((std::ptr::null_mut(), 0, 0));
-        RawRTCPeerConnectionConfigure {
-            bundle_policy: self.bundle_policy.map(|i| i as c_int).unwrap_or(0),
-            ice_transport_policy: self.ice_transport_policy.map(|i| i as c_int).unwrap_or(0),
-            peer_identity: self
-                .peer_identity
+
+        // The strings are filled in afterwards, so that drop frees the
+        // strings converted before a failure.
+        let mut raw = RawRTCPeerConnectionConfigure {
+            bundle_policy: value.bundle_policy.map(|i| i as c_int).unwrap_or(0),
+            ice_transport_policy: value.ice_transport_policy.map(|i| i as c_int).unwrap_or(0),
+            peer_identity: std::ptr::null(),
+            rtcp_mux_policy: value.rtcp_mux_policy.map(|i| i as c_int).unwrap_or(0),
+            ice_candidate_pool_size: value.ice_candidate_pool_size.unwrap_or(0) as c_int,
+            ice_servers: std::ptr::null(),
+            ice_servers_size: 0,
+            ice_servers_capacity: 0,
+            min_port: value
+                .port_range
+                .as_ref()
+                .map(|range| *range.start() as c_int)
+                .unwrap_or(0),
+            max_port: value
+                .port_range
                 .as_ref()
-                .map(|s| to_c_str(s).unwrap())
-                .unwrap_or(std::ptr::null_mut()),
-            rtcp_mux_policy: self.rtcp_mux_policy.map(|i| i as c_int).unwrap_or(0),
-            ice_candidate_pool_size: self.ice_candidate_pool_size.unwrap_or(0) as c_int,
-            ice_servers_capacity: ice_servers_capacity as c_int,
-            ice_servers_size: ice_servers_size as c_int,
-            ice_servers,
+                .map(|range| *range.end() as c_int)
+                .unwrap_or(0),
+            allowed_interfaces: std::ptr::null(),
+            allowed_interfaces_size: 0,
+            allowed_interfaces_capacity: 0,
+            denied_interfaces: std::ptr::null(),
+            denied_interfaces_size: 0,
+            denied_interfaces_capacity: 0,
+            enable_ipv6: option_bool(value.enable_ipv6),
+            enable_tcp_candidates: option_bool(value.enable_tcp_candidates),
+            continual_gathering_policy: value
+                .continual_gathering_policy
+                .map(|i| i as c_int)
+                .unwrap_or(0),
+            include_loopback: option_bool(value.include_loopback),
+            include_link_local: option_bool(value.include_link_local),
+            certificates_capacity: certificates_capacity as c_int,
+            certificates_size: certificates_size as c_int,
+            certificates,
+        };
+
+        raw.peer_identity = option_string(&value.peer_identity)?;
+        (
+            raw.allowed_interfaces,
+            raw.allowed_interfaces_size,
+            raw.allowed_interfaces_capacity,
+        ) = string_list(&value.allowed_interfaces)?;
+        (
+            raw.denied_interfaces,
+            raw.denied_interfaces_size,
+            raw.denied_interfaces_capacity,
+        ) = string_list(&value.denied_interfaces)?;
+
+        if let Some(servers) = &value.ice_servers {
+            let (ptr, size, capacity) = servers
+                .iter()
+                .map(RawRTCIceServer::try_from)
+                .collect::<Result<Vec<_>, _>>()?
+                .into_c_layout();
+            raw.ice_servers = ptr;
+            raw.ice_servers_size = size as c_int;
+            raw.ice_servers_capacity = capacity as c_int;
         }
+
+        Ok(raw)
     }
 }
 
 impl RTCConfiguration {
-    pub(crate) fn get_raw(&self) -> RawRTCPeerConnectionConfigure {
-        self.into()
+    /// Check the ice servers and the network options, native panics or
+    /// fails silently on invalid values.
+    pub fn validate(&self) -> Result<(), RTCConfigurationError> {
+        for server in self.ice_servers.iter().flatten() {
+            server.validate()?;
+        }
+
+        if let Some(range) = &self.port_range {
+            if range.is_empty() || *range.start() == 0 {
+                return Err(RTCConfigurationError::InvalidPortRange);
+            }
+        }
+
+        check_nul(
+            self.peer_identity
+                .iter()
+                .chain(self.allowed_interfaces.iter().flatten())
+                .chain(self.denied_interfaces.iter().flatten()),
+        )
+    }
+
+    pub(crate) fn get_raw(&self) -> Result<RawRTCPeerConnectionConfigure, StringError> {
+        self.try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::IpAddr,
+        sync::{
+            mpsc::{channel, Sender},
+            Mutex,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        DataChannelOptions, IceGatheringState, Observer, RTCIceCandidate, RTCPeerConnection,
+    };
+
+    #[test]
+    fn raw_configuration() {
+        let config = RTCConfiguration {
+            ice_servers: Some(vec![RTCIceServer {
+                urls: Some(vec!["stun:stun.l.google.com:19302".to_string()]),
+                ..Default::default()
+            }]),
+            peer_identity: Some("alice".to_string()),
+            allowed_interfaces: Some(vec!["eth0".to_string(), "wlan0".to_string()]),
+            port_range: Some(50000..=50010),
+            include_loopback: Some(true),
+            ..Default::default()
+        };
+
+        let raw = config.get_raw().unwrap();
+        assert_eq!(raw.ice_servers_size, 1);
+        assert_eq!(raw.allowed_interfaces_size, 2);
+        assert!(raw.denied_interfaces.is_null());
+        assert_eq!((raw.min_port, raw.max_port), (50000, 50010));
+        assert_eq!((raw.include_loopback, raw.include_link_local), (1, -1));
+    }
+
+    #[test]
+    fn nul_character() {
+        let server = RTCIceServer {
+            urls: Some(vec!["turn:turn.example.com".to_string()]),
+            username: Some("alice".to_string()),
+            credential: Some("se\0cret".to_string()),
+        };
+
+        assert!(matches!(
+            RawRTCIceServer::try_from(&server),
+            Err(StringError::NulError)
+        ));
+
+        let config = RTCConfiguration {
+            ice_servers: Some(vec![server]),
+            denied_interfaces: Some(vec!["eth0".to_string()]),
+            ..Default::default()
+        };
+
+        assert!(matches!(config.get_raw(), Err(StringError::NulError)));
+        assert!(matches!(
+            config.validate(),
+            Err(RTCConfigurationError::NulCharacter(_))
+        ));
+    }
+
+    #[test]
+    fn deserialize_configuration() {
+        let config: RTCConfiguration = serde_json::from_str(
+            r#"{
+                "iceServers": [
+                    { "urls": "stun:stun.example.com" },
+                    {
+                        "urls": ["turn:turn.example.com", "turns:turn.example.com"],
+                        "username": "alice",
+                        "credential": "secret"
+                    }
+                ],
+                "iceTransportPolicy": "relay"
+            }"#,
+        )
+        .unwrap();
+
+        let servers = config.ice_servers.as_ref().unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(
+            servers[0].urls,
+            Some(vec!["stun:stun.example.com".to_string()])
+        );
+        assert_eq!(servers[0].username, None);
+        assert_eq!(
+            servers[1].urls,
+            Some(vec![
+                "turn:turn.example.com".to_string(),
+                "turns:turn.example.com".to_string()
+            ])
+        );
+        assert_eq!(servers[1].username.as_deref(), Some("alice"));
+        assert_eq!(servers[1].credential.as_deref(), Some("secret"));
+        assert!(matches!(
+            config.ice_transport_policy,
+            Some(IceTransportPolicy::Relay)
+        ));
+        assert!(config.validate().is_ok());
+
+        let server: RTCIceServer = serde_json::from_str("{}").unwrap();
+        assert_eq!(server.urls, None);
+    }
+
+    struct CandidateObserver(Mutex<Sender<Option<RTCIceCandidate>>>);
+
+    impl Observer for CandidateObserver {
+        fn on_ice_candidate(&self, candidate: RTCIceCandidate) {
+            let _ = self.0.lock().unwrap().send(Some(candidate));
+        }
+
+        fn on_ice_gathering_change(&self, state: IceGatheringState) {
+            if let IceGatheringState::Complete = state {
+                let _ = self.0.lock().unwrap().send(None);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the native library and a loopback interface"]
+    fn loopback_candidates() {
+        let config = RTCConfiguration {
+            port_range: Some(50000..=50009),
+            include_loopback: Some(true),
+            enable_tcp_candidates: Some(false),
+            ..Default::default()
+        };
+
+        let (sender, receiver) = channel();
+        let observer = CandidateObserver(Mutex::new(sender));
+        let pc = RTCPeerConnection::new(&config, observer).unwrap();
+        let _channel = pc.create_data_channel("gather", &DataChannelOptions::default());
+
+        futures::executor::block_on(async {
+            let offer = pc.create_offer().await.unwrap();
+            pc.set_local_description(&offer).await.unwrap();
+        });
+
+        // `candidate:<foundation> <component> <protocol> <priority> <address>
+        // <port> typ <type> ...`
+        let mut hosts = Vec::new();
+        while let Some(candidate) = receiver.recv_timeout(Duration::from_secs(10)).unwrap() {
+            let fields = candidate.candidate.split(' ').collect::<Vec<_>>();
+            if fields.get(7) == Some(&"host") {
+                let address = fields[4].parse::<IpAddr>().unwrap();
+                hosts.push((address, fields[5].parse::<u16>().unwrap()));
+            }
+        }
+
+        assert!(hosts.iter().any(|(address, _)| address.is_loopback()));
+        for (address, port) in hosts {
+            assert!((50000..=50009).contains(&port), "{} {}", address, port);
+        }
     }
 }
@@ -0,0 +1,275 @@
+use std::{
+    ffi::{c_char, c_int, c_void},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    cstr::{free_cstring, to_c_str, StringError},
+    AudioDecoderFactory, AudioDeviceModule, AudioEncoderFactory, MediaEngine, Observer,
+    RTCConfiguration, RTCError, RTCPeerConnection, VideoDecoderFactory, VideoEncoderFactory,
+};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_create_peer_connection_factory(
+        options: *const crate::rtc_peerconnection_factory::RawPeerConnectionFactoryOptions,
+        engine: *const crate::media_engine::RawMediaEngine,
+    ) -> *const crate::rtc_peerconnection_factory::RawPeerConnectionFactory;
+
+    pub(crate) fn rtc_free_peer_connection_factory(
+        factory: *const crate::rtc_peerconnection_factory::RawPeerConnectionFactory,
+    );
+}
+
+pub(crate) type RawPeerConnectionFactory = c_void;
+
+/// The scheduling priority of a native thread.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThreadPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Realtime,
+}
+
+/// Options of one of the native threads of a factory.
+#[derive(Clone, Debug, Default)]
+pub struct ThreadOptions {
+    /// The thread name, as shown by debuggers and profilers.
+    pub name: Option<String>,
+    pub priority: ThreadPriority,
+}
+
+/// Network adapter types, used to keep the ice agent off some networks.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdapterType {
+    Ethernet = 1,
+    Wifi = 2,
+    Cellular = 4,
+    Vpn = 8,
+    Loopback = 16,
+}
+
+#[repr(C)]
+pub(crate) struct RawThreadOptions {
+    name: *const c_char,
+    priority: ThreadPriority,
+}
+
+impl TryFrom<&ThreadOptions> for RawThreadOptions {
+    type Error = StringError;
+
+    fn try_from(options: &ThreadOptions) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: match &options.name {
+                Some(name) => to_c_str(name)?,
+                None => std::ptr::null(),
+            },
+            priority: options.priority,
+        })
+    }
+}
+
+impl Drop for RawThreadOptions {
+    fn drop(&mut self) {
+        free_cstring(self.name);
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawPeerConnectionFactoryOptions {
+    signaling_thread: RawThreadOptions,
+    worker_thread: RawThreadOptions,
+    network_thread: RawThreadOptions,
+    // Native keeps referring to the field trials string, it lives as long
+    // as the factory.
+    field_trials: *const c_char,
+    network_ignore_mask: c_int,
+}
+
+impl Drop for RawPeerConnectionFactoryOptions {
+    fn drop(&mut self) {
+        free_cstring(self.field_trials);
+    }
+}
+
+/// Configures a peer connection factory.
+#[derive(Default)]
+pub struct PeerConnectionFactoryBuilder {
+    signaling_thread: ThreadOptions,
+    worker_thread: ThreadOptions,
+    network_thread: ThreadOptions,
+    engine: MediaEngine,
+    field_trials: Option<String>,
+    network_ignore_mask: c_int,
+}
+
+impl PeerConnectionFactoryBuilder {
+    /// The thread the peer connection api and the observers run on.
+    pub fn signaling_thread(mut self, options: ThreadOptions) -> Self {
+        self.signaling_thread = options;
+        self
+    }
+
+    /// The thread media is processed on.
+    pub fn worker_thread(mut self, options: ThreadOptions) -> Self {
+        self.worker_thread = options;
+        self
+    }
+
+    /// The thread the sockets are handled on.
+    pub fn network_thread(mut self, options: ThreadOptions) -> Self {
+        self.network_thread = options;
+        self
+    }
+
+    /// Use the codecs and the audio device of the media engine, replacing
+    /// any set before.
+    pub fn media_engine(mut self, engine: MediaEngine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn video_encoder_factory<T: VideoEncoderFactory + 'static>(mut self, factory: T) -> Self {
+        self.engine = self.engine.with_video_encoder_factory(factory);
+        self
+    }
+
+    pub fn video_decoder_factory<T: VideoDecoderFactory + 'static>(mut self, factory: T) -> Self {
+        self.engine = self.engine.with_video_decoder_factory(factory);
+        self
+    }
+
+    pub fn audio_encoder_factory<T: AudioEncoderFactory + 'static>(mut self, factory: T) -> Self {
+        self.engine = self.engine.with_audio_encoder_factory(factory);
+        self
+    }
+
+    pub fn audio_decoder_factory<T: AudioDecoderFactory + 'static>(mut self, factory: T) -> Self {
+        self.engine = self.engine.with_audio_decoder_factory(factory);
+        self
+    }
+
+    pub fn audio_device_module<T: AudioDeviceModule + 'static>(mut self, device: T) -> Self {
+        self.engine = self.engine.with_audio_device_module(device);
+        self
+    }
+
+    /// Field trials of the connections of this factory, in the native
+    /// format, as in `WebRTC-Foo/Enabled/WebRTC-Bar/Disabled/`.
+    pub fn field_trials(mut self, trials: &str) -> Self {
+        self.field_trials = Some(trials.to_string());
+        self
+    }
+
+    /// Never gather candidates on networks of this type.
+    pub fn ignore_network(mut self, adapter: AdapterType) -> Self {
+        self.network_ignore_mask |= adapter as c_int;
+        self
+    }
+
+    pub fn build(self) -> Result<Arc<PeerConnectionFactory>, RTCError> {
+        let options = Box::new(RawPeerConnectionFactoryOptions {
+            signaling_thread: (&self.signaling_thread)
+                .try_into()
+                .map_err(RTCError::StringError)?,
+            worker_thread: (&self.worker_thread)
+                .try_into()
+                .map_err(RTCError::StringError)?,
+            network_thread: (&self.network_thread)
+                .try_into()
+                .map_err(RTCError::StringError)?,
+            field_trials: match &self.field_trials {
+                Some(trials) => to_c_str(trials).map_err(RTCError::StringError)?,
+                None => std::ptr::null(),
+            },
+            network_ignore_mask: self.network_ignore_mask,
+        });
+
+        let engine = self.engine.get_raw().map_err(RTCError::StringError)?;
+        let raw = unsafe { rtc_create_peer_connection_factory(&*options, &engine) };
+        if raw.is_null() {
+            Err(RTCError::CreateFactoryFailed)
+        } else {
+            Ok(Arc::new(PeerConnectionFactory {
+                engine: self.engine,
+                options,
+                raw,
+            }))
+        }
+    }
+}
+
+static DEFAULT_FACTORY: Mutex<Option<Arc<PeerConnectionFactory>>> = Mutex::new(None);
+
+/// Creates peer connections and owns what they share: the native threads,
+/// the codecs, the audio device and the field trials.
+///
+/// Creating a factory is expensive, a process with many connections
+/// should create them all from one factory.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn connect(
+/// #     config: RTCConfiguration,
+/// #     observer: impl Observer + 'static,
+/// # ) -> Result<(), RTCError> {
+/// let factory = PeerConnectionFactory::builder()
+///     .audio_device_module(VirtualAudioDevice::new(48000, 2))
+///     .ignore_network(AdapterType::Vpn)
+///     .build()?;
+///
+/// let pc = factory.create_peer_connection(&config, observer)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PeerConnectionFactory {
+    raw: *const RawPeerConnectionFactory,
+    // Both are used by native until the factory is freed.
+    #[allow(dead_code)]
+    engine: MediaEngine,
+    #[allow(dead_code)]
+    options: Box<RawPeerConnectionFactoryOptions>,
+}
+
+unsafe impl Send for PeerConnectionFactory {}
+unsafe impl Sync for PeerConnectionFactory {}
+
+impl PeerConnectionFactory {
+    pub fn builder() -> PeerConnectionFactoryBuilder {
+        PeerConnectionFactoryBuilder::default()
+    }
+
+    /// The factory used by `RTCPeerConnection::new`, created with the
+    /// default options on first use.
+    pub fn default_factory() -> Result<Arc<Self>, RTCError> {
+        let mut factory = DEFAULT_FACTORY.lock().unwrap();
+        if let Some(factory) = factory.as_ref() {
+            return Ok(factory.clone());
+        }
+
+        Ok(factory.insert(Self::builder().build()?).clone())
+    }
+
+    /// Create a peer connection, the connection keeps the factory alive.
+    pub fn create_peer_connection<T: Observer + 'static>(
+        self: &Arc<Self>,
+        config: &RTCConfiguration,
+        observer: T,
+    ) -> Result<Arc<RTCPeerConnection>, RTCError> {
+        RTCPeerConnection::create(self.clone(), config, observer)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawPeerConnectionFactory {
+        self.raw
+    }
+}
+
+impl Drop for PeerConnectionFactory {
+    fn drop(&mut self) {
+        unsafe { rtc_free_peer_connection_factory(self.raw) }
+    }
+}
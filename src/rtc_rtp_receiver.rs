@@ -0,0 +1,77 @@
+use std::ffi::c_void;
+
+use crate::{encoded_frame::TransformSlot, EncodedFrameTransform, MediaStreamTrack};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_free_rtp_receiver(
+        receiver: *const crate::rtc_rtp_receiver::RawRTCRtpReceiver,
+    );
+
+    pub(crate) fn rtc_set_rtp_receiver_transform(
+        receiver: *const crate::rtc_rtp_receiver::RawRTCRtpReceiver,
+        handler: extern "C" fn(
+            &crate::encoded_frame::TransformContext,
+            *const crate::encoded_frame::RawEncodedFrame,
+        ),
+        ctx: &crate::encoded_frame::TransformContext,
+    );
+
+    pub(crate) fn rtc_remove_rtp_receiver_transform(
+        receiver: *const crate::rtc_rtp_receiver::RawRTCRtpReceiver,
+    );
+}
+
+pub(crate) type RawRTCRtpReceiver = c_void;
+
+/// The RTCRtpReceiver interface allows an application to inspect the
+/// receipt of a MediaStreamTrack.
+pub struct RTCRtpReceiver {
+    raw: *const RawRTCRtpReceiver,
+    track: MediaStreamTrack,
+    transform: TransformSlot,
+}
+
+unsafe impl Send for RTCRtpReceiver {}
+unsafe impl Sync for RTCRtpReceiver {}
+
+impl RTCRtpReceiver {
+    pub(crate) fn from_raw(raw: *const RawRTCRtpReceiver, track: MediaStreamTrack) -> Self {
+        assert!(!raw.is_null());
+        Self {
+            transform: TransformSlot::default(),
+            track,
+            raw,
+        }
+    }
+
+    /// The track which is being received.
+    pub fn track(&self) -> &MediaStreamTrack {
+        &self.track
+    }
+
+    /// Pass every encoded frame through the transform before it is
+    /// decoded, replacing the previous transform.
+    pub fn set_transform<T: EncodedFrameTransform + 'static>(&self, transform: T) {
+        let raw = self.raw;
+        self.transform.set(
+            Box::new(transform),
+            |handler, ctx| unsafe { rtc_set_rtp_receiver_transform(raw, handler, ctx) },
+            || unsafe { rtc_remove_rtp_receiver_transform(raw) },
+        );
+    }
+
+    /// Decode the received frames unchanged again.
+    pub fn remove_transform(&self) {
+        let raw = self.raw;
+        self.transform
+            .remove(|| unsafe { rtc_remove_rtp_receiver_transform(raw) });
+    }
+}
+
+impl Drop for RTCRtpReceiver {
+    fn drop(&mut self) {
+        self.remove_transform();
+        unsafe { rtc_free_rtp_receiver(self.raw) }
+    }
+}
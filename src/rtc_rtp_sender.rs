@@ -0,0 +1,81 @@
+use std::ffi::c_void;
+
+use crate::{encoded_frame::TransformSlot, EncodedFrameTransform, MediaStreamTrack};
+
+#[allow(improper_ctypes)]
+extern "C" {
+    pub(crate) fn rtc_get_rtp_sender(
+        peer: *const crate::rtc_peerconnection::RawRTCPeerConnection,
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    ) -> *const crate::rtc_rtp_sender::RawRTCRtpSender;
+
+    pub(crate) fn rtc_free_rtp_sender(sender: *const crate::rtc_rtp_sender::RawRTCRtpSender);
+
+    pub(crate) fn rtc_set_rtp_sender_transform(
+        sender: *const crate::rtc_rtp_sender::RawRTCRtpSender,
+        handler: extern "C" fn(
+            &crate::encoded_frame::TransformContext,
+            *const crate::encoded_frame::RawEncodedFrame,
+        ),
+        ctx: &crate::encoded_frame::TransformContext,
+    );
+
+    pub(crate) fn rtc_remove_rtp_sender_transform(
+        sender: *const crate::rtc_rtp_sender::RawRTCRtpSender,
+    );
+}
+
+pub(crate) type RawRTCRtpSender = c_void;
+
+/// The RTCRtpSender interface provides the ability to control and obtain
+/// details about how a particular MediaStreamTrack is encoded and sent to
+/// a remote peer.
+pub struct RTCRtpSender {
+    raw: *const RawRTCRtpSender,
+    track: MediaStreamTrack,
+    transform: TransformSlot,
+}
+
+unsafe impl Send for RTCRtpSender {}
+unsafe impl Sync for RTCRtpSender {}
+
+impl RTCRtpSender {
+    pub(crate) fn from_raw(raw: *const RawRTCRtpSender, track: MediaStreamTrack) -> Self {
+        assert!(!raw.is_null());
+        Self {
+            transform: TransformSlot::default(),
+            track,
+            raw,
+        }
+    }
+
+    /// The track which is being sent.
+    pub fn track(&self) -> &MediaStreamTrack {
+        &self.track
+    }
+
+    /// Pass every encoded frame through the transform before it is
+    /// packetized, replacing the previous transform.
+    pub fn set_transform<T: EncodedFrameTransform + 'static>(&self, transform: T) {
+        let raw = self.raw;
+        self.transform.set(
+            Box::new(transform),
+            |handler, ctx| unsafe { rtc_set_rtp_sender_transform(raw, handler, ctx) },
+            || unsafe { rtc_remove_rtp_sender_transform(raw) },
+        );
+    }
+
+    /// Send the encoded frames unchanged again.
+    pub fn remove_transform(&self) {
+        let raw = self.raw;
+        self.transform
+            .remove(|| unsafe { rtc_remove_rtp_sender_transform(raw) });
+    }
+}
+
+impl Drop for RTCRtpSender {
+    fn drop(&mut self) {
+        self.remove_transform();
+        unsafe { rtc_free_rtp_sender(self.raw) }
+    }
+}
@@ -14,6 +14,7 @@ use crate::{
     cstr::{from_c_str, StringError},
     rtc_peerconnection::RawRTCPeerConnection,
     rtc_session_description::RawRTCSessionDescription,
+    trace::{rtc_event, rtc_span, Span},
     Promisify, PromisifyExt, RTCSessionDescription,
 };
 
@@ -47,7 +48,7 @@ impl fmt::Display for SetDescriptionError {
     }
 }
 
-#[derive(PartialEq, Eq, PartialOrd)]
+#[derive(PartialEq, Eq, PartialOrd, Debug)]
 pub(crate) enum SetDescriptionKind {
     Local,
     Remote,
@@ -76,6 +77,7 @@ pub struct SetDescriptionObserver<'a> {
     desc: &'a RTCSessionDescription,
     pc: *const RawRTCPeerConnection,
     ret: Arc<AtomicPtr<Result<(), SetDescriptionError>>>,
+    span: Span,
 }
 
 unsafe impl Send for SetDescriptionObserver<'_> {}
@@ -86,9 +88,30 @@ impl<'a> PromisifyExt for SetDescriptionObserver<'a> {
     type Output = ();
 
     fn handle(&self, waker: Arc<AtomicWaker>) -> Result<(), Self::Err> {
+        rtc_event!(
+            DEBUG,
+            parent: &self.span,
+            kind = ?self.desc.kind,
+            size = self.desc.sdp.len(),
+            "set description started"
+        );
+
         let ret = self.ret.clone();
+        #[cfg(feature = "tracing")]
+        let span = self.span.clone();
         let ctx = Box::into_raw(Box::new(SetDescriptionContext {
             callback: Box::new(move |res| {
+                #[cfg(feature = "tracing")]
+                match &res {
+                    Ok(()) => rtc_event!(DEBUG, parent: &span, "set description completed"),
+                    Err(e) => rtc_event!(
+                        WARN,
+                        parent: &span,
+                        error = ?e,
+                        "set description failed"
+                    ),
+                }
+
                 ret.store(Box::into_raw(Box::new(res)), Ordering::Relaxed);
                 waker.wake();
             }),
@@ -123,8 +146,10 @@ impl<'a> SetDescriptionFuture<'a> {
         pc: *const RawRTCPeerConnection,
         desc: &'a RTCSessionDescription,
         kind: SetDescriptionKind,
+        parent: &Span,
     ) -> Self {
         Promisify::new(SetDescriptionObserver {
+            span: rtc_span!(DEBUG, parent: parent, "set_description", ?kind),
             ret: Arc::new(AtomicPtr::new(std::ptr::null_mut())),
             desc,
             kind,
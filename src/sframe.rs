@@ -0,0 +1,984 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+
+use aes::Aes128;
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes128Gcm, Aes256Gcm, Nonce,
+};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha512};
+
+use crate::{EncodedFrame, EncodedFrameSink, EncodedFrameTransform};
+
+/// The number of derived keys a decryptor keeps around, so that frames
+/// of the previous keys still decrypt during a rotation.
+const KEY_CACHE_SIZE: usize = 8;
+
+/// How far a decryptor ratchets forward at once, a key id further ahead
+/// is more likely a stale generation than a fast sender.
+const MAX_RATCHET_STEPS: u64 = 256;
+
+/// The SFrame cipher suites of RFC 9605.
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherSuite {
+    AesCtr128HmacSha256_80 = 1,
+    AesCtr128HmacSha256_64 = 2,
+    AesCtr128HmacSha256_32 = 3,
+    AesGcm128Sha256 = 4,
+    AesGcm256Sha512 = 5,
+}
+
+impl CipherSuite {
+    /// The length of the derived key, for the ctr suites this is the
+    /// encryption key followed by the authentication key.
+    fn key_len(self) -> usize {
+        match self {
+            Self::AesCtr128HmacSha256_80
+            | Self::AesCtr128HmacSha256_64
+            | Self::AesCtr128HmacSha256_32 => 48,
+            Self::AesGcm128Sha256 => 16,
+            Self::AesGcm256Sha512 => 32,
+        }
+    }
+
+    fn tag_len(self) -> usize {
+        match self {
+            Self::AesCtr128HmacSha256_80 => 10,
+            Self::AesCtr128HmacSha256_64 => 8,
+            Self::AesCtr128HmacSha256_32 => 4,
+            Self::AesGcm128Sha256 | Self::AesGcm256Sha512 => 16,
+        }
+    }
+
+    fn hkdf_expand(self, secret: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+        let mut okm = vec![0u8; len];
+        match self {
+            Self::AesGcm256Sha512 => Hkdf::<Sha512>::new(Some(&[]), secret)
+                .expand(info, &mut okm)
+                .expect("sframe hkdf output too long"),
+            _ => Hkdf::<Sha256>::new(Some(&[]), secret)
+                .expand(info, &mut okm)
+                .expect("sframe hkdf output too long"),
+        }
+
+        okm
+    }
+
+    /// Derive the next base key of a ratchet.
+    fn ratchet(self, base_key: &[u8]) -> Vec<u8> {
+        let hash_len = match self {
+            Self::AesGcm256Sha512 => 64,
+            _ => 32,
+        };
+
+        self.hkdf_expand(base_key, b"SFrame 1.0 Ratchet", hash_len)
+    }
+}
+
+#[derive(Debug)]
+pub enum SFrameError {
+    /// The frame is too short or the header is malformed.
+    InvalidHeader,
+    /// The key provider has no key for the key id of the frame.
+    UnknownKeyId(u64),
+    /// The key provider has no current key to encrypt with.
+    NoKey,
+    /// The frame failed the integrity check, it was tampered with or
+    /// encrypted with a different key.
+    AuthenticationFailed,
+}
+
+impl Error for SFrameError {}
+
+impl fmt::Display for SFrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "invalid sframe header"),
+            Self::UnknownKeyId(kid) => write!(f, "unknown sframe key id: {}", kid),
+            Self::NoKey => write!(f, "no sframe key to encrypt with"),
+            Self::AuthenticationFailed => write!(f, "sframe authentication failed"),
+        }
+    }
+}
+
+/// The source of the base keys, implemented by the key distribution.
+///
+/// Keys are identified by a key id which must not be reused for a
+/// different key. To rotate, make a new key id current, receivers look up
+/// the new key when the first frame using it arrives.
+pub trait KeyProvider: Send + Sync {
+    /// The key id senders encrypt with, `None` drops outgoing frames.
+    fn current_key_id(&self) -> Option<u64>;
+    /// The base key of a key id.
+    fn key(&self, key_id: u64) -> Option<Vec<u8>>;
+    /// Called when a frame with an unknown key id has been received, e.g.
+    /// to request the key from the distribution service.
+    #[allow(unused)]
+    fn on_missing_key(&self, key_id: u64) {}
+}
+
+impl<T: KeyProvider> KeyProvider for Arc<T> {
+    fn current_key_id(&self) -> Option<u64> {
+        self.as_ref().current_key_id()
+    }
+
+    fn key(&self, key_id: u64) -> Option<Vec<u8>> {
+        self.as_ref().key(key_id)
+    }
+
+    fn on_missing_key(&self, key_id: u64) {
+        self.as_ref().on_missing_key(key_id)
+    }
+}
+
+/// A key provider holding the keys in memory.
+#[derive(Default)]
+pub struct MemoryKeyProvider {
+    keys: RwLock<HashMap<u64, Vec<u8>>>,
+    current: RwLock<Option<u64>>,
+}
+
+impl MemoryKeyProvider {
+    pub fn set_key(&self, key_id: u64, key: &[u8]) {
+        self.keys.write().unwrap().insert(key_id, key.to_vec());
+    }
+
+    pub fn remove_key(&self, key_id: u64) {
+        self.keys.write().unwrap().remove(&key_id);
+    }
+
+    /// Make a key the one senders encrypt with.
+    pub fn set_current_key_id(&self, key_id: u64) {
+        let _ = self.current.write().unwrap().insert(key_id);
+    }
+}
+
+impl KeyProvider for MemoryKeyProvider {
+    fn current_key_id(&self) -> Option<u64> {
+        *self.current.read().unwrap()
+    }
+
+    fn key(&self, key_id: u64) -> Option<Vec<u8>> {
+        self.keys.read().unwrap().get(&key_id).cloned()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SFrameOptions {
+    pub cipher_suite: CipherSuite,
+    /// The low bits of the sframe key id used for the ratchet generation,
+    /// zero disables ratcheting. The key ids of the key provider are
+    /// shifted up by this many bits.
+    pub ratchet_bits: u8,
+}
+
+impl Default for SFrameOptions {
+    fn default() -> Self {
+        Self {
+            cipher_suite: CipherSuite::AesGcm128Sha256,
+            ratchet_bits: 0,
+        }
+    }
+}
+
+impl SFrameOptions {
+    fn generation_mask(&self) -> u64 {
+        (1u64 << self.ratchet_bits.min(63)) - 1
+    }
+
+    fn kid(&self, key_id: u64, generation: u64) -> u64 {
+        (key_id << self.ratchet_bits.min(63)) | (generation & self.generation_mask())
+    }
+}
+
+/// The frame counters of an encryptor or a decryptor.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SFrameStats {
+    /// Frames encrypted or decrypted.
+    pub frames: u64,
+    /// Frames dropped because there was no key for them.
+    pub missing_key: u64,
+    /// Frames dropped because they failed to parse or authenticate.
+    pub invalid: u64,
+}
+
+#[derive(Default)]
+struct AtomicStats {
+    frames: AtomicU64,
+    missing_key: AtomicU64,
+    invalid: AtomicU64,
+}
+
+impl AtomicStats {
+    fn load(&self) -> SFrameStats {
+        SFrameStats {
+            frames: self.frames.load(Ordering::Relaxed),
+            missing_key: self.missing_key.load(Ordering::Relaxed),
+            invalid: self.invalid.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count<T>(&self, result: &Result<T, SFrameError>) {
+        let counter = match result {
+            Ok(_) => &self.frames,
+            Err(SFrameError::NoKey | SFrameError::UnknownKeyId(_)) => &self.missing_key,
+            Err(_) => &self.invalid,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The key and salt derived for one sframe key id.
+struct KeyMaterial {
+    suite: CipherSuite,
+    key: Vec<u8>,
+    salt: [u8; 12],
+}
+
+impl KeyMaterial {
+    fn derive(suite: CipherSuite, kid: u64, base_key: &[u8]) -> Self {
+        let label = |name: &str| {
+            let mut info = name.as_bytes().to_vec();
+            info.extend_from_slice(&kid.to_be_bytes());
+            info.extend_from_slice(&(suite as u16).to_be_bytes());
+            info
+        };
+
+        let key = suite.hkdf_expand(base_key, &label("SFrame 1.0 Secret key "), suite.key_len());
+        let mut salt = [0u8; 12];
+        salt.copy_from_slice(&suite.hkdf_expand(base_key, &label("SFrame 1.0 Secret salt "), 12));
+
+        Self { suite, key, salt }
+    }
+
+    fn nonce(&self, ctr: u64) -> [u8; 12] {
+        let mut nonce = self.salt;
+        for (n, c) in nonce[4..].iter_mut().zip(ctr.to_be_bytes()) {
+            *n ^= c;
+        }
+
+        nonce
+    }
+
+    /// The tag of the ctr suites, over the lengths, the nonce, the header
+    /// and the ciphertext.
+    fn ctr_tag(&self, nonce: &[u8], aad: &[u8], ct: &[u8]) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key[16..]).unwrap();
+        mac.update(&(aad.len() as u64).to_be_bytes());
+        mac.update(&(ct.len() as u64).to_be_bytes());
+        mac.update(&(self.suite.tag_len() as u64).to_be_bytes());
+        mac.update(nonce);
+        mac.update(aad);
+        mac.update(ct);
+        mac
+    }
+
+    fn ctr_apply(&self, nonce: &[u8; 12], buf: &mut [u8]) {
+        let mut iv = [0u8; 16];
+        iv[..12].copy_from_slice(nonce);
+        ctr::Ctr128BE::<Aes128>::new(self.key[..16].into(), &iv.into()).apply_keystream(buf);
+    }
+
+    fn seal(&self, ctr: u64, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = self.nonce(ctr);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+
+        match self.suite {
+            CipherSuite::AesGcm128Sha256 => Aes128Gcm::new_from_slice(&self.key)
+                .unwrap()
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .expect("sframe encrypt failed"),
+            CipherSuite::AesGcm256Sha512 => Aes256Gcm::new_from_slice(&self.key)
+                .unwrap()
+                .encrypt(Nonce::from_slice(&nonce), payload)
+                .expect("sframe encrypt failed"),
+            _ => {
+                let mut ct = plaintext.to_vec();
+                self.ctr_apply(&nonce, &mut ct);
+                let tag = self.ctr_tag(&nonce, aad, &ct).finalize().into_bytes();
+                ct.extend_from_slice(&tag[..self.suite.tag_len()]);
+                ct
+            }
+        }
+    }
+
+    fn open(&self, ctr: u64, aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, SFrameError> {
+        let nonce = self.nonce(ctr);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+
+        match self.suite {
+            CipherSuite::AesGcm128Sha256 => Aes128Gcm::new_from_slice(&self.key)
+                .unwrap()
+                .decrypt(Nonce::from_slice(&nonce), payload)
+                .map_err(|_| SFrameError::AuthenticationFailed),
+            CipherSuite::AesGcm256Sha512 => Aes256Gcm::new_from_slice(&self.key)
+                .unwrap()
+                .decrypt(Nonce::from_slice(&nonce), payload)
+                .map_err(|_| SFrameError::AuthenticationFailed),
+            _ => {
+                let size = ciphertext
+                    .len()
+                    .checked_sub(self.suite.tag_len())
+                    .ok_or(SFrameError::InvalidHeader)?;
+                let (ct, tag) = ciphertext.split_at(size);
+                self.ctr_tag(&nonce, aad, ct)
+                    .verify_truncated_left(tag)
+                    .map_err(|_| SFrameError::AuthenticationFailed)?;
+
+                let mut pt = ct.to_vec();
+                self.ctr_apply(&nonce, &mut pt);
+                Ok(pt)
+            }
+        }
+    }
+}
+
+/// The minimal big endian length of a header value, at least one byte.
+fn value_len(value: u64) -> usize {
+    ((64 - value.leading_zeros() as usize).div_ceil(8)).max(1)
+}
+
+/// Write the sframe header, values below 8 are stored in the config byte.
+fn encode_header(kid: u64, ctr: u64) -> Vec<u8> {
+    let mut header = vec![0u8];
+    for (value, shift) in [(kid, 4), (ctr, 0)] {
+        if value < 8 {
+            header[0] |= (value as u8) << shift;
+        } else {
+            let len = value_len(value);
+            header[0] |= (0x08 | (len as u8 - 1)) << shift;
+            header.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+        }
+    }
+
+    header
+}
+
+/// Parse the sframe header into the key id, the counter and the header
+/// length.
+fn decode_header(frame: &[u8]) -> Result<(u64, u64, usize), SFrameError> {
+    let config = *frame.first().ok_or(SFrameError::InvalidHeader)?;
+    let mut offset = 1;
+    let mut values = [0u64; 2];
+    for (value, bits) in values.iter_mut().zip([config >> 4, config & 0x0f]) {
+        if bits & 0x08 == 0 {
+            *value = bits as u64;
+        } else {
+            let len = (bits & 0x07) as usize + 1;
+            let bytes = frame
+                .get(offset..offset + len)
+                .ok_or(SFrameError::InvalidHeader)?;
+            *value = bytes.iter().fold(0, |acc, b| (acc << 8) | *b as u64);
+            offset += len;
+        }
+    }
+
+    Ok((values[0], values[1], offset))
+}
+
+struct EncryptorState {
+    key_id: Option<u64>,
+    generation: u64,
+    base_key: Vec<u8>,
+    material: Option<KeyMaterial>,
+    counter: u64,
+}
+
+/// Encrypts outgoing frames with SFrame (RFC 9605), install it as the
+/// transform of a sender.
+///
+/// The current key of the key provider is picked up on every frame, so
+/// rotating keys only takes changing the current key id of the provider.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use librtc::*;
+/// # fn encrypt(
+/// #     pc: &RTCPeerConnection,
+/// #     track: MediaStreamTrack,
+/// #     stream: Arc<MediaStream>,
+/// # ) -> Result<(), RTCError> {
+/// let keys = Arc::new(MemoryKeyProvider::default());
+/// keys.set_key(1, b"a secret shared by all participants");
+/// keys.set_current_key_id(1);
+///
+/// let sender = pc.add_track(track, stream)?;
+/// sender.set_transform(SFrameEncryptor::new(keys.clone(), SFrameOptions::default()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SFrameEncryptor {
+    provider: Arc<dyn KeyProvider>,
+    options: SFrameOptions,
+    state: Arc<Mutex<EncryptorState>>,
+    stats: Arc<AtomicStats>,
+}
+
+impl SFrameEncryptor {
+    pub fn new<T: KeyProvider + 'static>(provider: T, options: SFrameOptions) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(EncryptorState {
+                key_id: None,
+                generation: 0,
+                base_key: Vec::new(),
+                material: None,
+                counter: 0,
+            })),
+            stats: Arc::default(),
+            provider: Arc::new(provider),
+            options,
+        }
+    }
+
+    /// Advance the current key to the next ratchet generation, receivers
+    /// follow by ratcheting the same key. Returns false if ratcheting is
+    /// disabled or there is no current key yet.
+    pub fn ratchet(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let key_id = match state.key_id {
+            Some(key_id) if self.options.ratchet_bits > 0 => key_id,
+            _ => return false,
+        };
+
+        let suite = self.options.cipher_suite;
+        state.base_key = suite.ratchet(&state.base_key);
+        state.generation = (state.generation + 1) & self.options.generation_mask();
+        let kid = self.options.kid(key_id, state.generation);
+        state.material = Some(KeyMaterial::derive(suite, kid, &state.base_key));
+        true
+    }
+
+    /// Encrypt a payload into an sframe.
+    pub fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>, SFrameError> {
+        let ret = self.seal(payload);
+        self.stats.count(&ret);
+        ret
+    }
+
+    pub fn stats(&self) -> SFrameStats {
+        self.stats.load()
+    }
+
+    fn seal(&self, payload: &[u8]) -> Result<Vec<u8>, SFrameError> {
+        let key_id = self.provider.current_key_id().ok_or(SFrameError::NoKey)?;
+        let mut state = self.state.lock().unwrap();
+        if state.key_id != Some(key_id) {
+            let base_key = self.provider.key(key_id).ok_or(SFrameError::NoKey)?;
+            let kid = self.options.kid(key_id, 0);
+            state.material = Some(KeyMaterial::derive(
+                self.options.cipher_suite,
+                kid,
+                &base_key,
+            ));
+
+            state.key_id = Some(key_id);
+            state.base_key = base_key;
+            state.generation = 0;
+        }
+
+        let kid = self.options.kid(key_id, state.generation);
+        let ctr = state.counter;
+        state.counter += 1;
+
+        let mut frame = encode_header(kid, ctr);
+        let material = state.material.as_ref().ok_or(SFrameError::NoKey)?;
+        let ciphertext = material.seal(ctr, &frame, payload);
+        frame.extend_from_slice(&ciphertext);
+        Ok(frame)
+    }
+}
+
+impl EncodedFrameTransform for SFrameEncryptor {
+    fn transform(&self, mut frame: EncodedFrame, sink: &EncodedFrameSink) {
+        // Frames without a key are dropped, sending them in the clear
+        // would defeat the purpose.
+        if let Ok(data) = self.encrypt(frame.data()) {
+            frame.set_data(data);
+            sink.send(frame);
+        }
+    }
+}
+
+/// The ratchet of a key id of the provider.
+struct Ratchet {
+    // The key of the provider the ratchet started from.
+    key: Vec<u8>,
+    generation: u64,
+    base_key: Vec<u8>,
+}
+
+#[derive(Default)]
+struct DecryptorState {
+    // Derived keys by sframe key id, the most recent first.
+    keys: Vec<(u64, KeyMaterial)>,
+    // The newest authenticated ratchet generation of each key id of the
+    // provider.
+    ratchets: HashMap<u64, Ratchet>,
+}
+
+/// Decrypts incoming sframes, install it as the transform of a receiver.
+///
+/// Frames with a key id the key provider does not know are dropped and
+/// reported to the provider, decryption resumes as soon as the provider
+/// has the key. Frames that fail to authenticate are dropped without
+/// touching the keys, so an injected frame cannot disturb decryption.
+#[derive(Clone)]
+pub struct SFrameDecryptor {
+    provider: Arc<dyn KeyProvider>,
+    options: SFrameOptions,
+    state: Arc<Mutex<DecryptorState>>,
+    stats: Arc<AtomicStats>,
+}
+
+impl SFrameDecryptor {
+    pub fn new<T: KeyProvider + 'static>(provider: T, options: SFrameOptions) -> Self {
+        Self {
+            state: Arc::default(),
+            stats: Arc::default(),
+            provider: Arc::new(provider),
+            options,
+        }
+    }
+
+    /// Decrypt an sframe into the payload.
+    pub fn decrypt(&self, frame: &[u8]) -> Result<Vec<u8>, SFrameError> {
+        let ret = self.open(frame);
+        self.stats.count(&ret);
+        ret
+    }
+
+    pub fn stats(&self) -> SFrameStats {
+        self.stats.load()
+    }
+
+    fn open(&self, frame: &[u8]) -> Result<Vec<u8>, SFrameError> {
+        let (kid, ctr, header_len) = decode_header(frame)?;
+        let (header, ciphertext) = frame.split_at(header_len);
+        let key_id = kid >> self.options.ratchet_bits.min(63);
+
+        let mut state = self.state.lock().unwrap();
+        let ret = match state.keys.iter().find(|(k, _)| *k == kid) {
+            Some((_, material)) => material.open(ctr, header, ciphertext),
+            None => {
+                let (material, ratchet) = self.derive(&mut state, kid, key_id)?;
+                let ret = material.open(ctr, header, ciphertext);
+
+                // Only an authenticated frame moves the ratchet forward.
+                if ret.is_ok() {
+                    state.ratchets.insert(key_id, ratchet);
+                    state.keys.insert(0, (kid, material));
+                    state.keys.truncate(KEY_CACHE_SIZE);
+                }
+
+                ret
+            }
+        };
+
+        if ret.is_err() {
+            self.reload(&mut state, key_id);
+        }
+
+        ret
+    }
+
+    /// Derive the key of an sframe key id, ratcheting the base key of the
+    /// provider forward to the generation of the key id. The ratchet is
+    /// returned instead of stored, the frame is not authenticated yet.
+    fn derive(
+        &self,
+        state: &mut DecryptorState,
+        kid: u64,
+        key_id: u64,
+    ) -> Result<(KeyMaterial, Ratchet), SFrameError> {
+        let ratchet = match state.ratchets.entry(key_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let Some(key) = self.provider.key(key_id) else {
+                    self.provider.on_missing_key(key_id);
+                    return Err(SFrameError::UnknownKeyId(kid));
+                };
+
+                entry.insert(Ratchet {
+                    base_key: key.clone(),
+                    generation: 0,
+                    key,
+                })
+            }
+        };
+
+        let mask = self.options.generation_mask();
+        let target = kid & mask;
+        if target.wrapping_sub(ratchet.generation) & mask > MAX_RATCHET_STEPS {
+            return Err(SFrameError::UnknownKeyId(kid));
+        }
+
+        let suite = self.options.cipher_suite;
+        let mut generation = ratchet.generation;
+        let mut base_key = ratchet.base_key.clone();
+        while generation != target {
+            base_key = suite.ratchet(&base_key);
+            generation = (generation + 1) & mask;
+        }
+
+        Ok((
+            KeyMaterial::derive(suite, kid, &base_key),
+            Ratchet {
+                key: ratchet.key.clone(),
+                generation,
+                base_key,
+            },
+        ))
+    }
+
+    /// Start over from the key of the provider if it was replaced under the
+    /// same key id, otherwise the keys are kept.
+    fn reload(&self, state: &mut DecryptorState, key_id: u64) {
+        let Some(ratchet) = state.ratchets.get(&key_id) else {
+            return;
+        };
+
+        match self.provider.key(key_id) {
+            Some(key) if key != ratchet.key => {
+                let shift = self.options.ratchet_bits.min(63);
+                state.keys.retain(|(kid, _)| kid >> shift != key_id);
+                state.ratchets.insert(
+                    key_id,
+                    Ratchet {
+                        base_key: key.clone(),
+                        generation: 0,
+                        key,
+                    },
+                );
+            }
+            _ => (),
+        }
+    }
+}
+
+impl EncodedFrameTransform for SFrameDecryptor {
+    fn transform(&self, mut frame: EncodedFrame, sink: &EncodedFrameSink) {
+        if let Ok(data) = self.decrypt(frame.data()) {
+            frame.set_data(data);
+            sink.send(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    fn hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&value[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn keys(key_id: u64, key: &[u8]) -> Arc<MemoryKeyProvider> {
+        let keys = Arc::new(MemoryKeyProvider::default());
+        keys.set_key(key_id, key);
+        keys.set_current_key_id(key_id);
+        keys
+    }
+
+    fn options(cipher_suite: CipherSuite, ratchet_bits: u8) -> SFrameOptions {
+        SFrameOptions {
+            cipher_suite,
+            ratchet_bits,
+        }
+    }
+
+    // RFC 9605, appendix C.4.
+    #[test]
+    fn rfc9605_vectors() {
+        let vectors = [
+            (
+                CipherSuite::AesCtr128HmacSha256_80,
+                "3f7d9a7c83ae8e1c8a11ae695ab59314b367e359fadac7b9c46b2bc6f81f46e1\
+                 6b96f0811868d59402b7e870102720b3",
+                "50b29329a04dc0f184ac3168",
+                "9901234567449408b6f490086165b9d6f62b24ae1a59a56486b4ae8ed036b889\
+                 12e24f11",
+            ),
+            (
+                CipherSuite::AesCtr128HmacSha256_64,
+                "e2ec5c797540310483b16bf6e7a570d2a27d192fe869c7ccd8584a8d9dab9154\
+                 9fbe553f5113461ec6aa83bf3865553e",
+                "e68ac8dd3d02fbcd368c5577",
+                "99012345673f31438db4d09434e43afa0f8a2f00867a2be085046a9f5cb4f101\
+                 d607",
+            ),
+            (
+                CipherSuite::AesCtr128HmacSha256_32,
+                "2c5703089cbb8c583475e4fc461d97d18809df79b6d550f78eb6d50ffa80d892\
+                 11d57909934f46f5405e38cd583c69fe",
+                "38c16e4f5159700c00c7f350",
+                "990123456717fc8af28a5a695afcfc6c8df6358a17e26b2fcb3bae32e443",
+            ),
+            (
+                CipherSuite::AesGcm128Sha256,
+                "d34f547f4ca4f9a7447006fe7fcbf768",
+                "75234edefe07819026751816",
+                "9901234567b7412c2513a1b66dbb48841bbaf17f598751176ad847681a69c6d0\
+                 b091c07018ce4adb34eb",
+            ),
+            (
+                CipherSuite::AesGcm256Sha512,
+                "d3e27b0d4a5ae9e55df01a70e6d4d28d969b246e2936f4b7a5d9b494da6b9633",
+                "84991c167b8cd23c93708ec7",
+                "990123456794f509d36e9beacb0e261d99c7d1e972f1fed787d4049f17ca2135\
+                 3c1cc24d56ceabced279",
+            ),
+        ];
+
+        let (kid, ctr) = (0x123, 0x4567);
+        let base_key = hex("000102030405060708090a0b0c0d0e0f");
+        let metadata = b"IETF SFrame WG";
+        let plaintext = b"draft-ietf-sframe-enc";
+        for (suite, key, salt, ct) in vectors {
+            let material = KeyMaterial::derive(suite, kid, &base_key);
+            assert_eq!(material.key, hex(key), "{:?}", suite);
+            assert_eq!(material.salt.to_vec(), hex(salt), "{:?}", suite);
+
+            let mut aad = encode_header(kid, ctr);
+            aad.extend_from_slice(metadata);
+
+            let mut frame = encode_header(kid, ctr);
+            frame.extend_from_slice(&material.seal(ctr, &aad, plaintext));
+            assert_eq!(frame, hex(ct), "{:?}", suite);
+
+            let (_, _, header_len) = decode_header(&frame).unwrap();
+            let opened = material.open(ctr, &aad, &frame[header_len..]).unwrap();
+            assert_eq!(opened, plaintext);
+        }
+    }
+
+    #[test]
+    fn header() {
+        let vectors = [
+            (0, 0, "00"),
+            (0, 7, "07"),
+            (0, 8, "0808"),
+            (7, 0, "70"),
+            (8, 0, "8008"),
+            (0x123, 0x4567, "9901234567"),
+            (0xff, 0x100, "89ff0100"),
+            (0x10000, 1, "a1010000"),
+            (u64::MAX, u64::MAX, "ffffffffffffffffffffffffffffffffff"),
+        ];
+
+        for (kid, ctr, header) in vectors {
+            assert_eq!(encode_header(kid, ctr), hex(header));
+            assert_eq!(
+                decode_header(&hex(header)).unwrap(),
+                (kid, ctr, header.len() / 2)
+            );
+        }
+
+        for kid in (0..64).map(|shift| 1u64 << shift) {
+            for ctr in [0, 1, kid - 1, kid, kid.wrapping_mul(3)] {
+                let mut frame = encode_header(kid, ctr);
+                let len = frame.len();
+                frame.extend_from_slice(b"payload");
+                assert_eq!(decode_header(&frame).unwrap(), (kid, ctr, len));
+            }
+        }
+
+        assert!(decode_header(&[]).is_err());
+        assert!(decode_header(&hex("9901")).is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        let suites = [
+            CipherSuite::AesCtr128HmacSha256_80,
+            CipherSuite::AesCtr128HmacSha256_64,
+            CipherSuite::AesCtr128HmacSha256_32,
+            CipherSuite::AesGcm128Sha256,
+            CipherSuite::AesGcm256Sha512,
+        ];
+
+        for suite in suites {
+            let keys = keys(3, b"base key");
+            let encryptor = SFrameEncryptor::new(keys.clone(), options(suite, 0));
+            let decryptor = SFrameDecryptor::new(keys, options(suite, 0));
+            for size in [0, 1, 100, 1500] {
+                let payload = vec![size as u8; size];
+                let frame = encryptor.encrypt(&payload).unwrap();
+                // The key id and the counter fit in the config byte.
+                assert_eq!(frame.len(), 1 + payload.len() + suite.tag_len());
+                assert_eq!(decryptor.decrypt(&frame).unwrap(), payload);
+            }
+
+            assert_eq!(decryptor.stats().frames, 4);
+        }
+    }
+
+    #[test]
+    fn ratchet() {
+        let keys = keys(1, b"base key");
+        let options = options(CipherSuite::AesGcm128Sha256, 4);
+        let encryptor = SFrameEncryptor::new(keys.clone(), options);
+        let decryptor = SFrameDecryptor::new(keys.clone(), options);
+
+        // Ratcheting needs a current key and generation bits.
+        assert!(!SFrameEncryptor::new(MemoryKeyProvider::default(), options).ratchet());
+        let disabled = SFrameEncryptor::new(keys, SFrameOptions::default());
+        disabled.encrypt(b"media").unwrap();
+        assert!(!disabled.ratchet());
+
+        // The generation wraps around after 16 ratchets.
+        let frame = encryptor.encrypt(b"first").unwrap();
+        for generation in 1..40u64 {
+            assert!(encryptor.ratchet());
+
+            let frame = encryptor.encrypt(b"media").unwrap();
+            let (kid, _, _) = decode_header(&frame).unwrap();
+            assert_eq!(kid, (1 << 4) | (generation % 16));
+            assert_eq!(decryptor.decrypt(&frame).unwrap(), b"media");
+        }
+
+        // The previous generations can not be derived again, only the keys
+        // still in the cache decrypt.
+        assert!(decryptor.decrypt(&frame).is_err());
+
+        // A receiver that missed generations catches up.
+        for _ in 0..5 {
+            encryptor.ratchet();
+        }
+
+        let frame = encryptor.encrypt(b"media").unwrap();
+        assert_eq!(decryptor.decrypt(&frame).unwrap(), b"media");
+    }
+
+    #[test]
+    fn injected_frames() {
+        let keys = keys(1, b"base key");
+        let options = options(CipherSuite::AesCtr128HmacSha256_80, 16);
+        let encryptor = SFrameEncryptor::new(keys.clone(), options);
+        let decryptor = SFrameDecryptor::new(keys, options);
+
+        // Follow the sender past the ratchet limit of a fresh receiver.
+        for _ in 0..MAX_RATCHET_STEPS + 10 {
+            encryptor.ratchet();
+            let frame = encryptor.encrypt(b"media").unwrap();
+            decryptor.decrypt(&frame).unwrap();
+        }
+
+        let frame = encryptor.encrypt(b"media").unwrap();
+        let (kid, ctr, _) = decode_header(&frame).unwrap();
+
+        // Garbage with the current key id, and with a key id of a future
+        // generation, which must not move the ratchet.
+        for kid in [kid, kid + 100] {
+            let mut garbage = encode_header(kid, ctr + 1);
+            garbage.extend_from_slice(&[0x55; 64]);
+            assert!(matches!(
+                decryptor.decrypt(&garbage),
+                Err(SFrameError::AuthenticationFailed)
+            ));
+        }
+
+        assert_eq!(decryptor.decrypt(&frame).unwrap(), b"media");
+        encryptor.ratchet();
+        let frame = encryptor.encrypt(b"media").unwrap();
+        assert_eq!(decryptor.decrypt(&frame).unwrap(), b"media");
+
+        let stats = decryptor.stats();
+        assert_eq!(stats.invalid, 2);
+        assert_eq!(stats.frames, MAX_RATCHET_STEPS + 12);
+    }
+
+    #[derive(Default)]
+    struct CountingProvider {
+        keys: MemoryKeyProvider,
+        missing: AtomicUsize,
+    }
+
+    impl KeyProvider for CountingProvider {
+        fn current_key_id(&self) -> Option<u64> {
+            self.keys.current_key_id()
+        }
+
+        fn key(&self, key_id: u64) -> Option<Vec<u8>> {
+            self.keys.key(key_id)
+        }
+
+        fn on_missing_key(&self, _: u64) {
+            self.missing.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn unknown_key_id() {
+        let options = SFrameOptions::default();
+        let encryptor = SFrameEncryptor::new(keys(9, b"base key"), options);
+        let provider = Arc::new(CountingProvider::default());
+        let decryptor = SFrameDecryptor::new(provider.clone(), options);
+
+        let frame = encryptor.encrypt(b"media").unwrap();
+        assert!(matches!(
+            decryptor.decrypt(&frame),
+            Err(SFrameError::UnknownKeyId(9))
+        ));
+        assert_eq!(provider.missing.load(Ordering::Relaxed), 1);
+        assert_eq!(decryptor.stats().missing_key, 1);
+
+        provider.keys.set_key(9, b"base key");
+        assert_eq!(decryptor.decrypt(&frame).unwrap(), b"media");
+
+        let encryptor = SFrameEncryptor::new(MemoryKeyProvider::default(), options);
+        assert!(matches!(
+            encryptor.encrypt(b"media"),
+            Err(SFrameError::NoKey)
+        ));
+    }
+
+    #[test]
+    fn rotation() {
+        let keys = keys(1, b"first key");
+        let options = SFrameOptions::default();
+        let encryptor = SFrameEncryptor::new(keys.clone(), options);
+        let decryptor = SFrameDecryptor::new(keys.clone(), options);
+
+        let first = encryptor.encrypt(b"first").unwrap();
+        keys.set_key(2, b"second key");
+        keys.set_current_key_id(2);
+        let second = encryptor.encrypt(b"second").unwrap();
+
+        // Frames of both keys decrypt during the rotation, in any order.
+        assert_eq!(decryptor.decrypt(&second).unwrap(), b"second");
+        assert_eq!(decryptor.decrypt(&first).unwrap(), b"first");
+
+        // A key replaced under the same key id is picked up after the first
+        // frame that fails with the old key.
+        keys.set_key(2, b"replaced key");
+        let encryptor = SFrameEncryptor::new(keys, options);
+        let replaced = encryptor.encrypt(b"replaced").unwrap();
+        assert!(decryptor.decrypt(&replaced).is_err());
+        assert_eq!(decryptor.decrypt(&replaced).unwrap(), b"replaced");
+        assert_eq!(decryptor.decrypt(&first).unwrap(), b"first");
+    }
+}
@@ -1,3 +1,13 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use crate::trace::{rtc_event, Span};
+
 /// A Sink is a value into which other values can be sent.
 pub trait SinkExt: Send {
     type Item;
@@ -18,4 +28,157 @@ impl<T> Sinker<T> {
             sink: Box::new(sink),
         }
     }
+
+    /// create a sink trait wrapper from a closure.
+    pub fn from_fn<F>(handler: F) -> Self
+    where
+        F: Fn(T) + Send + 'static,
+        T: 'static,
+    {
+        Self::new(FnSink {
+            handler,
+            _item: std::marker::PhantomData,
+        })
+    }
+}
+
+struct FnSink<F, T> {
+    handler: F,
+    _item: std::marker::PhantomData<fn(T)>,
+}
+
+impl<F: Fn(T) + Send, T> SinkExt for FnSink<F, T> {
+    type Item = T;
+
+    fn on_data(&self, item: T) {
+        (self.handler)(item)
+    }
+}
+
+/// Keeps a sink registered, the sink is removed when the handle is dropped.
+///
+/// Dropping the handle inside a callback of the sink it belongs to
+/// deadlocks, the removal waits for all running callbacks.
+#[must_use = "the sink is removed again when the handle is dropped"]
+pub struct SinkHandle {
+    remove: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl SinkHandle {
+    pub(crate) fn new<F: FnOnce() + Send + Sync + 'static>(remove: F) -> Self {
+        Self {
+            remove: Some(Box::new(remove)),
+        }
+    }
+
+    /// Keep the sink registered for the lifetime of its owner.
+    pub fn detach(mut self) {
+        self.remove.take();
+    }
+}
+
+impl Drop for SinkHandle {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            remove();
+        }
+    }
+}
+
+/// The key of a registered sink.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum SinkKey {
+    /// Chosen by the user with the deprecated id based api.
+    Id(u8),
+    /// Allocated for a sink handle.
+    Handle(u64),
+}
+
+struct SinkEntry<T> {
+    sinker: Sinker<T>,
+    // The items pushed to the sink so far.
+    #[cfg(feature = "tracing")]
+    frames: AtomicU64,
+}
+
+/// The sinks of a track or channel.
+///
+/// The owner attaches its native callback when the first sink is inserted
+/// and detaches it when the last one is removed.
+pub(crate) struct SinkRegistry<T> {
+    sinks: RwLock<HashMap<SinkKey, SinkEntry<T>>>,
+    keys: AtomicU64,
+    // The sink events are recorded in this span, it names what the sinks
+    // are registered on.
+    span: Span,
+}
+
+impl<T> SinkRegistry<T> {
+    pub fn new(span: Span) -> Self {
+        Self {
+            sinks: RwLock::new(HashMap::new()),
+            keys: AtomicU64::new(0),
+            span,
+        }
+    }
+
+    /// Allocate a key that is never used by another sink.
+    pub fn next_key(&self) -> SinkKey {
+        SinkKey::Handle(self.keys.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Insert a sink, a sink with the same key is replaced. `attach` is
+    /// called if this is the first sink.
+    pub fn insert<F: FnOnce()>(&self, key: SinkKey, sink: Sinker<T>, attach: F) {
+        let mut sinks = self.sinks.write().unwrap();
+        if sinks.is_empty() {
+            attach();
+        }
+
+        rtc_event!(DEBUG, parent: &self.span, sink = ?key, "sink added");
+        sinks.insert(
+            key,
+            SinkEntry {
+                sinker: sink,
+                #[cfg(feature = "tracing")]
+                frames: AtomicU64::new(0),
+            },
+        );
+    }
+
+    /// Remove a sink, `detach` is called if no sink is left.
+    pub fn remove<F: FnOnce()>(&self, key: SinkKey, detach: F) -> Option<Sinker<T>> {
+        let mut sinks = self.sinks.write().unwrap();
+        let entry = sinks.remove(&key);
+        if sinks.is_empty() {
+            detach();
+        }
+
+        let entry = entry?;
+        rtc_event!(
+            DEBUG,
+            parent: &self.span,
+            sink = ?key,
+            frames = entry.frames.load(Ordering::Relaxed),
+            "sink removed"
+        );
+
+        Some(entry.sinker)
+    }
+}
+
+impl<T: Clone> SinkRegistry<T> {
+    /// Push the item to every sink.
+    pub fn on_data(&self, item: T) {
+        #[allow(unused_variables)]
+        for (key, entry) in self.sinks.read().unwrap().iter() {
+            #[cfg(feature = "tracing")]
+            {
+                let frames = entry.frames.fetch_add(1, Ordering::Relaxed) + 1;
+                rtc_event!(TRACE, parent: &self.span, sink = ?key, frames, "sink frame");
+            }
+
+            entry.sinker.sink.on_data(item.clone());
+        }
+    }
 }
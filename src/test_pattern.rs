@@ -0,0 +1,549 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    pacer::Pacer, xorshift::XorShift, I420Buffer, MediaStreamError, SinkExt, VideoFrame,
+    VideoSource, VideoSourceDriver, VideoTrack,
+};
+
+/// Alternating cells at the start of the barcode, used to find the black
+/// and white levels.
+const MARKER: [bool; 4] = [true, false, true, false];
+
+/// Marker, 32 bits counter, 32 bits timestamp and 8 bits checksum.
+const BARCODE_CELLS: usize = MARKER.len() + 32 + 32 + 8;
+
+/// Minimum luma difference between the white and black marker cells.
+const MIN_CONTRAST: u32 = 48;
+
+const WHITE: [u8; 3] = [235, 128, 128];
+const BLACK: [u8; 3] = [16, 128, 128];
+
+/// 75% color bars in bt.601, left to right.
+const BARS: [[u8; 3]; 7] = [
+    [180, 128, 128],
+    [162, 44, 142],
+    [131, 156, 44],
+    [112, 72, 58],
+    [84, 184, 198],
+    [65, 100, 212],
+    [35, 212, 114],
+];
+
+/// 3x5 pixel digits, one bit per pixel row by row.
+const DIGITS: [u16; 10] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b111_001_111_100_111,
+    0b111_001_111_001_111,
+    0b101_101_111_001_001,
+    0b111_100_111_001_111,
+    0b111_100_111_101_111,
+    0b111_001_010_010_010,
+    0b111_101_111_101_111,
+    0b111_101_111_001_111,
+];
+
+/// The lower 32 bits of the unix time in milliseconds, enough to measure
+/// latencies of up to 49 days.
+fn wall_clock() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u32)
+        .unwrap_or(0)
+}
+
+fn checksum(counter: u32, timestamp: u32) -> u8 {
+    counter
+        .to_be_bytes()
+        .iter()
+        .chain(timestamp.to_be_bytes().iter())
+        .fold(0x5a, |sum, byte| sum.rotate_left(1) ^ byte)
+}
+
+/// The picture drawn by a test pattern source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    /// SMPTE color bars.
+    ColorBars,
+    /// A box bouncing across a gray background with the frame counter
+    /// printed in the middle.
+    MovingBox,
+    /// A solid yuv color.
+    Solid([u8; 3]),
+    /// Random luma noise, the worst case for the encoder.
+    Noise,
+}
+
+/// Test pattern source configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct TestPatternOptions {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    pub pattern: TestPattern,
+}
+
+impl Default for TestPatternOptions {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            fps: 30,
+            pattern: TestPattern::ColorBars,
+        }
+    }
+}
+
+/// A video source that draws synthetic test patterns.
+///
+/// Every frame carries a barcode strip at the top with the frame counter and
+/// the wall clock time it was drawn at. The barcode survives scaling and
+/// lossy encoding, the receive side decodes it with `TestPatternStamp` or
+/// `TestPatternAnalyzer` to measure frame loss and latency.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// let source = TestPatternSource::new(TestPatternOptions::default());
+/// let (track, driver) = source.into_track("pattern")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TestPatternSource {
+    options: TestPatternOptions,
+    counter: u32,
+    pacer: Pacer,
+    rng: XorShift,
+}
+
+impl TestPatternSource {
+    pub fn new(options: TestPatternOptions) -> Self {
+        assert!(options.fps > 0);
+        assert!(options.width >= 2 && options.height >= 2);
+
+        Self {
+            pacer: Pacer::new(Duration::from_secs(1) / options.fps),
+            rng: XorShift::new(options.width as u64 * options.height as u64),
+            counter: 0,
+            options,
+        }
+    }
+
+    /// Start a driver that pushes the pattern into a new video track, the
+    /// track lives as long as the driver keeps running.
+    pub fn into_track(
+        self,
+        label: &str,
+    ) -> Result<(Arc<VideoTrack>, VideoSourceDriver), MediaStreamError> {
+        let track = VideoTrack::new(label)?;
+        let driver = VideoSourceDriver::spawn(self, track.clone());
+        Ok((track, driver))
+    }
+
+    /// Draw the next frame without waiting.
+    pub fn render(&mut self) -> I420Buffer {
+        let (width, height) = (self.options.width, self.options.height);
+        let mut buffer = I420Buffer::new(width, height);
+        match self.options.pattern {
+            TestPattern::ColorBars => draw_color_bars(&mut buffer),
+            TestPattern::Solid(color) => buffer.fill(color),
+            TestPattern::Noise => {
+                let (y, _, _) = buffer.planes_mut();
+                for chunk in y.chunks_mut(8) {
+                    let value = self.rng.next_u64().to_le_bytes();
+                    chunk.copy_from_slice(&value[..chunk.len()]);
+                }
+            }
+            TestPattern::MovingBox => {
+                buffer.fill([96, 128, 128]);
+
+                let size = (height / 4) & !1;
+                let travel = width.saturating_sub(size).max(1);
+                let step = (width / 60).max(2) as u64 * self.counter as u64;
+                let position = (step % (travel as u64 * 2)) as u32;
+                let x = if position < travel {
+                    position
+                } else {
+                    travel * 2 - position
+                };
+
+                buffer.fill_rect(x, (height - size) / 2, size, size, [210, 16, 146]);
+                draw_number(&mut buffer, self.counter, height / 60);
+            }
+        }
+
+        draw_barcode(&mut buffer, self.counter, wall_clock());
+        self.counter = self.counter.wrapping_add(1);
+        buffer
+    }
+}
+
+impl VideoSource for TestPatternSource {
+    fn next_frame(&mut self) -> Option<I420Buffer> {
+        self.pacer.wait();
+        Some(self.render())
+    }
+}
+
+fn draw_color_bars(buffer: &mut I420Buffer) {
+    let (width, height) = (buffer.width(), buffer.height());
+    let top = height * 2 / 3;
+    let middle = height * 3 / 4;
+    let bar = |index: u32| index * width / 7;
+
+    for (index, color) in BARS.iter().enumerate() {
+        let index = index as u32;
+        let x = bar(index);
+        buffer.fill_rect(x, 0, bar(index + 1) - x, top, *color);
+
+        // The castellations below are the bars in reverse order, with
+        // black in place of every other bar.
+        let reverse = match index % 2 {
+            0 => BARS[(6 - index) as usize],
+            _ => BLACK,
+        };
+
+        buffer.fill_rect(x, top, bar(index + 1) - x, middle - top, reverse);
+    }
+
+    // -I, 100% white, +Q and black.
+    let quarter = |index: u32| index * width * 5 / 28;
+    let bottom = height - middle;
+    buffer.fill_rect(0, middle, quarter(1), bottom, [58, 156, 97]);
+    buffer.fill_rect(quarter(1), middle, quarter(1), bottom, WHITE);
+    buffer.fill_rect(quarter(2), middle, quarter(1), bottom, [41, 168, 144]);
+    buffer.fill_rect(quarter(3), middle, width - quarter(3), bottom, BLACK);
+}
+
+fn draw_number(buffer: &mut I420Buffer, value: u32, scale: u32) {
+    let scale = scale.max(1) * 2;
+    let digits = value.to_string();
+    let advance = scale * 4;
+    let x = buffer.width().saturating_sub(advance * digits.len() as u32) / 2;
+    let y = buffer.height().saturating_sub(scale * 5) / 2;
+
+    for (index, digit) in digits.bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let left = x + index as u32 * advance;
+        for pixel in 0..15u32 {
+            if glyph & (1 << (14 - pixel)) != 0 {
+                let (column, row) = (pixel % 3, pixel / 3);
+                buffer.fill_rect(left + column * scale, y + row * scale, scale, scale, WHITE);
+            }
+        }
+    }
+}
+
+/// The height of the barcode strip for a frame height.
+fn barcode_height(height: u32) -> u32 {
+    (height / 16).max(2)
+}
+
+fn draw_barcode(buffer: &mut I420Buffer, counter: u32, timestamp: u32) {
+    let (width, height) = (buffer.width(), barcode_height(buffer.height()));
+    let checksum = checksum(counter, timestamp);
+    let bits = MARKER
+        .iter()
+        .copied()
+        .chain((0..32).rev().map(|bit| counter & (1 << bit) != 0))
+        .chain((0..32).rev().map(|bit| timestamp & (1 << bit) != 0))
+        .chain((0..8).rev().map(|bit| checksum & (1 << bit) != 0));
+
+    for (index, bit) in bits.enumerate() {
+        let x = index as u32 * width / BARCODE_CELLS as u32;
+        let next = (index as u32 + 1) * width / BARCODE_CELLS as u32;
+        buffer.fill_rect(x, 0, next - x, height, if bit { WHITE } else { BLACK });
+    }
+}
+
+/// The frame counter and send time carried by a test pattern frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TestPatternStamp {
+    /// The number of the frame, counting from 0.
+    pub counter: u32,
+    /// The lower 32 bits of the unix time in milliseconds the frame was
+    /// drawn at.
+    pub timestamp: u32,
+}
+
+impl TestPatternStamp {
+    /// Decode the barcode of a received frame, returns `None` if the frame
+    /// has no valid barcode.
+    pub fn decode(frame: &VideoFrame) -> Option<Self> {
+        Self::decode_luma(
+            frame.data_y(),
+            frame.stride_y(),
+            frame.width(),
+            frame.height(),
+        )
+    }
+
+    /// Decode the barcode of a buffer.
+    pub fn decode_buffer(buffer: &I420Buffer) -> Option<Self> {
+        Self::decode_luma(
+            buffer.data_y(),
+            buffer.stride_y(),
+            buffer.width(),
+            buffer.height(),
+        )
+    }
+
+    /// Time from drawing the frame until now, only meaningful if both sides
+    /// share the same clock, such as in a loopback test.
+    pub fn latency(&self) -> Duration {
+        let elapsed = wall_clock().wrapping_sub(self.timestamp) as i32;
+        Duration::from_millis(elapsed.max(0) as u64)
+    }
+
+    fn decode_luma(data: &[u8], stride: usize, width: u32, height: u32) -> Option<Self> {
+        let strip = barcode_height(height);
+        if width < BARCODE_CELLS as u32 || strip == 0 {
+            return None;
+        }
+
+        // Average the middle half of every cell, the edges are blurred by
+        // scaling and encoding.
+        let rows = (strip / 4)..(strip * 3 / 4).max(strip / 4 + 1);
+        let cells = (0..BARCODE_CELLS)
+            .map(|index| {
+                let x = index as u32 * width / BARCODE_CELLS as u32;
+                let next = (index as u32 + 1) * width / BARCODE_CELLS as u32;
+                let columns = (x + (next - x) / 4)..(next - (next - x) / 4).max(x + 1);
+
+                let mut sum = 0u32;
+                let mut count = 0u32;
+                for row in rows.clone() {
+                    let line = data.get(row as usize * stride..)?;
+                    for column in columns.clone() {
+                        sum += *line.get(column as usize)? as u32;
+                        count += 1;
+                    }
+                }
+
+                Some(sum / count.max(1))
+            })
+            .collect::<Option<Vec<u32>>>()?;
+
+        let white = (cells[0] + cells[2]) / 2;
+        let black = (cells[1] + cells[3]) / 2;
+        if white < black + MIN_CONTRAST {
+            return None;
+        }
+
+        let threshold = (white + black) / 2;
+        let mut bits = cells[MARKER.len()..].iter().map(|cell| *cell > threshold);
+        let mut read = |count: u32| {
+            (0..count).fold(0u32, |value, _| {
+                (value << 1) | bits.next().unwrap_or(false) as u32
+            })
+        };
+
+        let counter = read(32);
+        let timestamp = read(32);
+        if read(8) as u8 != checksum(counter, timestamp) {
+            return None;
+        }
+
+        Some(Self { counter, timestamp })
+    }
+}
+
+/// Statistics of the received test pattern frames.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TestPatternStats {
+    /// Frames that reached the analyzer.
+    pub frames_received: u64,
+    /// Frames without a readable barcode.
+    pub frames_undecodable: u64,
+    /// Frames that never arrived, including the frames the sender dropped
+    /// to adapt to the network.
+    pub frames_lost: u64,
+    /// Frames that arrived after a newer frame.
+    pub frames_reordered: u64,
+    /// Frames that arrived more than once.
+    pub frames_duplicated: u64,
+    /// The latest decoded frame counter.
+    pub last_counter: Option<u32>,
+    pub latency_min: Option<Duration>,
+    pub latency_max: Option<Duration>,
+    pub latency_avg: Option<Duration>,
+}
+
+struct AnalyzerState {
+    stats: TestPatternStats,
+    // Bit n is set if the frame n before the latest one has arrived.
+    seen: u64,
+    latency_sum: Duration,
+    latency_count: u32,
+}
+
+/// Decodes the barcode of every received frame and keeps statistics.
+///
+/// The analyzer is a video track sink, clones share the same statistics.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let remote_track = VideoTrack::new("remote")?;
+/// let analyzer = TestPatternAnalyzer::default();
+/// let _sink = remote_track.add_sink(Sinker::new(analyzer.clone()));
+///
+/// // later
+/// println!("{:?}", analyzer.stats());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TestPatternAnalyzer {
+    state: Arc<Mutex<AnalyzerState>>,
+}
+
+impl Default for TestPatternAnalyzer {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(AnalyzerState {
+                stats: TestPatternStats::default(),
+                seen: 0,
+                latency_sum: Duration::ZERO,
+                latency_count: 0,
+            })),
+        }
+    }
+}
+
+impl TestPatternAnalyzer {
+    /// Get a snapshot of the statistics.
+    pub fn stats(&self) -> TestPatternStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /// Account for one received frame.
+    pub fn process(&self, frame: &VideoFrame) -> Option<TestPatternStamp> {
+        let stamp = TestPatternStamp::decode(frame);
+        let mut state = self.state.lock().unwrap();
+        state.stats.frames_received += 1;
+
+        let stamp = match stamp {
+            Some(stamp) => stamp,
+            None => {
+                state.stats.frames_undecodable += 1;
+                return None;
+            }
+        };
+
+        let state = &mut *state;
+        let stats = &mut state.stats;
+        match stats.last_counter {
+            None => {
+                stats.last_counter = Some(stamp.counter);
+                state.seen = 1;
+            }
+            Some(last) => {
+                let distance = stamp.counter.wrapping_sub(last) as i32;
+                if distance > 0 {
+                    stats.frames_lost += distance as u64 - 1;
+                    stats.last_counter = Some(stamp.counter);
+                    state.seen = state.seen.checked_shl(distance as u32).unwrap_or(0) | 1;
+                } else {
+                    let age = distance.unsigned_abs();
+                    let bit = 1u64.checked_shl(age).unwrap_or(0);
+                    if state.seen & bit != 0 {
+                        stats.frames_duplicated += 1;
+                    } else {
+                        // A late frame was counted as lost when it was
+                        // skipped.
+                        state.seen |= bit;
+                        stats.frames_reordered += 1;
+                        stats.frames_lost = stats.frames_lost.saturating_sub(1);
+                    }
+                }
+            }
+        }
+
+        let latency = stamp.latency();
+        stats.latency_min = Some(stats.latency_min.map_or(latency, |min| min.min(latency)));
+        stats.latency_max = Some(stats.latency_max.map_or(latency, |max| max.max(latency)));
+        state.latency_sum += latency;
+        state.latency_count += 1;
+        stats.latency_avg = Some(state.latency_sum / state.latency_count);
+
+        Some(stamp)
+    }
+}
+
+impl SinkExt for TestPatternAnalyzer {
+    type Item = Arc<VideoFrame>;
+
+    fn on_data(&self, frame: Arc<VideoFrame>) {
+        self.process(frame.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_size() {
+        let patterns = [
+            TestPattern::ColorBars,
+            TestPattern::MovingBox,
+            TestPattern::Solid([16, 128, 128]),
+            TestPattern::Noise,
+        ];
+
+        for (width, height) in [(2, 2), (3, 5), (16, 128), (640, 480), (641, 479)] {
+            for pattern in patterns {
+                let mut source = TestPatternSource::new(TestPatternOptions {
+                    fps: 30,
+                    pattern,
+                    height,
+                    width,
+                });
+
+                for _ in 0..100 {
+                    let buffer = source.render();
+                    assert_eq!((buffer.width(), buffer.height()), (width, height));
+                }
+            }
+        }
+    }
+    #[test]
+    fn barcode_round_trip() {
+        let mut source = TestPatternSource::new(TestPatternOptions {
+            pattern: TestPattern::Noise,
+            ..Default::default()
+        });
+
+        let frames = (0..4)
+            .map(|i| source.render().scale(320, 240).into_frame(i * 33))
+            .collect::<Vec<_>>();
+
+        let analyzer = TestPatternAnalyzer::default();
+        for (index, frame) in frames.iter().enumerate() {
+            let stamp = analyzer.process(frame).unwrap();
+            assert_eq!(stamp.counter, index as u32);
+            assert!(stamp.latency() < Duration::from_secs(10));
+        }
+
+        // Skip a frame, then deliver it late and twice.
+        let late = source.render().into_frame(132);
+        analyzer.process(&source.render().into_frame(165)).unwrap();
+        analyzer.process(&late).unwrap();
+        analyzer.process(&late).unwrap();
+        assert!(analyzer
+            .process(&I420Buffer::new(320, 240).into_frame(198))
+            .is_none());
+
+        let stats = analyzer.stats();
+        assert_eq!(stats.frames_received, 8);
+        assert_eq!(stats.frames_undecodable, 1);
+        assert_eq!(stats.frames_lost, 0);
+        assert_eq!(stats.frames_reordered, 1);
+        assert_eq!(stats.frames_duplicated, 1);
+        assert_eq!(stats.last_counter, Some(5));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{f32::consts::PI, sync::Arc, time::Duration};
+
+use crate::{
+    xorshift::XorShift, AudioFrame, AudioSource, AudioSourceDriver, AudioTrack, MediaStreamError,
+};
+
+/// The row and column frequencies of the telephone keypad.
+fn dtmf_frequencies(digit: char) -> Option<(f32, f32)> {
+    let (row, column) = match digit.to_ascii_uppercase() {
+        '1' => (0, 0),
+        '2' => (0, 1),
+        '3' => (0, 2),
+        'A' => (0, 3),
+        '4' => (1, 0),
+        '5' => (1, 1),
+        '6' => (1, 2),
+        'B' => (1, 3),
+        '7' => (2, 0),
+        '8' => (2, 1),
+        '9' => (2, 2),
+        'C' => (2, 3),
+        '*' => (3, 0),
+        '0' => (3, 1),
+        '#' => (3, 2),
+        'D' => (3, 3),
+        _ => return None,
+    };
+
+    const ROWS: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+    const COLUMNS: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+    Some((ROWS[row], COLUMNS[column]))
+}
+
+/// The signal produced by a tone generator.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tone {
+    /// A sine wave with the given frequency in hertz.
+    Sine(f32),
+    /// A logarithmic sweep from one frequency to another, repeated every
+    /// `duration`. Both frequencies have to be above zero.
+    Sweep {
+        from: f32,
+        to: f32,
+        duration: Duration,
+    },
+    /// Uniform white noise.
+    WhiteNoise,
+    /// Digital silence.
+    Silence,
+    /// Dual tone beeps of the given keypad digits, each digit sounds for
+    /// `tone` followed by `gap` of silence, and the sequence repeats.
+    /// Characters that are not keypad digits are played as pauses.
+    Dtmf {
+        digits: String,
+        tone: Duration,
+        gap: Duration,
+    },
+}
+
+/// An audio source that synthesizes test signals.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// let generator = ToneGenerator::new(Tone::Sine(440.0), 48000, 1);
+/// let (track, driver) = generator.into_track("tone")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ToneGenerator {
+    tone: Tone,
+    sample_rate: usize,
+    channels: u8,
+    amplitude: f32,
+    // Position in samples per channel since the start.
+    position: u64,
+    phase: [f32; 2],
+    // The keypad frequencies of the dtmf digits, `None` for pauses.
+    dtmf: Vec<Option<(f32, f32)>>,
+    rng: XorShift,
+}
+
+impl ToneGenerator {
+    /// Create a generator, the amplitude defaults to -6dBFS.
+    pub fn new(tone: Tone, sample_rate: usize, channels: u8) -> Self {
+        assert!(channels > 0);
+        assert_eq!(sample_rate % 100, 0);
+        if let Tone::Sweep { from, to, .. } = &tone {
+            assert!(
+                *from > 0.0 && *to > 0.0,
+                "sweep frequencies must be positive"
+            );
+        }
+
+        let dtmf = match &tone {
+            Tone::Dtmf { digits, .. } => digits.chars().map(dtmf_frequencies).collect(),
+            _ => Vec::new(),
+        };
+
+        Self {
+            rng: XorShift::new(sample_rate as u64),
+            amplitude: 0.5,
+            phase: [0.0; 2],
+            position: 0,
+            sample_rate,
+            channels,
+            dtmf,
+            tone,
+        }
+    }
+
+    /// Set the linear peak amplitude, from 0.0 to 1.0 for full scale.
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+    }
+
+    /// Start a driver that pushes the generated audio into a new audio
+    /// track, the track lives as long as the driver keeps running.
+    pub fn into_track(
+        self,
+        label: &str,
+    ) -> Result<(Arc<AudioTrack>, AudioSourceDriver), MediaStreamError> {
+        let track = AudioTrack::new(label)?;
+        let driver = AudioSourceDriver::spawn(self, track.clone());
+        Ok((track, driver))
+    }
+
+    /// Advance the oscillator and return the next sine sample.
+    fn oscillate(&mut self, index: usize, frequency: f32) -> f32 {
+        let value = self.phase[index].sin();
+        self.phase[index] += 2.0 * PI * frequency / self.sample_rate as f32;
+        if self.phase[index] >= 2.0 * PI {
+            self.phase[index] -= 2.0 * PI;
+        }
+
+        value
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let position = self.position;
+        self.position += 1;
+
+        let rate = self.sample_rate as f64;
+        let oscillators = match &self.tone {
+            Tone::Sine(frequency) => Some((*frequency, None)),
+            Tone::Sweep { from, to, duration } => {
+                let length = (duration.as_secs_f64() * rate).max(1.0) as u64;
+                let progress = (position % length) as f32 / length as f32;
+                Some((from * (to / from).powf(progress), None))
+            }
+            Tone::WhiteNoise => return self.rng.next_f32(),
+            Tone::Silence => return 0.0,
+            Tone::Dtmf { tone, gap, .. } => {
+                let tone = (tone.as_secs_f64() * rate) as u64;
+                let period = (tone + (gap.as_secs_f64() * rate) as u64).max(1);
+                let count = self.dtmf.len().max(1) as u64;
+
+                let offset = position % (period * count);
+                match self.dtmf.get((offset / period) as usize).copied().flatten() {
+                    Some((low, high)) if offset % period < tone => Some((low, Some(high))),
+                    _ => None,
+                }
+            }
+        };
+
+        match oscillators {
+            Some((frequency, None)) => self.oscillate(0, frequency),
+            Some((low, Some(high))) => (self.oscillate(0, low) + self.oscillate(1, high)) * 0.5,
+            None => {
+                // Restart the oscillators, every beep starts at zero phase.
+                self.phase = [0.0; 2];
+                0.0
+            }
+        }
+    }
+}
+
+impl AudioSource for ToneGenerator {
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        let channels = self.channels as usize;
+        for frame in buf.chunks_exact_mut(channels) {
+            let sample = (self.next_sample() * self.amplitude * i16::MAX as f32) as i16;
+            frame.fill(sample);
+        }
+
+        buf.len() / channels * channels
+    }
+}
+
+/// Measure the level of one frequency in an audio frame with the goertzel
+/// algorithm, in dBFS of a full scale sine. Only the first channel is
+/// analyzed.
+///
+/// Used on the receive side to verify generated tones, a frame carrying the
+/// frequency measures close to the generator amplitude while other
+/// frequencies measure far below it.
+pub fn detect_tone(frame: &AudioFrame, frequency: f32) -> f32 {
+    let channels = (frame.channels() as usize).max(1);
+    let samples = frame.as_ref();
+    let count = samples.len() / channels;
+    if count == 0 {
+        return -127.0;
+    }
+
+    let coefficient = 2.0 * (2.0 * PI * frequency / frame.sample_rate() as f32).cos();
+    let (mut s1, mut s2) = (0f32, 0f32);
+    for sample in samples.iter().step_by(channels) {
+        let s0 = *sample as f32 / 32768.0 + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+
+    let power = (s1 * s1 + s2 * s2 - coefficient * s1 * s2).max(0.0);
+    let amplitude = 2.0 * power.sqrt() / count as f32;
+    if amplitude <= 0.0 {
+        -127.0
+    } else {
+        (20.0 * amplitude.log10()).max(-127.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "sweep frequencies must be positive")]
+    fn sweep_from_zero() {
+        ToneGenerator::new(
+            Tone::Sweep {
+                from: 0.0,
+                to: 1000.0,
+                duration: Duration::from_secs(1),
+            },
+            48000,
+            1,
+        );
+    }
+
+    #[test]
+    fn dtmf() {
+        let mut generator = ToneGenerator::new(
+            Tone::Dtmf {
+                digits: "1 #".to_string(),
+                tone: Duration::from_millis(100),
+                gap: Duration::from_millis(100),
+            },
+            8000,
+            1,
+        );
+
+        // One 10ms frame in the middle of every tone and gap.
+        let mut levels = Vec::new();
+        for index in 0..60 {
+            let mut buf = vec![0; 80];
+            generator.read(&mut buf);
+            if index % 10 == 5 {
+                let frame = AudioFrame::from_pcm(8000, 1, index * 10, buf);
+                levels.push((detect_tone(&frame, 697.0), detect_tone(&frame, 1477.0)));
+            }
+        }
+
+        let on = |level: f32| level > -20.0;
+        assert!(on(levels[0].0) && !on(levels[0].1));
+        for (low, high) in &levels[1..4] {
+            assert!(!on(*low) && !on(*high));
+        }
+        assert!(!on(levels[4].0) && on(levels[4].1));
+        assert!(!on(levels[5].0) && !on(levels[5].1));
+    }
+}
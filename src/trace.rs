@@ -0,0 +1,64 @@
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Span;
+
+/// Stands in for the span of a connection or a future.
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Span;
+
+/// A unique id of an instrumented object, such as a connection.
+#[cfg(feature = "tracing")]
+pub(crate) fn next_id() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// The macros take the `tracing` syntax with a `Level` name first, they
+// compile to nothing without the `tracing` feature, the fields are then
+// never evaluated and the parent is only borrowed.
+
+#[cfg(feature = "tracing")]
+macro_rules! rtc_span {
+    ($level:ident, parent: $parent:expr, $($args:tt)+) => {
+        tracing::span!(target: "librtc", parent: $parent, tracing::Level::$level, $($args)+)
+    };
+    ($level:ident, $($args:tt)+) => {
+        tracing::span!(target: "librtc", tracing::Level::$level, $($args)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! rtc_span {
+    ($level:ident, parent: $parent:expr, $($args:tt)+) => {{
+        let _ = $parent;
+        $crate::trace::Span
+    }};
+    ($($args:tt)+) => {
+        $crate::trace::Span
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! rtc_event {
+    ($level:ident, parent: $parent:expr, $($args:tt)+) => {
+        tracing::event!(target: "librtc", parent: $parent, tracing::Level::$level, $($args)+)
+    };
+    ($level:ident, $($args:tt)+) => {
+        tracing::event!(target: "librtc", tracing::Level::$level, $($args)+)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! rtc_event {
+    ($level:ident, parent: $parent:expr, $($args:tt)+) => {{
+        let _ = $parent;
+    }};
+    ($($args:tt)+) => {
+        ()
+    };
+}
+
+pub(crate) use rtc_event;
+pub(crate) use rtc_span;
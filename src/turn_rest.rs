@@ -0,0 +1,191 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, Weak},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::{RTCConfiguration, RTCIceServer, RTCPeerConnection};
+
+/// The first retry of a failed refresh, doubled up to `MAX_RETRY_INTERVAL`.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The username and credential of TURN REST credentials valid until
+/// `expiry`, in seconds since the unix epoch.
+fn credentials(expiry: u64, user: &str, shared_secret: &str) -> (String, String) {
+    let username = format!("{}:{}", expiry, user);
+
+    // Hmac takes keys of any size.
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(shared_secret.as_bytes()).unwrap();
+    mac.update(username.as_bytes());
+    (username, STANDARD.encode(mac.finalize().into_bytes()))
+}
+
+impl RTCIceServer {
+    /// A turn server with time-limited credentials of the TURN REST api,
+    /// the username is `expiry:user` and the credential is
+    /// `base64(HMAC-SHA1(shared_secret, username))`.
+    ///
+    /// The turn server accepts the credentials until `ttl` from now.
+    pub fn turn_rest(urls: Vec<String>, user: &str, shared_secret: &str, ttl: Duration) -> Self {
+        let expiry = (SystemTime::now() + ttl)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (username, credential) = credentials(expiry, user, shared_secret);
+
+        Self {
+            credential: Some(credential),
+            username: Some(username),
+            urls: Some(urls),
+        }
+    }
+}
+
+/// The parameters of TURN REST credentials, see `RTCIceServer::turn_rest`.
+#[derive(Clone, Debug)]
+pub struct TurnRestOptions {
+    pub urls: Vec<String>,
+    pub user: String,
+    pub shared_secret: String,
+    pub ttl: Duration,
+}
+
+impl TurnRestOptions {
+    /// A turn server with new credentials.
+    pub fn ice_server(&self) -> RTCIceServer {
+        RTCIceServer::turn_rest(self.urls.clone(), &self.user, &self.shared_secret, self.ttl)
+    }
+}
+
+/// Renews the TURN REST credentials of a connection before they expire.
+///
+/// The turn servers of the configuration with the urls of the options are
+/// replaced with new credentials through `set_configuration` when three
+/// quarters of the ttl have passed, a failed refresh is retried within
+/// seconds. The refresher stops when it is dropped or the connection is
+/// gone.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use librtc::*;
+/// # fn connect(secret: String, observer: impl Observer + 'static) -> Result<(), RTCError> {
+/// let turn = TurnRestOptions {
+///     urls: vec!["turn:turn.example.com".to_string()],
+///     user: "alice".to_string(),
+///     shared_secret: secret,
+///     ttl: Duration::from_secs(3600),
+/// };
+///
+/// let config = RTCConfiguration {
+///     ice_servers: Some(vec![turn.ice_server()]),
+///     ..Default::default()
+/// };
+///
+/// let pc = RTCPeerConnection::new(&config, observer)?;
+/// let _refresher = TurnRestRefresher::new(&pc, config, turn);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TurnRestRefresher {
+    closed: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl TurnRestRefresher {
+    pub fn new(
+        pc: &Arc<RTCPeerConnection>,
+        config: RTCConfiguration,
+        options: TurnRestOptions,
+    ) -> Self {
+        let closed = Arc::new((Mutex::new(false), Condvar::new()));
+        let pc = Arc::downgrade(pc);
+        let thread = {
+            let closed = closed.clone();
+            thread::spawn(move || refresh(pc, config, options, closed))
+        };
+
+        Self {
+            thread: Some(thread),
+            closed,
+        }
+    }
+}
+
+impl Drop for TurnRestRefresher {
+    fn drop(&mut self) {
+        let (closed, cvar) = &*self.closed;
+        *closed.lock().unwrap() = true;
+        cvar.notify_one();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn refresh(
+    pc: Weak<RTCPeerConnection>,
+    mut config: RTCConfiguration,
+    options: TurnRestOptions,
+    closed: Arc<(Mutex<bool>, Condvar)>,
+) {
+    let (closed, cvar) = &*closed;
+    let interval = (options.ttl * 3 / 4).max(Duration::from_secs(1));
+    let mut wait = interval;
+    let mut retry = RETRY_INTERVAL;
+    loop {
+        let (is_closed, _) = cvar
+            .wait_timeout_while(closed.lock().unwrap(), wait, |closed| !*closed)
+            .unwrap();
+        if *is_closed {
+            break;
+        }
+
+        drop(is_closed);
+
+        let Some(pc) = pc.upgrade() else {
+            break;
+        };
+
+        let server = options.ice_server();
+        for ice_server in config.ice_servers.iter_mut().flatten() {
+            if ice_server.urls.as_ref() == Some(&options.urls) {
+                *ice_server = server.clone();
+            }
+        }
+
+        // The old credentials expire a quarter of the ttl after the first
+        // attempt, so failures are retried well before that.
+        match pc.set_configuration(&config) {
+            Ok(()) => {
+                wait = interval;
+                retry = RETRY_INTERVAL;
+            }
+            Err(_) => {
+                wait = retry;
+                retry = (retry * 2).min(MAX_RETRY_INTERVAL);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_credentials() {
+        assert_eq!(
+            credentials(1700000000, "alice", "north"),
+            (
+                "1700000000:alice".to_string(),
+                "Cd/49soE35ICqcJF/bCTn8Z4OyE=".to_string()
+            )
+        );
+    }
+}
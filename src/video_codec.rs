@@ -0,0 +1,596 @@
+use std::ffi::{c_char, c_int, c_void};
+
+use crate::{
+    cstr::{free_cstring, to_c_str, StringError},
+    media_engine::{codec_result, RawCodecParameter, RawCodecParameters},
+    video_frame::RawVideoFrame,
+    CodecError, I420Buffer, VideoFrame,
+};
+
+/// A video format as advertised in the sdp, the codec name of the rtpmap
+/// and the fmtp parameters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VideoFormat {
+    pub name: String,
+    pub parameters: Vec<(String, String)>,
+}
+
+impl VideoFormat {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Add an fmtp parameter.
+    pub fn with_parameter(mut self, key: &str, value: &str) -> Self {
+        self.parameters.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawVideoFormat {
+    name: *const c_char,
+    parameters: *const RawCodecParameter,
+    parameters_size: c_int,
+}
+
+/// The raw formats of a factory, owning the strings.
+struct RawVideoFormats {
+    formats: Vec<RawVideoFormat>,
+    #[allow(dead_code)]
+    parameters: Vec<RawCodecParameters>,
+}
+
+impl RawVideoFormats {
+    fn new(formats: &[VideoFormat]) -> Result<Self, StringError> {
+        let parameters = formats
+            .iter()
+            .map(|format| RawCodecParameters::new(&format.parameters))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Drop frees the names converted before a failure.
+        let mut raw = Self {
+            formats: Vec::with_capacity(formats.len()),
+            parameters,
+        };
+
+        for (format, parameters) in formats.iter().zip(&raw.parameters) {
+            raw.formats.push(RawVideoFormat {
+                name: to_c_str(&format.name)?,
+                parameters: parameters.as_ptr(),
+                parameters_size: parameters.len(),
+            });
+        }
+
+        Ok(raw)
+    }
+}
+
+impl Drop for RawVideoFormats {
+    fn drop(&mut self) {
+        for format in &self.formats {
+            free_cstring(format.name);
+        }
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawVideoCodecSettings {
+    width: u32,
+    height: u32,
+    start_bitrate: u32,
+    max_bitrate: u32,
+    max_framerate: u32,
+    number_of_cores: u32,
+}
+
+/// The settings a codec is initialized with, bitrates are in kbps.
+#[derive(Clone, Copy, Debug)]
+pub struct VideoCodecSettings {
+    pub width: u32,
+    pub height: u32,
+    pub start_bitrate: u32,
+    pub max_bitrate: u32,
+    pub max_framerate: u32,
+    pub number_of_cores: u32,
+}
+
+impl From<&RawVideoCodecSettings> for VideoCodecSettings {
+    fn from(raw: &RawVideoCodecSettings) -> Self {
+        Self {
+            width: raw.width,
+            height: raw.height,
+            start_bitrate: raw.start_bitrate,
+            max_bitrate: raw.max_bitrate,
+            max_framerate: raw.max_framerate,
+            number_of_cores: raw.number_of_cores,
+        }
+    }
+}
+
+#[repr(C)]
+pub(crate) struct RawEncodedImage {
+    data: *const u8,
+    size: usize,
+    key_frame: bool,
+    timestamp: i64,
+    width: u32,
+    height: u32,
+}
+
+/// The output of a video encoder and the input of a video decoder.
+#[derive(Clone, Debug, Default)]
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub key_frame: bool,
+    /// The timestamp of the frame in milliseconds.
+    pub timestamp: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<&RawEncodedImage> for EncodedImage {
+    fn from(raw: &RawEncodedImage) -> Self {
+        Self {
+            data: if raw.data.is_null() {
+                Vec::new()
+            } else {
+                unsafe { std::slice::from_raw_parts(raw.data, raw.size) }.to_vec()
+            },
+            key_frame: raw.key_frame,
+            timestamp: raw.timestamp,
+            width: raw.width,
+            height: raw.height,
+        }
+    }
+}
+
+/// A video encoder implemented in rust.
+#[allow(unused)]
+pub trait VideoEncoder: Send {
+    fn init(&mut self, settings: &VideoCodecSettings) -> Result<(), CodecError>;
+    /// Encode a frame, returning `None` drops the frame, e.g. to stay
+    /// within the bitrate.
+    fn encode(
+        &mut self,
+        frame: &VideoFrame,
+        key_frame_requested: bool,
+    ) -> Result<Option<EncodedImage>, CodecError>;
+    /// The target bitrate in kbps and framerate from the congestion
+    /// controller.
+    fn set_rates(&mut self, bitrate: u32, framerate: f64) {}
+}
+
+/// A video decoder implemented in rust.
+#[allow(unused)]
+pub trait VideoDecoder: Send {
+    fn init(&mut self, settings: &VideoCodecSettings) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    /// Decode an image, returning `None` if the decoder needs more input
+    /// before it can produce a frame.
+    fn decode(&mut self, image: &EncodedImage) -> Result<Option<VideoFrame>, CodecError>;
+}
+
+/// Creates the video encoders of a peer connection.
+pub trait VideoEncoderFactory: Send + Sync {
+    /// The formats offered in the sdp, in order of preference. Only read
+    /// once, when the media engine is created.
+    fn supported_formats(&self) -> Vec<VideoFormat>;
+    /// Create an encoder for one of the supported formats.
+    fn create_encoder(&self, format: &VideoFormat) -> Option<Box<dyn VideoEncoder>>;
+}
+
+/// Creates the video decoders of a peer connection.
+pub trait VideoDecoderFactory: Send + Sync {
+    /// The formats accepted in the sdp, in order of preference. Only read
+    /// once, when the media engine is created.
+    fn supported_formats(&self) -> Vec<VideoFormat>;
+    /// Create a decoder for one of the supported formats.
+    fn create_decoder(&self, format: &VideoFormat) -> Option<Box<dyn VideoDecoder>>;
+}
+
+pub(crate) type VideoEncoderContext = Box<dyn VideoEncoder>;
+pub(crate) type VideoDecoderContext = Box<dyn VideoDecoder>;
+type EncodedImageCallback = extern "C" fn(*const c_void, *const RawEncodedImage);
+type DecodedFrameCallback = extern "C" fn(*const c_void, *const RawVideoFrame);
+
+/// The encoder factory vtable, native picks a format by its index in the
+/// formats array.
+#[repr(C)]
+#[rustfmt::skip]
+pub(crate) struct RawVideoEncoderFactory {
+    ctx: *const VideoEncoderFactoryRef,
+    formats: *const RawVideoFormat,
+    formats_size: c_int,
+    create: extern "C" fn(&VideoEncoderFactoryRef, c_int) -> *mut VideoEncoderContext,
+    init: extern "C" fn(&mut VideoEncoderContext, *const RawVideoCodecSettings) -> c_int,
+    encode: extern "C" fn(&mut VideoEncoderContext, *const RawVideoFrame, bool, EncodedImageCallback, *const c_void) -> c_int,
+    set_rates: extern "C" fn(&mut VideoEncoderContext, u32, f64),
+    release: extern "C" fn(*mut VideoEncoderContext),
+}
+
+/// Owns a rust encoder factory and its vtable.
+pub(crate) struct VideoEncoderFactoryRef {
+    raw: RawVideoEncoderFactory,
+    factory: Box<dyn VideoEncoderFactory>,
+    formats: Vec<VideoFormat>,
+    #[allow(dead_code)]
+    raw_formats: RawVideoFormats,
+}
+
+impl VideoEncoderFactoryRef {
+    pub(crate) fn new(factory: Box<dyn VideoEncoderFactory>) -> Result<Box<Self>, StringError> {
+        let formats = factory.supported_formats();
+        let raw_formats = RawVideoFormats::new(&formats)?;
+        let mut this = Box::new(Self {
+            raw: RawVideoEncoderFactory {
+                ctx: std::ptr::null(),
+                formats: raw_formats.formats.as_ptr(),
+                formats_size: raw_formats.formats.len() as c_int,
+                create: create_video_encoder,
+                init: init_video_encoder,
+                encode: encode_video_frame,
+                set_rates: set_video_encoder_rates,
+                release: release_video_encoder,
+            },
+            raw_formats,
+            formats,
+            factory,
+        });
+
+        this.raw.ctx = &*this;
+        Ok(this)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawVideoEncoderFactory {
+        &self.raw
+    }
+}
+
+#[no_mangle]
+extern "C" fn create_video_encoder(
+    ctx: &VideoEncoderFactoryRef,
+    index: c_int,
+) -> *mut VideoEncoderContext {
+    ctx.formats
+        .get(index as usize)
+        .and_then(|format| ctx.factory.create_encoder(format))
+        .map(|encoder| Box::into_raw(Box::new(encoder)))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[no_mangle]
+extern "C" fn init_video_encoder(
+    encoder: &mut VideoEncoderContext,
+    settings: *const RawVideoCodecSettings,
+) -> c_int {
+    assert!(!settings.is_null());
+    codec_result(encoder.init(&unsafe { &*settings }.into()))
+}
+
+/// The frame is handed over to rust and released when it is dropped, the
+/// encoded image is only valid during the callback.
+#[no_mangle]
+extern "C" fn encode_video_frame(
+    encoder: &mut VideoEncoderContext,
+    frame: *const RawVideoFrame,
+    key_frame_requested: bool,
+    callback: EncodedImageCallback,
+    callback_ctx: *const c_void,
+) -> c_int {
+    let frame = VideoFrame::from_raw(frame);
+    match encoder.encode(&frame, key_frame_requested) {
+        Ok(Some(image)) => {
+            let raw = RawEncodedImage {
+                data: image.data.as_ptr(),
+                size: image.data.len(),
+                key_frame: image.key_frame,
+                timestamp: image.timestamp,
+                width: image.width,
+                height: image.height,
+            };
+
+            callback(callback_ctx, &raw);
+            0
+        }
+        ret => codec_result(ret),
+    }
+}
+
+#[no_mangle]
+extern "C" fn set_video_encoder_rates(encoder: &mut VideoEncoderContext, bitrate: u32, fps: f64) {
+    encoder.set_rates(bitrate, fps)
+}
+
+#[no_mangle]
+extern "C" fn release_video_encoder(encoder: *mut VideoEncoderContext) {
+    if !encoder.is_null() {
+        drop(unsafe { Box::from_raw(encoder) });
+    }
+}
+
+/// The decoder factory vtable, native picks a format by its index in the
+/// formats array.
+#[repr(C)]
+#[rustfmt::skip]
+pub(crate) struct RawVideoDecoderFactory {
+    ctx: *const VideoDecoderFactoryRef,
+    formats: *const RawVideoFormat,
+    formats_size: c_int,
+    create: extern "C" fn(&VideoDecoderFactoryRef, c_int) -> *mut VideoDecoderContext,
+    init: extern "C" fn(&mut VideoDecoderContext, *const RawVideoCodecSettings) -> c_int,
+    decode: extern "C" fn(&mut VideoDecoderContext, *const RawEncodedImage, DecodedFrameCallback, *const c_void) -> c_int,
+    release: extern "C" fn(*mut VideoDecoderContext),
+}
+
+/// Owns a rust decoder factory and its vtable.
+pub(crate) struct VideoDecoderFactoryRef {
+    raw: RawVideoDecoderFactory,
+    factory: Box<dyn VideoDecoderFactory>,
+    formats: Vec<VideoFormat>,
+    #[allow(dead_code)]
+    raw_formats: RawVideoFormats,
+}
+
+impl VideoDecoderFactoryRef {
+    pub(crate) fn new(factory: Box<dyn VideoDecoderFactory>) -> Result<Box<Self>, StringError> {
+        let formats = factory.supported_formats();
+        let raw_formats = RawVideoFormats::new(&formats)?;
+        let mut this = Box::new(Self {
+            raw: RawVideoDecoderFactory {
+                ctx: std::ptr::null(),
+                formats: raw_formats.formats.as_ptr(),
+                formats_size: raw_formats.formats.len() as c_int,
+                create: create_video_decoder,
+                init: init_video_decoder,
+                decode: decode_video_image,
+                release: release_video_decoder,
+            },
+            raw_formats,
+            formats,
+            factory,
+        });
+
+        this.raw.ctx = &*this;
+        Ok(this)
+    }
+
+    pub(crate) fn get_raw(&self) -> *const RawVideoDecoderFactory {
+        &self.raw
+    }
+}
+
+#[no_mangle]
+extern "C" fn create_video_decoder(
+    ctx: &VideoDecoderFactoryRef,
+    index: c_int,
+) -> *mut VideoDecoderContext {
+    ctx.formats
+        .get(index as usize)
+        .and_then(|format| ctx.factory.create_decoder(format))
+        .map(|decoder| Box::into_raw(Box::new(decoder)))
+        .unwrap_or(std::ptr::null_mut())
+}
+
+#[no_mangle]
+extern "C" fn init_video_decoder(
+    decoder: &mut VideoDecoderContext,
+    settings: *const RawVideoCodecSettings,
+) -> c_int {
+    assert!(!settings.is_null());
+    codec_result(decoder.init(&unsafe { &*settings }.into()))
+}
+
+/// The image is borrowed for the call, the decoded frame is only valid
+/// during the callback.
+#[no_mangle]
+extern "C" fn decode_video_image(
+    decoder: &mut VideoDecoderContext,
+    image: *const RawEncodedImage,
+    callback: DecodedFrameCallback,
+    callback_ctx: *const c_void,
+) -> c_int {
+    assert!(!image.is_null());
+    match decoder.decode(&unsafe { &*image }.into()) {
+        Ok(Some(frame)) => {
+            callback(callback_ctx, frame.get_raw());
+            0
+        }
+        ret => codec_result(ret),
+    }
+}
+
+#[no_mangle]
+extern "C" fn release_video_decoder(decoder: *mut VideoDecoderContext) {
+    if !decoder.is_null() {
+        drop(unsafe { Box::from_raw(decoder) });
+    }
+}
+
+/// A codec that sends uncompressed i420 frames.
+///
+/// Meant for testing codec negotiation and the rust codec plumbing on
+/// machines without any encoder, every frame is a key frame and the
+/// bitrate is ignored, so keep the resolution small.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PassthroughVideoCodec;
+
+impl PassthroughVideoCodec {
+    /// The codec name in the sdp.
+    pub const NAME: &'static str = "X-I420";
+}
+
+impl VideoEncoderFactory for PassthroughVideoCodec {
+    fn supported_formats(&self) -> Vec<VideoFormat> {
+        vec![VideoFormat::new(Self::NAME)]
+    }
+
+    fn create_encoder(&self, format: &VideoFormat) -> Option<Box<dyn VideoEncoder>> {
+        (format.name == Self::NAME).then(|| Box::new(PassthroughEncoder) as Box<dyn VideoEncoder>)
+    }
+}
+
+impl VideoDecoderFactory for PassthroughVideoCodec {
+    fn supported_formats(&self) -> Vec<VideoFormat> {
+        vec![VideoFormat::new(Self::NAME)]
+    }
+
+    fn create_decoder(&self, format: &VideoFormat) -> Option<Box<dyn VideoDecoder>> {
+        (format.name == Self::NAME).then(|| Box::new(PassthroughDecoder) as Box<dyn VideoDecoder>)
+    }
+}
+
+/// Writes the frame size followed by the tightly packed planes.
+struct PassthroughEncoder;
+
+impl VideoEncoder for PassthroughEncoder {
+    fn init(&mut self, _: &VideoCodecSettings) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn encode(&mut self, frame: &VideoFrame, _: bool) -> Result<Option<EncodedImage>, CodecError> {
+        let buffer = I420Buffer::from_frame(frame);
+        let mut data = Vec::with_capacity(8 + buffer.data_y().len() * 3 / 2);
+        data.extend_from_slice(&buffer.width().to_be_bytes());
+        data.extend_from_slice(&buffer.height().to_be_bytes());
+        data.extend_from_slice(buffer.data_y());
+        data.extend_from_slice(buffer.data_u());
+        data.extend_from_slice(buffer.data_v());
+
+        Ok(Some(EncodedImage {
+            timestamp: frame.timestamp(),
+            width: buffer.width(),
+            height: buffer.height(),
+            key_frame: true,
+            data,
+        }))
+    }
+}
+
+struct PassthroughDecoder;
+
+impl VideoDecoder for PassthroughDecoder {
+    fn decode(&mut self, image: &EncodedImage) -> Result<Option<VideoFrame>, CodecError> {
+        let data = &image.data;
+        if data.len() < 8 {
+            return Err(CodecError::InvalidParameter);
+        }
+
+        let width = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let height = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+        let luma = width as usize * height as usize;
+        let chroma = width.div_ceil(2) as usize * height.div_ceil(2) as usize;
+        let planes = &data[8..];
+        if planes.len() != luma + chroma * 2 {
+            return Err(CodecError::InvalidParameter);
+        }
+
+        let (y, uv) = planes.split_at(luma);
+        let (u, v) = uv.split_at(chroma);
+        let buffer = I420Buffer::from_planes(width, height, y.to_vec(), u.to_vec(), v.to_vec())
+            .ok_or(CodecError::InvalidParameter)?;
+        Ok(Some(buffer.into_frame(image.timestamp.max(0) as usize)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codec_test::{negotiate, rtpmaps},
+        MediaEngine, MediaStreamTrack,
+    };
+
+    fn frame(width: u32, height: u32, timestamp: usize) -> VideoFrame {
+        let chroma = (width.div_ceil(2) * height.div_ceil(2)) as usize;
+        let y = (0..width * height).map(|i| i as u8).collect();
+        let u = (0..chroma).map(|i| (i * 3) as u8).collect();
+        let v = (0..chroma).map(|i| (i * 7) as u8).collect();
+        I420Buffer::from_planes(width, height, y, u, v)
+            .unwrap()
+            .into_frame(timestamp)
+    }
+
+    #[test]
+    fn passthrough_round_trip() {
+        let codec = PassthroughVideoCodec;
+        let format = VideoFormat::new(PassthroughVideoCodec::NAME);
+        let mut encoder = codec.create_encoder(&format).unwrap();
+        let mut decoder = codec.create_decoder(&format).unwrap();
+
+        for (width, height) in [(1, 1), (2, 2), (5, 3), (7, 9), (320, 240)] {
+            let input = frame(width, height, 40);
+            let image = encoder.encode(&input, false).unwrap().unwrap();
+            assert!(image.key_frame);
+            assert_eq!(
+                (image.width, image.height, image.timestamp),
+                (width, height, 40)
+            );
+
+            let output = decoder.decode(&image).unwrap().unwrap();
+            let (input, output) = (
+                I420Buffer::from_frame(&input),
+                I420Buffer::from_frame(&output),
+            );
+            assert_eq!((output.width(), output.height()), (width, height));
+            assert_eq!(output.data_y(), input.data_y());
+            assert_eq!(output.data_u(), input.data_u());
+            assert_eq!(output.data_v(), input.data_v());
+        }
+    }
+
+    #[test]
+    fn passthrough_invalid_image() {
+        let mut decoder = PassthroughDecoder;
+        let mut image = PassthroughEncoder
+            .encode(&frame(5, 3, 0), true)
+            .unwrap()
+            .unwrap();
+
+        image.data.pop();
+        assert!(matches!(
+            decoder.decode(&image),
+            Err(CodecError::InvalidParameter)
+        ));
+
+        image.data.truncate(4);
+        assert!(matches!(
+            decoder.decode(&image),
+            Err(CodecError::InvalidParameter)
+        ));
+    }
+
+    #[test]
+    fn passthrough_formats() {
+        let codec = PassthroughVideoCodec;
+        assert_eq!(
+            VideoEncoderFactory::supported_formats(&codec),
+            [VideoFormat::new("X-I420")]
+        );
+
+        assert!(codec.create_encoder(&VideoFormat::new("VP8")).is_none());
+        assert!(codec.create_decoder(&VideoFormat::new("VP8")).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs the native library"]
+    fn passthrough_negotiation() {
+        let engine = || {
+            MediaEngine::default()
+                .with_video_encoder_factory(PassthroughVideoCodec)
+                .with_video_decoder_factory(PassthroughVideoCodec)
+        };
+
+        // The rust codec is preferred over the codecs built into native.
+        let track = MediaStreamTrack::create_video_track("video").unwrap();
+        let (offer, answer) = negotiate(engine, track);
+        for sdp in [offer, answer] {
+            assert_eq!(rtpmaps(&sdp, "video").first().unwrap(), "X-I420/90000");
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{pacer::Pacer, I420Buffer, SinkExt, VideoFrame, VideoTrack};
+
+/// How the inputs are arranged in the composited frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompositorLayout {
+    /// All inputs in equally sized cells.
+    Grid,
+    /// The active speaker on top, everyone else in a strip below.
+    ActiveSpeaker,
+    /// The active speaker fills the frame, everyone else in small
+    /// thumbnails in the bottom right corner.
+    PictureInPicture,
+}
+
+/// Video compositor configuration.
+#[derive(Clone, Copy, Debug)]
+pub struct VideoCompositorOptions {
+    /// The width of the output frame.
+    pub width: u32,
+    /// The height of the output frame.
+    pub height: u32,
+    /// The output frame rate.
+    pub fps: u32,
+    /// The initial layout.
+    pub layout: CompositorLayout,
+    /// The yuv color of the area that is not covered by any input.
+    pub background: [u8; 3],
+}
+
+impl Default for VideoCompositorOptions {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fps: 30,
+            layout: CompositorLayout::Grid,
+            background: [16, 128, 128],
+        }
+    }
+}
+
+/// A rectangle in the output frame.
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    /// Fit a frame into the rectangle keeping its aspect ratio, centered.
+    /// Everything is aligned to even pixels for the chroma planes.
+    fn fit(&self, width: u32, height: u32) -> Rect {
+        if width == 0 || height == 0 {
+            return Rect { width: 0, ..*self };
+        }
+
+        let scale = f64::min(
+            self.width as f64 / width as f64,
+            self.height as f64 / height as f64,
+        );
+
+        let fit_width = ((width as f64 * scale) as u32 & !1).max(2);
+        let fit_height = ((height as f64 * scale) as u32 & !1).max(2);
+        Rect {
+            x: (self.x + (self.width.saturating_sub(fit_width)) / 2) & !1,
+            y: (self.y + (self.height.saturating_sub(fit_height)) / 2) & !1,
+            width: fit_width,
+            height: fit_height,
+        }
+    }
+}
+
+struct CompositorInputShared {
+    id: u32,
+    latest: Mutex<Option<Arc<VideoFrame>>>,
+    // The last scaled frame, reused while no new frame arrives.
+    cache: Mutex<Option<(Arc<VideoFrame>, I420Buffer)>>,
+}
+
+/// One input of the video compositor.
+///
+/// The input is a video track sink, register it to the track that should
+/// appear in the composited frame. Only the latest frame is kept.
+#[derive(Clone)]
+pub struct VideoCompositorInput {
+    shared: Arc<CompositorInputShared>,
+}
+
+impl VideoCompositorInput {
+    /// The id of the input, used to select the active speaker.
+    pub fn id(&self) -> u32 {
+        self.shared.id
+    }
+}
+
+impl SinkExt for VideoCompositorInput {
+    type Item = Arc<VideoFrame>;
+
+    fn on_data(&self, frame: Arc<VideoFrame>) {
+        let _ = self.shared.latest.lock().unwrap().insert(frame);
+    }
+}
+
+struct VideoCompositorInner {
+    options: VideoCompositorOptions,
+    track: Arc<VideoTrack>,
+    inputs: RwLock<Vec<Arc<CompositorInputShared>>>,
+    layout: Mutex<CompositorLayout>,
+    active: Mutex<Option<u32>>,
+    ids: AtomicU32,
+}
+
+impl VideoCompositorInner {
+    /// Compute the rectangle of every input, in drawing order.
+    fn layout(&self, inputs: &[Arc<CompositorInputShared>]) -> Vec<(usize, Rect)> {
+        let (width, height) = (self.options.width, self.options.height);
+        let full = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+
+        if inputs.is_empty() {
+            return Vec::new();
+        }
+
+        let active = self
+            .active
+            .lock()
+            .unwrap()
+            .and_then(|id| inputs.iter().position(|input| input.id == id))
+            .unwrap_or(0);
+
+        let others = (0..inputs.len())
+            .filter(|index| *index != active)
+            .collect::<Vec<usize>>();
+
+        match *self.layout.lock().unwrap() {
+            CompositorLayout::Grid => {
+                let count = inputs.len() as u32;
+                let columns = (count as f64).sqrt().ceil() as u32;
+                let rows = count.div_ceil(columns);
+                let (cell_width, cell_height) = (width / columns, height / rows);
+                (0..inputs.len())
+                    .map(|index| {
+                        let (column, row) = (index as u32 % columns, index as u32 / columns);
+                        (
+                            index,
+                            Rect {
+                                x: column * cell_width,
+                                y: row * cell_height,
+                                width: cell_width,
+                                height: cell_height,
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            CompositorLayout::ActiveSpeaker => {
+                if others.is_empty() {
+                    return vec![(active, full)];
+                }
+
+                let main_height = height * 3 / 4;
+                let strip_width = width / others.len() as u32;
+                let mut rects = vec![(
+                    active,
+                    Rect {
+                        height: main_height,
+                        ..full
+                    },
+                )];
+
+                for (position, index) in others.iter().enumerate() {
+                    rects.push((
+                        *index,
+                        Rect {
+                            x: position as u32 * strip_width,
+                            y: main_height,
+                            width: strip_width,
+                            height: height - main_height,
+                        },
+                    ));
+                }
+
+                rects
+            }
+            CompositorLayout::PictureInPicture => {
+                let (thumb_width, thumb_height) = (width / 4, height / 4);
+                let margin = (width / 64) & !1;
+                let mut rects = vec![(active, full)];
+                for (position, index) in others.iter().enumerate() {
+                    let offset = (position as u32 + 1) * (thumb_width + margin);
+                    if offset > width {
+                        break;
+                    }
+
+                    rects.push((
+                        *index,
+                        Rect {
+                            x: width - offset,
+                            y: height.saturating_sub(thumb_height + margin),
+                            width: thumb_width,
+                            height: thumb_height,
+                        },
+                    ));
+                }
+
+                rects
+            }
+        }
+    }
+
+    /// Draw one output frame.
+    fn render(&self, canvas: &mut I420Buffer) {
+        canvas.fill(self.options.background);
+
+        let inputs = self.inputs.read().unwrap().clone();
+        for (index, cell) in self.layout(&inputs) {
+            let input = &inputs[index];
+            let frame = match input.latest.lock().unwrap().clone() {
+                Some(frame) => frame,
+                None => continue,
+            };
+
+            let rect = cell.fit(frame.width(), frame.height());
+            if rect.width == 0 {
+                continue;
+            }
+
+            // Only scale again when the frame or the target size changed.
+            let mut cache = input.cache.lock().unwrap();
+            let is_valid = cache
+                .as_ref()
+                .map(|(cached, buffer)| {
+                    Arc::ptr_eq(cached, &frame)
+                        && buffer.width() == rect.width
+                        && buffer.height() == rect.height
+                })
+                .unwrap_or(false);
+
+            if !is_valid {
+                let buffer = I420Buffer::from_frame_scaled(&frame, rect.width, rect.height);
+                let _ = cache.insert((frame, buffer));
+            }
+
+            if let Some((_, buffer)) = cache.as_ref() {
+                canvas.blit(buffer, rect.x, rect.y);
+            }
+        }
+    }
+}
+
+/// Composites many video tracks into one.
+///
+/// The compositor keeps the latest frame of every input and renders them
+/// into a frame of fixed size at a fixed rate, the result is pushed into a
+/// local video track. Frames are scaled with the crate's own scaler, keeping
+/// the aspect ratio of every input.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use librtc::*;
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let remote_tracks: Vec<Arc<VideoTrack>> = Vec::new();
+/// let output = VideoTrack::new("composite")?;
+/// let compositor = VideoCompositor::new(output, VideoCompositorOptions::default());
+///
+/// let mut sinks = Vec::new();
+/// for track in remote_tracks {
+///     let input = compositor.add_input();
+///     sinks.push(track.add_sink(Sinker::new(input)));
+/// }
+///
+/// compositor.set_layout(CompositorLayout::ActiveSpeaker);
+/// # Ok(())
+/// # }
+/// ```
+pub struct VideoCompositor {
+    inner: Arc<VideoCompositorInner>,
+    closed: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl VideoCompositor {
+    /// Create a video compositor that pushes into the given track, the
+    /// output starts immediately.
+    pub fn new(track: Arc<VideoTrack>, options: VideoCompositorOptions) -> Self {
+        assert!(options.fps > 0);
+        assert!(options.width >= 2 && options.height >= 2);
+
+        let inner = Arc::new(VideoCompositorInner {
+            layout: Mutex::new(options.layout),
+            inputs: RwLock::new(Vec::new()),
+            active: Mutex::new(None),
+            ids: AtomicU32::new(0),
+            options,
+            track,
+        });
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let inner = inner.clone();
+            let closed = closed.clone();
+            thread::spawn(move || {
+                let mut pacer = Pacer::new(Duration::from_secs(1) / options.fps);
+                let started = Instant::now();
+                while !closed.load(Ordering::Relaxed) {
+                    pacer.wait();
+
+                    let mut canvas = I420Buffer::new(options.width, options.height);
+                    inner.render(&mut canvas);
+
+                    let timestamp = started.elapsed().as_millis() as usize;
+                    inner.track.add_frame(&canvas.into_frame(timestamp));
+                }
+            })
+        };
+
+        Self {
+            handle: Some(handle),
+            closed,
+            inner,
+        }
+    }
+
+    /// Add a new input to the composition.
+    pub fn add_input(&self) -> VideoCompositorInput {
+        let shared = Arc::new(CompositorInputShared {
+            id: self.inner.ids.fetch_add(1, Ordering::Relaxed),
+            latest: Mutex::new(None),
+            cache: Mutex::new(None),
+        });
+
+        self.inner.inputs.write().unwrap().push(shared.clone());
+        VideoCompositorInput { shared }
+    }
+
+    /// Remove an input from the composition.
+    pub fn remove_input(&self, id: u32) {
+        self.inner
+            .inputs
+            .write()
+            .unwrap()
+            .retain(|input| input.id != id);
+    }
+
+    /// Change the layout, takes effect with the next frame.
+    pub fn set_layout(&self, layout: CompositorLayout) {
+        *self.inner.layout.lock().unwrap() = layout;
+    }
+
+    /// Select the input that is shown large in the active speaker and
+    /// picture in picture layouts, defaults to the first input.
+    pub fn set_active_speaker(&self, id: Option<u32>) {
+        *self.inner.active.lock().unwrap() = id;
+    }
+}
+
+impl Drop for VideoCompositor {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{ffi::c_void, slice::from_raw_parts, sync::Arc};
 
-use crate::media_stream_track::rtc_free_frame;
+use crate::{media_stream_track::rtc_free_frame, I420Buffer};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -25,6 +25,10 @@ pub(crate) struct RawVideoFrame {
 #[derive(Debug)]
 pub struct VideoFrame {
     raw: *const RawVideoFrame,
+    // Frames created from an owned buffer keep it here, the raw frame only
+    // borrows the planes.
+    #[allow(dead_code)]
+    buf: Option<I420Buffer>,
 }
 
 unsafe impl Send for VideoFrame {}
@@ -38,7 +42,7 @@ impl VideoFrame {
     /// create video frame from raw video frame type.
     pub(crate) fn from_raw(raw: *const RawVideoFrame) -> Arc<Self> {
         assert!(!raw.is_null());
-        Arc::new(Self { raw })
+        Arc::new(Self { raw, buf: None })
     }
 
     /// Create i420 frame structure from memory buffer.
@@ -61,9 +65,37 @@ impl VideoFrame {
                 width,
                 height,
             })),
+            buf: None,
         }
     }
 
+    /// Create i420 frame that owns its image buffer, the timestamp is in
+    /// milliseconds.
+    pub fn from_buffer(buf: I420Buffer, timestamp: usize) -> Self {
+        let stride_uv = buf.stride_uv() as u32;
+        Self {
+            raw: Box::into_raw(Box::new(RawVideoFrame {
+                planes: [
+                    buf.data_y().as_ptr(),
+                    buf.data_u().as_ptr(),
+                    buf.data_v().as_ptr(),
+                    std::ptr::null(),
+                ],
+                strides: [buf.stride_y() as u32, stride_uv, stride_uv, 0],
+                timestamp: timestamp as i64,
+                height: buf.height(),
+                width: buf.width(),
+                remote: false,
+            })),
+            buf: Some(buf),
+        }
+    }
+
+    /// get video frame timestamp
+    pub fn timestamp(&self) -> i64 {
+        unsafe { &*self.raw }.timestamp
+    }
+
     /// get video frame width
     pub fn width(&self) -> u32 {
         unsafe { &*self.raw }.width
@@ -90,7 +122,7 @@ impl VideoFrame {
     /// get i420 frame u buffer
     pub fn data_u(&self) -> &[u8] {
         let raw = unsafe { &*self.raw };
-        let size = (raw.strides[1] * (raw.height / 2)) as usize;
+        let size = (raw.strides[1] * raw.height.div_ceil(2)) as usize;
         unsafe { from_raw_parts(raw.planes[1], size) }
     }
 
@@ -103,7 +135,7 @@ impl VideoFrame {
     /// get i420 frame v buffer
     pub fn data_v(&self) -> &[u8] {
         let raw = unsafe { &*self.raw };
-        let size = (raw.strides[2] * (raw.height / 2)) as usize;
+        let size = (raw.strides[2] * raw.height.div_ceil(2)) as usize;
         unsafe { from_raw_parts(raw.planes[2], size) }
     }
 
@@ -0,0 +1,220 @@
+use std::{
+    ffi::c_int,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{I420Buffer, VideoTrack};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RawVideoSourceWants {
+    max_pixel_count: c_int,
+    target_pixel_count: c_int,
+    max_framerate_fps: c_int,
+}
+
+/// What the encoder currently wants from the source of a track.
+///
+/// Under congestion or cpu overuse webrtc asks the source for smaller or
+/// fewer frames, a source that honors the request saves the encoder from
+/// scaling and dropping frames on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VideoSourceWants {
+    /// Frames should not have more pixels than this.
+    pub max_pixel_count: Option<u32>,
+    /// The pixel count the source should aim for.
+    pub target_pixel_count: Option<u32>,
+    /// Frames should not be delivered faster than this.
+    pub max_framerate: Option<u32>,
+}
+
+impl From<RawVideoSourceWants> for VideoSourceWants {
+    fn from(raw: RawVideoSourceWants) -> Self {
+        // Native uses int max for "no limit".
+        let limit = |value: c_int| {
+            if value > 0 && value < c_int::MAX {
+                Some(value as u32)
+            } else {
+                None
+            }
+        };
+
+        Self {
+            max_pixel_count: limit(raw.max_pixel_count),
+            target_pixel_count: limit(raw.target_pixel_count),
+            max_framerate: limit(raw.max_framerate_fps),
+        }
+    }
+}
+
+impl VideoSourceWants {
+    /// The size a frame should be scaled to, keeping the aspect ratio and
+    /// even dimensions. Returns `None` if the frame can be sent as it is.
+    pub fn scaled_size(&self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let pixels = width as u64 * height as u64;
+        let limit = match (self.target_pixel_count, self.max_pixel_count) {
+            (Some(target), Some(max)) => target.min(max),
+            (Some(limit), None) | (None, Some(limit)) => limit,
+            (None, None) => return None,
+        } as u64;
+
+        if pixels <= limit || pixels == 0 {
+            return None;
+        }
+
+        let scale = (limit as f64 / pixels as f64).sqrt();
+        let scaled_width = ((width as f64 * scale) as u32 & !1).max(2);
+        let scaled_height = ((height as f64 * scale) as u32 & !1).max(2);
+        Some((scaled_width, scaled_height))
+    }
+}
+
+/// A producer of video frames.
+///
+/// The source decides its own cadence, `next_frame` blocks until the next
+/// frame is ready, such as a camera waiting for the next capture or a file
+/// reader pacing to the frame rate of the file.
+pub trait VideoSource: Send {
+    /// Produce the next frame, returns `None` when the source has ended.
+    fn next_frame(&mut self) -> Option<I420Buffer>;
+}
+
+impl<T: VideoSource + ?Sized> VideoSource for Box<T> {
+    fn next_frame(&mut self) -> Option<I420Buffer> {
+        (**self).next_frame()
+    }
+}
+
+#[derive(Default)]
+struct DriverCounters {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    scaled: AtomicU64,
+}
+
+/// Runs a video source on a dedicated thread and pushes its frames into a
+/// local video track.
+///
+/// Frames are timestamped with the time since the driver started, and
+/// adapted to what the encoder wants before they reach the track: frames
+/// above the requested pixel count are scaled down and frames above the
+/// requested frame rate are dropped.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # struct Camera;
+/// # impl VideoSource for Camera {
+/// #     fn next_frame(&mut self) -> Option<I420Buffer> {
+/// #         None
+/// #     }
+/// # }
+/// # fn main() -> Result<(), MediaStreamError> {
+/// # let camera = Camera;
+/// let track = VideoTrack::new("camera")?;
+/// let driver = VideoSourceDriver::spawn(camera, track.clone());
+/// # Ok(())
+/// # }
+/// ```
+pub struct VideoSourceDriver {
+    closed: Arc<AtomicBool>,
+    counters: Arc<DriverCounters>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl VideoSourceDriver {
+    /// Start pulling frames from the source, the driver stops when the
+    /// source ends or the driver is dropped.
+    pub fn spawn<S: VideoSource + 'static>(mut source: S, track: Arc<VideoTrack>) -> Self {
+        let closed = Arc::new(AtomicBool::new(false));
+        let counters = Arc::new(DriverCounters::default());
+        let handle = {
+            let closed = closed.clone();
+            let counters = counters.clone();
+            thread::spawn(move || {
+                let started = Instant::now();
+                let mut last_sent: Option<Instant> = None;
+                while !closed.load(Ordering::Relaxed) {
+                    let buffer = match source.next_frame() {
+                        Some(buffer) => buffer,
+                        None => break,
+                    };
+
+                    let now = Instant::now();
+                    let wants = track.source_wants();
+
+                    // Allow a little jitter, otherwise a source running at
+                    // exactly the limit would lose frames.
+                    if let (Some(fps), Some(last)) = (wants.max_framerate, last_sent) {
+                        let interval = Duration::from_secs(1) / fps.max(1);
+                        if now - last < interval.mul_f32(0.9) {
+                            counters.dropped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                    }
+
+                    let buffer = match wants.scaled_size(buffer.width(), buffer.height()) {
+                        Some((width, height)) => {
+                            counters.scaled.fetch_add(1, Ordering::Relaxed);
+                            buffer.scale(width, height)
+                        }
+                        None => buffer,
+                    };
+
+                    let timestamp = (now - started).as_millis() as usize;
+                    track.add_frame(&buffer.into_frame(timestamp));
+                    counters.sent.fetch_add(1, Ordering::Relaxed);
+                    last_sent = Some(now);
+                }
+            })
+        };
+
+        Self {
+            handle: Some(handle),
+            counters,
+            closed,
+        }
+    }
+
+    /// Whether the source has ended.
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map(|handle| handle.is_finished())
+            .unwrap_or(true)
+    }
+
+    /// Number of frames pushed into the track.
+    pub fn frames_sent(&self) -> u64 {
+        self.counters.sent.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames dropped to honor the requested frame rate.
+    pub fn frames_dropped(&self) -> u64 {
+        self.counters.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of frames scaled down to honor the requested resolution.
+    pub fn frames_scaled(&self) -> u64 {
+        self.counters.scaled.load(Ordering::Relaxed)
+    }
+
+    /// Stop the driver and wait for the thread, the source is dropped once
+    /// its current frame is done.
+    pub fn stop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for VideoSourceDriver {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
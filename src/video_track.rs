@@ -1,17 +1,18 @@
-use std::{
-    collections::HashMap,
-    ffi::c_char,
-    sync::{Arc, RwLock},
-};
+use std::{ffi::c_char, sync::Arc};
 
 use crate::{
-    cstr::{c_str_to_str, free_cstring, to_c_str},
+    cstr::{c_str_to_str, free_cstring, to_c_str, StringError},
+    frame_stream::{FramePolicy, FrameStream},
     media_stream::MediaStreamError,
     media_stream_track::{
-        rtc_free_media_stream_track, rtc_remove_media_stream_track_frame_h, RawMediaStreamTrack,
+        get_track_enabled, get_track_id, get_track_state, rtc_free_media_stream_track,
+        rtc_remove_media_stream_track_frame_h, set_track_enabled, stop_track, MediaStreamTrackKind,
+        MediaStreamTrackState, RawMediaStreamTrack, TrackEvents,
     },
+    sink::{SinkKey, SinkRegistry},
+    trace::rtc_span,
     video_frame::RawVideoFrame,
-    Sinker, VideoFrame,
+    SinkHandle, Sinker, VideoFrame, VideoSourceWants,
 };
 
 #[allow(improper_ctypes)]
@@ -33,13 +34,19 @@ extern "C" {
         ),
         ctx: &crate::video_track::VideoTrack,
     );
+
+    pub(crate) fn rtc_get_video_track_source_wants(
+        track: *const crate::media_stream_track::RawMediaStreamTrack,
+    ) -> crate::video_source::RawVideoSourceWants;
 }
 
 /// The VideoTrack interface represents a single video track from
 /// a MediaStreamTrack.
 pub struct VideoTrack {
     pub(crate) raw: *const RawMediaStreamTrack,
-    sinks: RwLock<HashMap<u8, Sinker<Arc<VideoFrame>>>>,
+    sinks: SinkRegistry<Arc<VideoFrame>>,
+    events: TrackEvents,
+    id: String,
 }
 
 unsafe impl Send for VideoTrack {}
@@ -50,6 +57,64 @@ impl VideoTrack {
         c_str_to_str(unsafe { (*self.raw).label }).expect("get video track label string to failed")
     }
 
+    /// Returns a string containing a unique identifier (GUID) for the
+    /// track.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Always `MediaStreamTrackKind::Video`.
+    pub fn kind(&self) -> MediaStreamTrackKind {
+        MediaStreamTrackKind::Video
+    }
+
+    /// Returns whether the track is allowed to render the source stream.
+    pub fn enabled(&self) -> bool {
+        get_track_enabled(self.raw)
+    }
+
+    /// A disabled track produces black frames instead of the source media,
+    /// the track itself stays live.
+    pub fn set_enabled(&self, enabled: bool) {
+        set_track_enabled(self.raw, enabled)
+    }
+
+    /// Returns whether the track is currently unable to provide media data
+    /// due to a technical issue, such as the remote side not sending.
+    pub fn muted(&self) -> bool {
+        self.events.muted()
+    }
+
+    /// Returns the status of the track.
+    pub fn ready_state(&self) -> MediaStreamTrackState {
+        get_track_state(self.raw)
+    }
+
+    /// Stops the track, the source is released and the track is ended.
+    /// Stopping a track does not fire the ended event.
+    pub fn stop(&self) {
+        stop_track(self.raw)
+    }
+
+    /// Called when the track is muted, usually because the remote side
+    /// stopped sending media. Replaces the previous handler.
+    pub fn on_mute<F: Fn() + Send + Sync + 'static>(&self, handler: F) {
+        self.events.set_on_mute(Box::new(handler))
+    }
+
+    /// Called when the track is unmuted, the remote side is sending media
+    /// again. Replaces the previous handler.
+    pub fn on_unmute<F: Fn() + Send + Sync + 'static>(&self, handler: F) {
+        self.events.set_on_unmute(Box::new(handler))
+    }
+
+    /// Called when the track has ended because the remote side will never
+    /// send on it again, this is the moment to release everything attached
+    /// to the track. Replaces the previous handler.
+    pub fn on_ended<F: Fn() + Send + Sync + 'static>(&self, handler: F) {
+        self.events.set_on_ended(Box::new(handler))
+    }
+
     /// Create a new video track, may fail to create, such as
     /// insufficient memory.
     pub fn new(label: &str) -> Result<Arc<Self>, MediaStreamError> {
@@ -63,7 +128,7 @@ impl VideoTrack {
         if raw.is_null() {
             Err(MediaStreamError::CreateTrackFailed)
         } else {
-            Ok(Self::from_raw(raw))
+            Self::from_raw(raw).map_err(MediaStreamError::StringError)
         }
     }
 
@@ -77,51 +142,114 @@ impl VideoTrack {
         }
     }
 
-    /// Register video track frame sink, one track can register multiple sinks.
-    /// The sink id cannot be repeated, otherwise the sink implementation will
-    /// be overwritten.
-    pub fn register_sink(&self, id: u8, sink: Sinker<Arc<VideoFrame>>) {
-        let mut sinks = self.sinks.write().unwrap();
+    /// Returns what the encoders of the track currently want from its
+    /// source, only meaningful for local video tracks.
+    pub fn source_wants(&self) -> VideoSourceWants {
+        unsafe { rtc_get_video_track_source_wants(self.raw) }.into()
+    }
 
-        // Register for the first time, register the callback function to
-        // webrtc native, and then do not need to register again.
-        if sinks.is_empty() {
-            unsafe { rtc_set_video_track_frame_h(self.raw, on_video_frame, self) }
-        }
+    /// Register a video track frame sink, one track can register any number
+    /// of sinks. The sink stays registered until the returned handle is
+    /// dropped.
+    ///
+    /// The handle must not be dropped from inside a sink callback of the
+    /// same track, removing the sink waits for the callbacks to return and
+    /// would deadlock.
+    pub fn add_sink(self: &Arc<Self>, sink: Sinker<Arc<VideoFrame>>) -> SinkHandle {
+        let key = self.sinks.next_key();
+        self.insert_sink(key, sink);
+
+        let this = Arc::downgrade(self);
+        SinkHandle::new(move || {
+            if let Some(this) = this.upgrade() {
+                this.remove_sink_by_key(key);
+            }
+        })
+    }
+
+    /// Call the handler with every frame of the track, until the returned
+    /// handle is dropped.
+    pub fn on_frame<F>(self: &Arc<Self>, handler: F) -> SinkHandle
+    where
+        F: Fn(Arc<VideoFrame>) + Send + 'static,
+    {
+        self.add_sink(Sinker::from_fn(handler))
+    }
 
-        sinks.insert(id, sink);
+    /// Register video track frame sink with a fixed id, a sink with the same
+    /// id is overwritten.
+    #[deprecated(note = "use `add_sink`, which returns a handle")]
+    pub fn register_sink(&self, id: u8, sink: Sinker<Arc<VideoFrame>>) {
+        self.insert_sink(SinkKey::Id(id), sink);
     }
 
-    /// Delete the registered sink, if it exists, it will return the deleted
-    /// sink.
+    /// Delete the sink registered with a fixed id, if it exists, it will
+    /// return the deleted sink.
+    #[deprecated(note = "drop the handle returned by `add_sink` instead")]
     pub fn remove_sink(&self, id: u8) -> Option<Sinker<Arc<VideoFrame>>> {
-        let mut sinks = self.sinks.write().unwrap();
-        let value = sinks.remove(&id);
-        if sinks.is_empty() {
-            unsafe { rtc_remove_media_stream_track_frame_h(self.raw) }
-        }
+        self.remove_sink_by_key(SinkKey::Id(id))
+    }
 
-        value
+    /// Receive the frames of the track as an asynchronous stream.
+    ///
+    /// At most `capacity` frames are queued, the policy decides which frames
+    /// are dropped when the consumer falls behind. The stream ends when the
+    /// track is dropped.
+    pub fn frames(
+        self: &Arc<Self>,
+        capacity: usize,
+        policy: FramePolicy,
+    ) -> FrameStream<Arc<VideoFrame>> {
+        let (queue, mut stream) = FrameStream::channel(capacity, policy);
+        stream.attach(self.add_sink(Sinker::new(queue)));
+        stream
     }
 
-    /// create video track from raw type ptr.
-    pub(crate) fn from_raw(raw: *const RawMediaStreamTrack) -> Arc<Self> {
+    /// create video track from raw type ptr, the track is freed if its id
+    /// can not be read.
+    pub(crate) fn from_raw(raw: *const RawMediaStreamTrack) -> Result<Arc<Self>, StringError> {
         assert!(!raw.is_null());
-        Arc::new(Self {
-            sinks: RwLock::new(HashMap::new()),
+        let id = match get_track_id(raw) {
+            Ok(id) => id,
+            Err(e) => {
+                unsafe { rtc_free_media_stream_track(raw) }
+                return Err(e);
+            }
+        };
+
+        let this = Arc::new(Self {
+            sinks: SinkRegistry::new(rtc_span!(DEBUG, "sinks", kind = "video_track")),
+            events: TrackEvents::default(),
+            id,
             raw,
+        });
+
+        this.events.register(raw);
+        Ok(this)
+    }
+
+    fn insert_sink(&self, key: SinkKey, sink: Sinker<Arc<VideoFrame>>) {
+        // Register for the first time, register the callback function to
+        // webrtc native, and then do not need to register again.
+        self.sinks.insert(key, sink, || unsafe {
+            rtc_set_video_track_frame_h(self.raw, on_video_frame, self)
+        });
+    }
+
+    fn remove_sink_by_key(&self, key: SinkKey) -> Option<Sinker<Arc<VideoFrame>>> {
+        self.sinks.remove(key, || unsafe {
+            rtc_remove_media_stream_track_frame_h(self.raw)
         })
     }
 
     fn on_data(this: &Self, frame: Arc<VideoFrame>) {
-        for sinker in this.sinks.read().unwrap().values() {
-            sinker.sink.on_data(frame.clone());
-        }
+        this.sinks.on_data(frame);
     }
 }
 
 impl Drop for VideoTrack {
     fn drop(&mut self) {
+        self.events.unregister(self.raw);
         unsafe { rtc_remove_media_stream_track_frame_h(self.raw) }
         unsafe { rtc_free_media_stream_track(self.raw) }
     }
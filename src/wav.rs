@@ -0,0 +1,666 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    audio_resampler::FormatConverter, AudioFrame, AudioSource, ChannelLayout, ChannelMixer,
+    Resampler, SinkExt,
+};
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+/// The size of the canonical pcm header written by the recorder.
+const HEADER_LEN: u64 = 44;
+
+#[derive(Debug)]
+pub enum WavError {
+    Io(io::Error),
+    /// The stream is not a riff wave file, or a chunk is malformed.
+    InvalidHeader(&'static str),
+    /// The sample format or channel count is not supported.
+    UnsupportedFormat(String),
+}
+
+impl Error for WavError {}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "wav io error: {}", e),
+            Self::InvalidHeader(reason) => write!(f, "invalid wav header: {}", reason),
+            Self::UnsupportedFormat(format) => write!(f, "unsupported wav format: {}", format),
+        }
+    }
+}
+
+impl From<io::Error> for WavError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// The sample format of a wav file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WavSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    /// Whether the samples are ieee floats rather than integers.
+    pub float: bool,
+}
+
+impl WavSpec {
+    fn block_align(&self) -> usize {
+        self.channels as usize * self.bits_per_sample as usize / 8
+    }
+
+    /// Decode one sample to 16 bits.
+    fn decode(&self, bytes: &[u8]) -> i16 {
+        let float = |value: f64| (value * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+        match (self.float, self.bits_per_sample) {
+            (false, 8) => ((bytes[0] as i16) - 128) << 8,
+            (false, 16) => i16::from_le_bytes([bytes[0], bytes[1]]),
+            (false, 24) => i16::from_le_bytes([bytes[1], bytes[2]]),
+            (false, 32) => i16::from_le_bytes([bytes[2], bytes[3]]),
+            (true, 32) => {
+                float(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64)
+            }
+            (true, 64) => float(f64::from_le_bytes(bytes[..8].try_into().unwrap())),
+            _ => 0,
+        }
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads a wav file as an audio source.
+///
+/// 8, 16, 24 and 32 bit integer and 32 and 64 bit float files at any sample
+/// rate are accepted. The audio is resampled to the output rate, 48kHz by
+/// default, and 5.1 files are mixed down to stereo.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let reader = WavReader::open("greeting.wav")?;
+/// let track = AudioTrack::new("ivr")?;
+/// let driver = AudioSourceDriver::spawn(reader, track.clone());
+/// # Ok(())
+/// # }
+/// ```
+pub struct WavReader<R> {
+    reader: R,
+    spec: WavSpec,
+    data_start: u64,
+    data_len: u64,
+    position: u64,
+    looping: bool,
+    flushed: bool,
+    output_rate: usize,
+    output_channels: u8,
+    mixer: Option<ChannelMixer>,
+    resampler: Resampler,
+    fifo: VecDeque<i16>,
+}
+
+impl WavReader<BufReader<File>> {
+    /// Open a wav file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, WavError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> WavReader<R> {
+    /// Read the headers up to the start of the samples.
+    pub fn new(mut reader: R) -> Result<Self, WavError> {
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff)?;
+        if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+            return Err(WavError::InvalidHeader("not a riff wave file"));
+        }
+
+        let mut spec = None;
+        let (data_start, data_len) = loop {
+            let mut chunk = [0u8; 8];
+            match reader.read_exact(&mut chunk) {
+                Ok(()) => (),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(WavError::InvalidHeader("no data chunk"));
+                }
+                Err(e) => return Err(e.into()),
+            }
+
+            let size = read_u32(&chunk, 4) as u64;
+            match &chunk[..4] {
+                b"fmt " => {
+                    if size < 16 {
+                        return Err(WavError::InvalidHeader("fmt chunk too short"));
+                    }
+
+                    // Only the basic fields and the extensible sub format are
+                    // used, the rest of the chunk is skipped.
+                    let mut fmt = [0u8; 40];
+                    let len = if size >= 40 { 40 } else { 16 };
+                    reader.read_exact(&mut fmt[..len])?;
+                    let _ = spec.insert(Self::parse_format(&fmt[..len])?);
+                    reader.seek(SeekFrom::Current((size + (size & 1)) as i64 - len as i64))?;
+                }
+                b"data" => {
+                    let start = reader.stream_position()?;
+                    let end = reader.seek(SeekFrom::End(0))?;
+                    reader.seek(SeekFrom::Start(start))?;
+
+                    // Streaming writers leave the size at 0 or the maximum.
+                    let available = end - start;
+                    let len = if size == 0 || size == u32::MAX as u64 {
+                        available
+                    } else {
+                        size.min(available)
+                    };
+
+                    break (start, len);
+                }
+                _ => {
+                    // Chunks are padded to an even size.
+                    reader.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+                }
+            }
+        };
+
+        let spec = spec.ok_or(WavError::InvalidHeader("no fmt chunk before the data"))?;
+        let (output_channels, mixer) = match spec.channels {
+            1 | 2 => (spec.channels as u8, None),
+            6 => (
+                2,
+                Some(ChannelMixer::new(
+                    ChannelLayout::Surround51,
+                    ChannelLayout::Stereo,
+                )),
+            ),
+            channels => {
+                return Err(WavError::UnsupportedFormat(format!(
+                    "{} channels",
+                    channels
+                )))
+            }
+        };
+
+        let output_rate = 48000;
+        Ok(Self {
+            resampler: Resampler::new(spec.sample_rate as usize, output_rate, output_channels),
+            fifo: VecDeque::new(),
+            looping: false,
+            flushed: false,
+            position: 0,
+            output_channels,
+            output_rate,
+            data_start,
+            data_len,
+            reader,
+            mixer,
+            spec,
+        })
+    }
+
+    fn parse_format(fmt: &[u8]) -> Result<WavSpec, WavError> {
+        let mut format = read_u16(fmt, 0);
+        if format == FORMAT_EXTENSIBLE {
+            if fmt.len() < 40 {
+                return Err(WavError::InvalidHeader("extensible fmt chunk too short"));
+            }
+
+            // The sub format guid starts with the actual format tag.
+            format = read_u16(fmt, 24);
+        }
+
+        let spec = WavSpec {
+            channels: read_u16(fmt, 2),
+            sample_rate: read_u32(fmt, 4),
+            bits_per_sample: read_u16(fmt, 14),
+            float: format == FORMAT_FLOAT,
+        };
+
+        let supported = match format {
+            FORMAT_PCM => matches!(spec.bits_per_sample, 8 | 16 | 24 | 32),
+            FORMAT_FLOAT => matches!(spec.bits_per_sample, 32 | 64),
+            _ => false,
+        };
+
+        if !supported || spec.channels == 0 || spec.sample_rate == 0 {
+            return Err(WavError::UnsupportedFormat(format!(
+                "format {} with {} bits",
+                format, spec.bits_per_sample
+            )));
+        }
+
+        Ok(spec)
+    }
+
+    /// The format of the file.
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// The length of the audio in the file.
+    pub fn duration(&self) -> Duration {
+        let frames = self.data_len / self.spec.block_align() as u64;
+        Duration::from_secs_f64(frames as f64 / self.spec.sample_rate as f64)
+    }
+
+    /// Resample the audio to the given rate, a multiple of 100.
+    pub fn set_output_rate(&mut self, sample_rate: usize) {
+        assert_eq!(sample_rate % 100, 0);
+        self.output_rate = sample_rate;
+        self.resampler = Resampler::new(
+            self.spec.sample_rate as usize,
+            sample_rate,
+            self.output_channels,
+        );
+    }
+
+    /// Start from the beginning again after the end of the file.
+    pub fn set_looping(&mut self, enable: bool) {
+        self.looping = enable;
+    }
+
+    /// Continue reading at the beginning of the file.
+    pub fn rewind(&mut self) -> Result<(), WavError> {
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        self.resampler.reset();
+        self.fifo.clear();
+        self.flushed = false;
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Convert up to 10ms of the file into the fifo, returns false at the
+    /// end of the audio.
+    fn fill(&mut self) -> Result<bool, WavError> {
+        let block_align = self.spec.block_align();
+        let remaining = (self.data_len - self.position) as usize / block_align;
+        if remaining == 0 {
+            if self.looping && self.position > 0 {
+                self.reader.seek(SeekFrom::Start(self.data_start))?;
+                self.position = 0;
+                return Ok(true);
+            }
+
+            if self.flushed {
+                return Ok(false);
+            }
+
+            // Push silence through the filter to get the tail of the audio.
+            self.flushed = true;
+            let channels = self.output_channels as usize;
+            let frames =
+                self.resampler.latency() * self.spec.sample_rate as usize / self.output_rate + 1;
+            let mut output = Vec::new();
+            self.resampler
+                .process(&vec![0; frames * channels], &mut output);
+            self.fifo.extend(output);
+            return Ok(true);
+        }
+
+        let frames = remaining.min((self.spec.sample_rate as usize / 100).max(1));
+        let mut bytes = vec![0u8; frames * block_align];
+        self.reader.read_exact(&mut bytes)?;
+        self.position += bytes.len() as u64;
+
+        let bytes_per_sample = self.spec.bits_per_sample as usize / 8;
+        let samples = bytes
+            .chunks_exact(bytes_per_sample)
+            .map(|sample| self.spec.decode(sample))
+            .collect::<Vec<i16>>();
+
+        let mut output = Vec::with_capacity(samples.len() * 2);
+        match &self.mixer {
+            Some(mixer) => {
+                let mut mixed = Vec::with_capacity(frames * 2);
+                mixer.process(&samples, &mut mixed);
+                self.resampler.process(&mixed, &mut output);
+            }
+            None => self.resampler.process(&samples, &mut output),
+        }
+
+        self.fifo.extend(output);
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek + Send> AudioSource for WavReader<R> {
+    fn sample_rate(&self) -> usize {
+        self.output_rate
+    }
+
+    fn channels(&self) -> u8 {
+        self.output_channels
+    }
+
+    fn read(&mut self, buf: &mut [i16]) -> usize {
+        while self.fifo.len() < buf.len() {
+            // Read errors end the source like the end of the file.
+            if !self.fill().unwrap_or(false) {
+                break;
+            }
+        }
+
+        let channels = self.output_channels as usize;
+        let size = self.fifo.len().min(buf.len()) / channels * channels;
+        for (sample, value) in buf.iter_mut().zip(self.fifo.drain(..size)) {
+            *sample = value;
+        }
+
+        size
+    }
+}
+
+struct WavWriterState<W: Write + Seek> {
+    writer: W,
+    sample_rate: usize,
+    channels: u8,
+    converter: FormatConverter,
+    data_len: u64,
+    stopped: bool,
+    error: Option<WavError>,
+}
+
+impl<W: Write + Seek> WavWriterState<W> {
+    fn write_header(&mut self) -> io::Result<()> {
+        let data_len = self.data_len.min((u32::MAX as u64) - HEADER_LEN) as u32;
+        let channels = self.channels as u16;
+        let sample_rate = self.sample_rate as u32;
+        let block_align = channels * 2;
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(data_len + HEADER_LEN as u32 - 8).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&FORMAT_PCM.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_len.to_le_bytes());
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&header)?;
+        self.writer
+            .seek(SeekFrom::Start(HEADER_LEN + self.data_len))?;
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &AudioFrame) -> io::Result<()> {
+        let samples = match self.converter.convert(frame) {
+            Some(samples) => samples,
+            None => return Ok(()),
+        };
+
+        let bytes = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+
+        self.writer.write_all(&bytes)?;
+        self.data_len += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriterState<W> {
+    fn drop(&mut self) {
+        let _ = self.finalize();
+    }
+}
+
+/// Records audio frames into a 16 bit pcm wav file.
+///
+/// The writer is an audio track sink, clones share the same file. Frames of
+/// any format are converted to the format of the file. The header is
+/// completed when the last clone is dropped, or on `finalize`, so the file
+/// is valid even if recording stops unexpectedly between two finalizes.
+///
+/// To record several tracks into one file, mix them first:
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let remote_track = AudioTrack::new("remote")?;
+/// let writer = WavWriter::create("voicemail.wav", 16000, 1)?;
+/// let mixer = AudioMixer::new(AudioMixerOptions::default());
+/// mixer.add_sink_output(Sinker::new(writer.clone()), None);
+///
+/// let input = mixer.add_input();
+/// let _sink = remote_track.add_sink(Sinker::new(input));
+/// # Ok(())
+/// # }
+/// ```
+pub struct WavWriter<W: Write + Seek = BufWriter<File>> {
+    state: Arc<Mutex<WavWriterState<W>>>,
+}
+
+impl<W: Write + Seek> Clone for WavWriter<W> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Create a wav file with the given format.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        sample_rate: usize,
+        channels: u8,
+    ) -> Result<Self, WavError> {
+        Self::new(BufWriter::new(File::create(path)?), sample_rate, channels)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Write wav into any seekable writer, mono, stereo and 5.1 are
+    /// supported.
+    pub fn new(writer: W, sample_rate: usize, channels: u8) -> Result<Self, WavError> {
+        if ChannelLayout::from_channels(channels).is_none() {
+            return Err(WavError::UnsupportedFormat(format!(
+                "{} channels",
+                channels
+            )));
+        }
+
+        let mut state = WavWriterState {
+            converter: FormatConverter::new(sample_rate, channels),
+            stopped: false,
+            error: None,
+            data_len: 0,
+            sample_rate,
+            channels,
+            writer,
+        };
+
+        state.write_header()?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Write one frame.
+    pub fn write(&self, frame: &AudioFrame) -> Result<(), WavError> {
+        Ok(self.state.lock().unwrap().write_frame(frame)?)
+    }
+
+    /// The length of the recorded audio.
+    pub fn duration(&self) -> Duration {
+        let state = self.state.lock().unwrap();
+        let frames = state.data_len / (state.channels as u64 * 2);
+        Duration::from_secs_f64(frames as f64 / state.sample_rate as f64)
+    }
+
+    /// Take the error that stopped the sink, if any.
+    pub fn error(&self) -> Option<WavError> {
+        self.state.lock().unwrap().error.take()
+    }
+
+    /// Update the header with the current length and flush, recording can
+    /// continue afterwards.
+    pub fn finalize(&self) -> Result<(), WavError> {
+        Ok(self.state.lock().unwrap().finalize()?)
+    }
+}
+
+impl<W: Write + Seek + Send> SinkExt for WavWriter<W> {
+    type Item = Arc<AudioFrame>;
+
+    fn on_data(&self, frame: Arc<AudioFrame>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.stopped {
+            if let Err(e) = state.write_frame(&frame) {
+                let _ = state.error.insert(e.into());
+                state.stopped = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedCursor(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+    impl Write for SharedCursor {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for SharedCursor {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.0.lock().unwrap().seek(pos)
+        }
+    }
+
+    fn read_all<R: Read + Seek + Send>(mut reader: WavReader<R>, len: usize) -> Vec<i16> {
+        let mut samples = vec![0; len];
+        assert_eq!(reader.read(&mut samples), len);
+        samples
+    }
+
+    #[test]
+    fn pcm16_round_trip() {
+        let file = SharedCursor::default();
+        let writer = WavWriter::new(file.clone(), 48000, 2).unwrap();
+        let samples = (0..960)
+            .map(|i| (i * 31 - 15000) as i16)
+            .collect::<Vec<_>>();
+        writer
+            .write(&AudioFrame::from_pcm(48000, 2, 0, samples.clone()))
+            .unwrap();
+        drop(writer);
+
+        let bytes = file.0.lock().unwrap().get_ref().clone();
+        let reader = WavReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            reader.spec(),
+            WavSpec {
+                sample_rate: 48000,
+                channels: 2,
+                bits_per_sample: 16,
+                float: false,
+            }
+        );
+        assert_eq!(reader.duration(), Duration::from_millis(10));
+        assert_eq!(read_all(reader, samples.len()), samples);
+    }
+
+    #[test]
+    fn float_round_trip() {
+        let values = [0.0f32, 0.5, -0.5, 1.0, -1.0, 0.25];
+
+        // A float fmt chunk with an empty extension, as many tools write.
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(4 + 26 + 8 + values.len() as u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&18u32.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_FLOAT.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&48000u32.to_le_bytes());
+        bytes.extend_from_slice(&(48000u32 * 4).to_le_bytes());
+        bytes.extend_from_slice(&4u16.to_le_bytes());
+        bytes.extend_from_slice(&32u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(values.len() as u32 * 4).to_le_bytes());
+        for value in values {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let reader = WavReader::new(Cursor::new(bytes)).unwrap();
+        assert!(reader.spec().float);
+        assert_eq!(
+            read_all(reader, values.len()),
+            [0, 16384, -16384, 32767, -32768, 8192]
+        );
+    }
+
+    #[test]
+    fn header_after_drop() {
+        let file = SharedCursor::default();
+        let writer = WavWriter::new(file.clone(), 16000, 1).unwrap();
+        for timestamp in [0, 10, 20] {
+            writer
+                .write(&AudioFrame::from_pcm(16000, 1, timestamp, vec![1; 160]))
+                .unwrap();
+        }
+
+        let clone = writer.clone();
+        drop(writer);
+        drop(clone);
+
+        let bytes = file.0.lock().unwrap().get_ref().clone();
+        assert_eq!(bytes.len(), HEADER_LEN as usize + 960);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4), 36 + 960);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(read_u32(&bytes, 16), 16);
+        assert_eq!(read_u16(&bytes, 20), FORMAT_PCM);
+        assert_eq!(read_u16(&bytes, 22), 1);
+        assert_eq!(read_u32(&bytes, 24), 16000);
+        assert_eq!(read_u32(&bytes, 28), 32000);
+        assert_eq!(read_u16(&bytes, 32), 2);
+        assert_eq!(read_u16(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40), 960);
+    }
+}
@@ -0,0 +1,557 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{pacer::Pacer, I420Buffer, SinkExt, VideoFrame, VideoSource};
+
+const SIGNATURE: &str = "YUV4MPEG2";
+const FRAME_HEADER: &[u8] = b"FRAME\n";
+
+/// Headers longer than this are not y4m.
+const MAX_HEADER_LEN: usize = 1024;
+
+/// Frames wider or taller than this are rejected as a corrupt header.
+const MAX_DIMENSION: u32 = 16384;
+
+#[derive(Debug)]
+pub enum Y4mError {
+    Io(io::Error),
+    /// The stream does not start with a valid y4m header.
+    InvalidHeader(String),
+    /// A frame does not start with a frame header.
+    InvalidFrame(u64),
+    /// Only 4:2:0 streams can be read.
+    UnsupportedColorspace(String),
+    /// The frame to seek to is past the end of the stream.
+    FrameOutOfRange(u64),
+}
+
+impl Error for Y4mError {}
+
+impl fmt::Display for Y4mError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "y4m io error: {}", e),
+            Self::InvalidHeader(header) => write!(f, "invalid y4m header: {:?}", header),
+            Self::InvalidFrame(index) => write!(f, "invalid y4m frame header at frame {}", index),
+            Self::UnsupportedColorspace(colorspace) => {
+                write!(f, "unsupported y4m colorspace: {}", colorspace)
+            }
+            Self::FrameOutOfRange(index) => write!(f, "y4m frame {} is out of range", index),
+        }
+    }
+}
+
+impl From<io::Error> for Y4mError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// The stream parameters of a y4m file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Y4mHeader {
+    pub width: u32,
+    pub height: u32,
+    /// Frame rate as numerator and denominator.
+    pub frame_rate: (u32, u32),
+}
+
+impl Y4mHeader {
+    fn parse(line: &str) -> Result<Self, Y4mError> {
+        let invalid = || Y4mError::InvalidHeader(line.to_string());
+        let mut tokens = line.split_ascii_whitespace();
+        if tokens.next() != Some(SIGNATURE) {
+            return Err(invalid());
+        }
+
+        let mut header = Self {
+            width: 0,
+            height: 0,
+            frame_rate: (30, 1),
+        };
+
+        for token in tokens {
+            // Tokens are never empty, the tag is the first character.
+            let mut chars = token.chars();
+            let key = chars.next().ok_or_else(invalid)?;
+            let value = chars.as_str();
+            match key {
+                'W' => header.width = value.parse().map_err(|_| invalid())?,
+                'H' => header.height = value.parse().map_err(|_| invalid())?,
+                'F' => {
+                    let (num, den) = value.split_once(':').ok_or_else(invalid)?;
+                    header.frame_rate = (
+                        num.parse().map_err(|_| invalid())?,
+                        den.parse().map_err(|_| invalid())?,
+                    );
+                }
+                'C' if !value.starts_with("420") => {
+                    return Err(Y4mError::UnsupportedColorspace(value.to_string()));
+                }
+                _ if !key.is_ascii() => return Err(invalid()),
+                // All 4:2:0 variants, interlacing, aspect ratio and
+                // extensions share the same layout of the planes.
+                _ => (),
+            }
+        }
+
+        if header.width == 0 || header.height == 0 || header.frame_rate.0 == 0 {
+            return Err(invalid());
+        }
+
+        if header.width > MAX_DIMENSION
+            || header.height > MAX_DIMENSION
+            || header.plane_sizes().is_none()
+        {
+            return Err(invalid());
+        }
+
+        Ok(header)
+    }
+
+    /// The size of the luma and of one chroma plane, `None` if they do not
+    /// fit in memory.
+    fn plane_sizes(&self) -> Option<(usize, usize)> {
+        let luma = (self.width as usize).checked_mul(self.height as usize)?;
+        let chroma =
+            (self.width.div_ceil(2) as usize).checked_mul(self.height.div_ceil(2) as usize)?;
+        luma.checked_add(chroma.checked_mul(2)?)?;
+        Some((luma, chroma))
+    }
+
+    /// The size of the planes of one frame.
+    fn frame_size(&self) -> usize {
+        let (luma, chroma) = self.plane_sizes().expect("checked by the header parser");
+        luma + chroma * 2
+    }
+
+    /// The duration of one frame.
+    fn frame_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frame_rate.1.max(1) as f64 / self.frame_rate.0 as f64)
+    }
+}
+
+impl fmt::Display for Y4mHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} W{} H{} F{}:{} Ip A1:1 C420jpeg",
+            SIGNATURE, self.width, self.height, self.frame_rate.0, self.frame_rate.1
+        )
+    }
+}
+
+/// Reads raw video from a y4m file, as a video source or frame by frame.
+///
+/// As a video source the reader paces the frames to the frame rate of the
+/// file and can loop forever.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut reader = Y4mReader::open("foreman_cif.y4m")?;
+/// reader.set_looping(true);
+///
+/// let track = VideoTrack::new("replay")?;
+/// let driver = VideoSourceDriver::spawn(reader, track.clone());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Y4mReader<R> {
+    reader: R,
+    header: Y4mHeader,
+    // Offset of the first frame header.
+    data_start: u64,
+    position: u64,
+    looping: bool,
+    pacer: Option<Pacer>,
+}
+
+impl Y4mReader<BufReader<File>> {
+    /// Open a y4m file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Y4mError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> Y4mReader<R> {
+    /// Read the stream header, pacing is enabled and looping disabled.
+    pub fn new(mut reader: R) -> Result<Self, Y4mError> {
+        let line = read_line(&mut reader)?.ok_or_else(|| Y4mError::InvalidHeader(String::new()))?;
+
+        let header = Y4mHeader::parse(&line)?;
+        Ok(Self {
+            pacer: Some(Pacer::new(header.frame_duration())),
+            data_start: reader.stream_position()?,
+            looping: false,
+            position: 0,
+            reader,
+            header,
+        })
+    }
+
+    /// The stream parameters.
+    pub fn header(&self) -> Y4mHeader {
+        self.header
+    }
+
+    /// Start from the first frame again after the last one.
+    pub fn set_looping(&mut self, enable: bool) {
+        self.looping = enable;
+    }
+
+    /// Deliver frames at the frame rate of the file, otherwise as fast as
+    /// they are read.
+    pub fn set_pacing(&mut self, enable: bool) {
+        self.pacer = if enable {
+            Some(Pacer::new(self.header.frame_duration()))
+        } else {
+            None
+        };
+    }
+
+    /// The index of the next frame.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The number of frames in the stream, assuming frames without frame
+    /// parameters.
+    pub fn frame_count(&mut self) -> Result<u64, Y4mError> {
+        let current = self.reader.stream_position()?;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(current))?;
+        Ok((end - self.data_start) / (FRAME_HEADER.len() + self.header.frame_size()) as u64)
+    }
+
+    /// Continue reading at the given frame.
+    ///
+    /// Seeking computes the offset from the frame size, so it only works
+    /// for streams without frame parameters, which is what practically all
+    /// tools write.
+    pub fn seek(&mut self, index: u64) -> Result<(), Y4mError> {
+        if index >= self.frame_count()? {
+            return Err(Y4mError::FrameOutOfRange(index));
+        }
+
+        let stride = (FRAME_HEADER.len() + self.header.frame_size()) as u64;
+        self.reader
+            .seek(SeekFrom::Start(self.data_start + index * stride))?;
+        self.position = index;
+        Ok(())
+    }
+
+    /// Read the next frame without pacing, returns `None` at the end of the
+    /// stream.
+    pub fn read_frame(&mut self) -> Result<Option<I420Buffer>, Y4mError> {
+        let line = match read_line(&mut self.reader)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        if !line.starts_with("FRAME") {
+            return Err(Y4mError::InvalidFrame(self.position));
+        }
+
+        let (width, height) = (self.header.width, self.header.height);
+        let (luma, chroma) = self
+            .header
+            .plane_sizes()
+            .expect("checked by the header parser");
+        let mut planes = [vec![0u8; luma], vec![0u8; chroma], vec![0u8; chroma]];
+        for plane in planes.iter_mut() {
+            match self.reader.read_exact(plane) {
+                Ok(()) => (),
+                // A truncated last frame ends the stream.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.position += 1;
+        let [y, u, v] = planes;
+        Ok(I420Buffer::from_planes(width, height, y, u, v))
+    }
+}
+
+impl<R: Read + Seek + Send> VideoSource for Y4mReader<R> {
+    fn next_frame(&mut self) -> Option<I420Buffer> {
+        if let Some(pacer) = self.pacer.as_mut() {
+            pacer.wait();
+        }
+
+        match self.read_frame() {
+            Ok(Some(frame)) => Some(frame),
+            Ok(None) if self.looping && self.position > 0 => {
+                self.seek(0).ok()?;
+                self.read_frame().ok().flatten()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Read one header line, returns `None` at the end of the stream.
+fn read_line<R: Read>(reader: &mut R) -> Result<Option<String>, Y4mError> {
+    let mut line = Vec::with_capacity(64);
+    let mut byte = [0u8];
+    loop {
+        if reader.read(&mut byte)? == 0 {
+            if line.is_empty() {
+                return Ok(None);
+            }
+
+            break;
+        }
+
+        if byte[0] == b'\n' {
+            break;
+        }
+
+        line.push(byte[0]);
+        if line.len() > MAX_HEADER_LEN {
+            return Err(Y4mError::InvalidHeader(
+                String::from_utf8_lossy(&line).into_owned(),
+            ));
+        }
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| Y4mError::InvalidHeader(String::from_utf8_lossy(e.as_bytes()).into_owned()))
+}
+
+struct WriterState<W: Write> {
+    writer: W,
+    header: Option<Y4mHeader>,
+    frames: u64,
+    stopped: bool,
+    error: Option<Y4mError>,
+}
+
+impl<W: Write> WriterState<W> {
+    fn write_frame(&mut self, frame: &VideoFrame, frame_rate: (u32, u32)) -> io::Result<()> {
+        // The stream takes the size of the first frame, later frames of a
+        // different size are scaled to it.
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                let header = Y4mHeader {
+                    width: frame.width(),
+                    height: frame.height(),
+                    frame_rate,
+                };
+
+                writeln!(self.writer, "{}", header)?;
+                *self.header.insert(header)
+            }
+        };
+
+        let buffer = I420Buffer::from_frame_scaled(frame, header.width, header.height);
+        self.writer.write_all(FRAME_HEADER)?;
+        self.writer.write_all(buffer.data_y())?;
+        self.writer.write_all(buffer.data_u())?;
+        self.writer.write_all(buffer.data_v())?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// Writes received video frames into a y4m file.
+///
+/// The writer is a video track sink, clones share the same file. Writing
+/// stops at the first error, which is kept for `error`.
+///
+/// ```no_run
+/// # use librtc::*;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let track = VideoTrack::new("remote")?;
+/// let writer = Y4mWriter::create("received.y4m", (30, 1))?;
+/// let _sink = track.add_sink(Sinker::new(writer.clone()));
+///
+/// // later
+/// writer.flush()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Y4mWriter<W: Write = BufWriter<File>> {
+    state: Arc<Mutex<WriterState<W>>>,
+    frame_rate: (u32, u32),
+}
+
+impl<W: Write> Clone for Y4mWriter<W> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            frame_rate: self.frame_rate,
+        }
+    }
+}
+
+impl Y4mWriter<BufWriter<File>> {
+    /// Create a y4m file, the frame rate is written to the header as
+    /// numerator and denominator.
+    pub fn create<P: AsRef<Path>>(path: P, frame_rate: (u32, u32)) -> Result<Self, Y4mError> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), frame_rate))
+    }
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Write y4m into any writer, the header is written with the first
+    /// frame.
+    pub fn new(writer: W, frame_rate: (u32, u32)) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(WriterState {
+                header: None,
+                stopped: false,
+                error: None,
+                frames: 0,
+                writer,
+            })),
+            frame_rate,
+        }
+    }
+
+    /// Write one frame.
+    pub fn write(&self, frame: &VideoFrame) -> Result<(), Y4mError> {
+        let mut state = self.state.lock().unwrap();
+        Ok(state.write_frame(frame, self.frame_rate)?)
+    }
+
+    /// The number of frames written.
+    pub fn frames_written(&self) -> u64 {
+        self.state.lock().unwrap().frames
+    }
+
+    /// Take the error that stopped the sink, if any.
+    pub fn error(&self) -> Option<Y4mError> {
+        self.state.lock().unwrap().error.take()
+    }
+
+    /// Flush the buffered frames to the file.
+    pub fn flush(&self) -> Result<(), Y4mError> {
+        Ok(self.state.lock().unwrap().writer.flush()?)
+    }
+}
+
+impl<W: Write + Send> SinkExt for Y4mWriter<W> {
+    type Item = Arc<VideoFrame>;
+
+    fn on_data(&self, frame: Arc<VideoFrame>) {
+        let mut state = self.state.lock().unwrap();
+        if !state.stopped {
+            if let Err(e) = state.write_frame(&frame, self.frame_rate) {
+                let _ = state.error.insert(e.into());
+                state.stopped = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn header() {
+        let header = Y4mHeader::parse(
+            "YUV4MPEG2 W352 H288 F30000:1001 It A128:117 C420mpeg2 XYSCSS=420MPEG2",
+        )
+        .unwrap();
+        assert_eq!(header.width, 352);
+        assert_eq!(header.height, 288);
+        assert_eq!(header.frame_rate, (30000, 1001));
+        assert_eq!(Y4mHeader::parse(&header.to_string()).unwrap(), header);
+
+        for line in [
+            "YUV4MPEG W352 H288",
+            "YUV4MPEG2 W352",
+            "YUV4MPEG2 W352 H288 F30",
+            "YUV4MPEG2 W352 H288 Fx:1",
+            "YUV4MPEG2 W352 H288 ÄW1",
+            "YUV4MPEG2 W352 H288 €",
+            "YUV4MPEG2 W70000 H70000",
+            "YUV4MPEG2 W352 H4294967295",
+        ] {
+            assert!(
+                matches!(Y4mHeader::parse(line), Err(Y4mError::InvalidHeader(_))),
+                "{}",
+                line
+            );
+        }
+
+        assert!(matches!(
+            Y4mHeader::parse("YUV4MPEG2 W352 H288 C444"),
+            Err(Y4mError::UnsupportedColorspace(_))
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        for (width, height) in [(4, 4), (5, 3)] {
+            let buffer = SharedBuffer::default();
+            let writer = Y4mWriter::new(buffer.clone(), (25, 1));
+            let mut frames = Vec::new();
+            for index in 0..3u8 {
+                let mut frame = I420Buffer::new(width, height);
+                let (y, u, v) = frame.planes_mut();
+                y.iter_mut()
+                    .enumerate()
+                    .for_each(|(i, p)| *p = i as u8 + index);
+                u.iter_mut()
+                    .enumerate()
+                    .for_each(|(i, p)| *p = 100 + i as u8);
+                v.iter_mut()
+                    .enumerate()
+                    .for_each(|(i, p)| *p = 200 + i as u8);
+                writer
+                    .write(&VideoFrame::from_buffer(frame.clone(), 0))
+                    .unwrap();
+                frames.push(frame);
+            }
+
+            let data = buffer.0.lock().unwrap().clone();
+            let mut reader = Y4mReader::new(Cursor::new(data)).unwrap();
+            assert_eq!(reader.header().width, width);
+            assert_eq!(reader.header().height, height);
+            assert_eq!(reader.header().frame_rate, (25, 1));
+            assert_eq!(reader.frame_count().unwrap(), 3);
+
+            for frame in &frames {
+                let read = reader.read_frame().unwrap().unwrap();
+                assert_eq!(read.data_y(), frame.data_y());
+                assert_eq!(read.data_u(), frame.data_u());
+                assert_eq!(read.data_v(), frame.data_v());
+            }
+
+            assert!(reader.read_frame().unwrap().is_none());
+            reader.seek(1).unwrap();
+            assert_eq!(
+                reader.read_frame().unwrap().unwrap().data_y(),
+                frames[1].data_y()
+            );
+        }
+    }
+}